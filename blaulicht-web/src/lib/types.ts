@@ -2,3 +2,72 @@ export interface DMXData {
     // Length = 255.
     channels: Uint8Array
 }
+
+//
+// Websocket protocol, mirrors `src/routes/ws.rs`.
+//
+
+// Keep in sync with `WS_PROTOCOL_VERSION`.
+export const PROTOCOL_VERSION = 2;
+
+export type MidiLearnTarget =
+  | { Matrix: { x: number; y: number } }
+  | { Control: { name: string; mode?: "toggle" | "momentary" | "fader" | "encoder" } };
+
+export type MidiLearnStatus =
+  | { state: "Waiting"; target: MidiLearnTarget }
+  | { state: "Learned"; name: string; device: string | null; input: { type: string }; mode: string }
+  | { state: "Cancelled" }
+  | { state: "Failed"; message: string };
+
+export type LogLevel = "Trace" | "Debug" | "Info" | "Warn" | "Error";
+
+export interface LogEntry {
+  // Milliseconds since the Unix epoch.
+  timestamp: number;
+  source: "Host" | "Wasm";
+  level: LogLevel;
+  message: string;
+}
+
+export interface MatrixEvent {
+  device: number;
+  x: number;
+  y: number;
+  value: boolean;
+}
+
+// Named control, handled by the engine like a control of the MIDI mapping.
+export interface ControlEvent {
+  name: string;
+  value: { Bool: boolean } | { Float: number } | { Relative: number };
+}
+
+// Tempo after tap tempo and manual overrides, `Blend` mixes both with the analyzed tempo.
+export type TempoSource = "Analyzed" | "Tap" | "Manual" | "Blend";
+
+export interface TempoStatus {
+  bpm: number;
+  source: TempoSource;
+}
+
+export type WSTopic = "Signals" | "Logs" | "Controls" | "Dmx" | "Stats";
+
+// Max messages per second and kind, `null` for no limit.
+export type WSSubscriptions = Partial<Record<WSTopic, number | null>>;
+
+export type WSRequest =
+  | { kind: "Hello"; value: { version: number } }
+  | { kind: "SelectAudioDevice"; value: string | null }
+  | { kind: "SelectSerialDevice"; value: string | null }
+  | { kind: "Reload"; value?: null }
+  | { kind: "MatrixControl"; value: MatrixEvent }
+  | { kind: "Control"; value: ControlEvent }
+  | { kind: "MidiLearn"; value: MidiLearnTarget }
+  | { kind: "MidiLearnCancel"; value?: null }
+  | { kind: "Subscribe"; value: WSSubscriptions };
+
+export type WSProtocolMessage =
+  | { kind: "Hello"; value: { version: number } }
+  | { kind: "Reply"; value: { id: number } }
+  | { kind: "Error"; value: { id: number | null; message: string } };
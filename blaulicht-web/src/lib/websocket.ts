@@ -4,19 +4,41 @@
 
 const WS_PATH = "api/ws";
 
-import type { DMXData } from "./types";
+// The server token is passed along in the dashboard URL, e.g. `/dash?token=...`.
+export function authToken(): string | null {
+  return new URLSearchParams(document.location.search).get("token");
+}
+
+export function authHeaders(): HeadersInit {
+  const token = authToken();
+  return token ? { Authorization: `Bearer ${token}` } : {};
+}
+
+import {
+  PROTOCOL_VERSION,
+  type DMXData,
+  type LogEntry,
+  type MidiLearnStatus,
+  type TempoStatus,
+  type WSProtocolMessage,
+  type WSRequest,
+  type WSSubscriptions,
+} from "./types";
+
+export type { LogEntry, MidiLearnStatus, MidiLearnTarget, TempoStatus } from "./types";
 
 export enum TopicKind {
   BPM = "Bpm",
   DMX = "Dmx",
   Heartbeat = "Heartbeat",
   AudioDevicesView = "AudioDevicesView",
-  AudioDeviceSelected = "SelectAudioDevice",
+  AudioDeviceSelected = "AudioSelected",
   Log = "Log",
-  WasmLog = "WasmLog",
+  LogBacklog = "LogBacklog",
   WasmControlsLog = "WasmControlsLog",
   WasmControlsSet = "WasmControlsSet",
   WasmControlsConfig = "WasmControlsConfig",
+  MidiLearn = "MidiLearn",
   Volume = "Volume",
   Bass = "Bass",
   BassAvgShort = "BassAvgShort",
@@ -24,16 +46,14 @@ export enum TopicKind {
   BeatVolume = "BeatVolume",
   LoopSpeed = "LoopSpeed",
   TickSpeed = "TickSpeed",
+  Tempo = "Tempo",
 }
 
 //
 // Send events
 //
 
-export interface SendEvent {
-  kind: "SelectAudioDevice" | "SelectSerialDevice" | "Reload" | "MatrixControl"
-  value: any;
-}
+export type SendEvent = WSRequest;
 
 //
 // End send events.
@@ -67,8 +87,8 @@ export function topicLog(): Topic<TopicKind.Log> {
   return { kind: TopicKind.Log };
 }
 
-export function topicWasmLog(): Topic<TopicKind.WasmLog> {
-  return { kind: TopicKind.WasmLog };
+export function topicLogBacklog(): Topic<TopicKind.LogBacklog> {
+  return { kind: TopicKind.LogBacklog };
 }
 
 export function topicWasmControlsLog(): Topic<TopicKind.WasmControlsLog> {
@@ -83,6 +103,10 @@ export function topicWasmControlsConfig(): Topic<TopicKind.WasmControlsConfig> {
   return { kind: TopicKind.WasmControlsConfig };
 }
 
+export function topicMidiLearn(): Topic<TopicKind.MidiLearn> {
+  return { kind: TopicKind.MidiLearn };
+}
+
 export function topicVolume(): Topic<TopicKind.Volume> {
   return { kind: TopicKind.Volume };
 }
@@ -111,6 +135,10 @@ export function topicTickSpeed(): Topic<TopicKind.TickSpeed> {
   return { kind: TopicKind.TickSpeed };
 }
 
+export function topicTempo(): Topic<TopicKind.Tempo> {
+  return { kind: TopicKind.Tempo };
+}
+
 export type UpdateMessage<T> = T extends TopicKind.DMX
   ? { kind: Topic<T>; value: number[] }
   : T extends TopicKind.Heartbeat
@@ -120,15 +148,17 @@ export type UpdateMessage<T> = T extends TopicKind.DMX
   : T extends TopicKind.AudioDeviceSelected
   ? { kind: Topic<T>; value: string }
   : T extends TopicKind.Log
-  ? { kind: Topic<T>; value: string }
-  : T extends TopicKind.WasmLog
-  ? { kind: Topic<T>; value: string }
+  ? { kind: Topic<T>; value: LogEntry }
+  : T extends TopicKind.LogBacklog
+  ? { kind: Topic<T>; value: LogEntry[] }
   : T extends TopicKind.WasmControlsLog
   ? { kind: Topic<T>; value: { x: number; y: number; value: string } }
   : T extends TopicKind.WasmControlsSet
   ? { kind: Topic<T>; value: { x: number; y: number; value: boolean } }
   : T extends TopicKind.WasmControlsConfig
   ? { kind: Topic<T>; value: { x: number; y: number } }
+  : T extends TopicKind.MidiLearn
+  ? { kind: Topic<T>; value: MidiLearnStatus }
   : T extends TopicKind.Volume
   ? { kind: Topic<T>; value: number }
   : T extends TopicKind.Bass
@@ -145,6 +175,8 @@ export type UpdateMessage<T> = T extends TopicKind.DMX
   ? { kind: Topic<T>; value: number }
   : T extends TopicKind.TickSpeed
   ? { kind: Topic<T>; value: number }
+  : T extends TopicKind.Tempo
+  ? { kind: Topic<T>; value: TempoStatus }
   : never;
 
 type OnMessageCallBack<T extends TopicKind> = (data: UpdateMessage<T>) => void;
@@ -197,8 +229,12 @@ export class BlaulichtWebsocket {
   socket: WebSocket;
   isReady: boolean = false;
   callbacks: BlaulichtWebsocketCallbacks;
+  nextId: number = 0;
+  pending: Map<number, { resolve: () => void; reject: (message: string) => void }> =
+    new Map();
 
-  constructor(callbacksP: BlaulichtWebsocketCallbacks) {
+  // Without subscriptions the server sends everything except DMX frames.
+  constructor(callbacksP: BlaulichtWebsocketCallbacks, subscriptions?: WSSubscriptions) {
     this.callbacks = callbacksP;
     let protocol = undefined;
     const host = document.location.host;
@@ -216,11 +252,19 @@ export class BlaulichtWebsocket {
     }
 
     let url = `${protocol}//${host}/${WS_PATH}`;
+    const token = authToken();
+    if (token) {
+      url += `?token=${encodeURIComponent(token)}`;
+    }
 
     this.socket = new WebSocket(url);
 
     this.socket.onopen = () => {
       this.isReady = true;
+      this.send({ kind: "Hello", value: { version: PROTOCOL_VERSION } });
+      if (subscriptions) {
+        this.send({ kind: "Subscribe", value: subscriptions });
+      }
       this.sync();
     };
 
@@ -239,11 +283,41 @@ export class BlaulichtWebsocket {
     };
   }
 
-  private onMessage(data: UpdateMessage<TopicKind>) {
+  private onMessage(data: UpdateMessage<TopicKind> | WSProtocolMessage) {
+    if (this.onProtocolMessage(data as WSProtocolMessage)) {
+      return;
+    }
+
     const topicStr = JSON.stringify({ kind: data.kind });
     this.callbacks.trigger(topicStr, data);
   }
 
+  // Returns false for messages which are not part of the protocol itself.
+  private onProtocolMessage(message: WSProtocolMessage): boolean {
+    switch (message.kind) {
+      case "Hello":
+        if (message.value.version !== PROTOCOL_VERSION) {
+          console.error(
+            `WS: server speaks protocol ${message.value.version}, expected ${PROTOCOL_VERSION}`
+          );
+        }
+        return true;
+      case "Reply":
+        this.pending.get(message.value.id)?.resolve();
+        this.pending.delete(message.value.id);
+        return true;
+      case "Error":
+        console.error(`WS: ${message.value.message}`);
+        if (message.value.id !== null) {
+          this.pending.get(message.value.id)?.reject(message.value.message);
+          this.pending.delete(message.value.id);
+        }
+        return true;
+      default:
+        return false;
+    }
+  }
+
   private sync() {
     console.log("WS: SYNC");
     return;
@@ -263,7 +337,17 @@ export class BlaulichtWebsocket {
     // );
   }
 
-  send(event: SendEvent) {
-    this.socket.send(JSON.stringify(event));
+  // Resolves once the server accepted the request.
+  send(event: SendEvent): Promise<void> {
+    const id = this.nextId++;
+    const promise = new Promise<void>((resolve, reject) =>
+      this.pending.set(id, { resolve, reject })
+    );
+
+    // Errors are logged in onMessage, callers do not have to handle them.
+    promise.catch(() => {});
+
+    this.socket.send(JSON.stringify({ id, ...event }));
+    return promise;
   }
 }
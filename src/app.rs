@@ -1,6 +1,8 @@
 use cpal::Device;
 use serde::Deserialize;
 
+use crate::midi::learn::MidiLearnRequest;
+
 #[derive(Deserialize, Clone, Debug)]
 pub struct MatrixEvent {
     pub device: u8,
@@ -12,9 +14,114 @@ pub struct MatrixEvent {
 #[derive(Deserialize, Clone, Debug)]
 pub struct MidiEvent {
     pub device: u8,
-    pub status: u8,
-    pub data0: u8,
-    pub data1: u8,
+    pub message: MidiMessage,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub enum MidiMessage {
+    // Passed through undecoded, used by the builtin control matrix.
+    Raw { status: u8, data0: u8, data1: u8 },
+    NoteOff { channel: u8, note: u8, velocity: u8 },
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    PolyPressure { channel: u8, note: u8, pressure: u8 },
+    ControlChange { channel: u8, control: u8, value: u8 },
+    ProgramChange { channel: u8, program: u8 },
+    ChannelPressure { channel: u8, pressure: u8 },
+    PitchBend { channel: u8, value: u16 },
+    // MSB (CC 0-31) and LSB (CC 32-63) pair combined into one value.
+    ControlChange14 { channel: u8, control: u8, value: u16 },
+    Nrpn { channel: u8, parameter: u16, value: u16 },
+    // Complete message including the 0xF0 / 0xF7 framing.
+    SysEx(Vec<u8>),
+    // System realtime messages used for clock and transport sync.
+    Clock,
+    Start,
+    Continue,
+    Stop,
+}
+
+impl MidiMessage {
+    /// Encodes this message into one or more MIDI messages ready to be sent to a device.
+    pub fn to_messages(&self) -> Vec<Vec<u8>> {
+        let cc = |channel: u8, control: u8, value: u8| vec![0xB0 | channel, control, value];
+        let msb = |value: u16| ((value >> 7) & 0x7F) as u8;
+        let lsb = |value: u16| (value & 0x7F) as u8;
+
+        match self {
+            MidiMessage::Raw {
+                status,
+                data0,
+                data1,
+            } => vec![vec![*status, *data0, *data1]],
+            MidiMessage::NoteOff {
+                channel,
+                note,
+                velocity,
+            } => vec![vec![0x80 | channel, *note, *velocity]],
+            MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => vec![vec![0x90 | channel, *note, *velocity]],
+            MidiMessage::PolyPressure {
+                channel,
+                note,
+                pressure,
+            } => vec![vec![0xA0 | channel, *note, *pressure]],
+            MidiMessage::ControlChange {
+                channel,
+                control,
+                value,
+            } => vec![cc(*channel, *control, *value)],
+            MidiMessage::ProgramChange { channel, program } => vec![vec![0xC0 | channel, *program]],
+            MidiMessage::ChannelPressure { channel, pressure } => {
+                vec![vec![0xD0 | channel, *pressure]]
+            }
+            MidiMessage::PitchBend { channel, value } => {
+                vec![vec![0xE0 | channel, lsb(*value), msb(*value)]]
+            }
+            MidiMessage::ControlChange14 {
+                channel,
+                control,
+                value,
+            } => vec![
+                cc(*channel, *control, msb(*value)),
+                cc(*channel, *control + 32, lsb(*value)),
+            ],
+            MidiMessage::Nrpn {
+                channel,
+                parameter,
+                value,
+            } => vec![
+                cc(*channel, 99, msb(*parameter)),
+                cc(*channel, 98, lsb(*parameter)),
+                cc(*channel, 6, msb(*value)),
+                cc(*channel, 38, lsb(*value)),
+            ],
+            MidiMessage::SysEx(data) => vec![data.clone()],
+            MidiMessage::Clock => vec![vec![0xF8]],
+            MidiMessage::Start => vec![vec![0xFA]],
+            MidiMessage::Continue => vec![vec![0xFB]],
+            MidiMessage::Stop => vec![vec![0xFC]],
+        }
+    }
+}
+
+/// Named control produced by the MIDI mapping, e.g. `strobe.brightness`.
+/// Also sent directly by the dashboard and OSC.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct ControlEvent {
+    pub name: String,
+    pub value: ControlValue,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ControlValue {
+    Bool(bool),
+    // Faders, scaled into the configured range.
+    Float(f32),
+    // Encoder steps.
+    Relative(i32),
 }
 
 #[derive(Clone)]
@@ -23,4 +130,7 @@ pub enum FromFrontend {
     SelectInputDevice(Option<Device>),
     SelectSerialDevice(Option<String>),
     MatrixControl(MatrixEvent),
+    MidiLearn(MidiLearnRequest),
+    MidiLearnCancel,
+    Control(ControlEvent),
 }
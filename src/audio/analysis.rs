@@ -12,6 +12,7 @@ use itertools::Itertools;
 use crate::audio::{stream::ROLLING_AVERAGE_VOLUME_SAMPLE_SIZE, SIGNAL_SPEED};
 use crate::{
     dmx::DmxUniverse,
+    midi::MidiClock,
     msg::{BpmInfo, Signal},
     shift_push, signal, util,
 };
@@ -30,6 +31,7 @@ pub fn bass(
     bass_samples: &mut VecDeque<u8>,
     bass_modifier: u8,
     bass_peaks: &mut VecDeque<Instant>,
+    midi_clock: &MidiClock,
 ) -> anyhow::Result<()> {
     signal!(
         now,
@@ -111,12 +113,15 @@ pub fn bass(
 
             let bpm = bpm as u8;
 
+            // An external MIDI clock always wins over the detected tempo.
+            let bpm_info = midi_clock.tempo().unwrap_or(BpmInfo {
+                bpm,
+                time_between_beats_millis: (avg_bass_peak_durations * 1000.0) as u16,
+            });
+
             &[
                 Signal::Bass(bass_sig),
-                Signal::Bpm(BpmInfo {
-                    bpm,
-                    time_between_beats_millis: (avg_bass_peak_durations * 1000.0) as u16,
-                }),
+                Signal::Bpm(bpm_info),
                 if peaked || elapsed_since_last_peak < 100 {
                     Signal::BassAvgShort(255)
                 } else if bass_moving_average > 40.0 {
@@ -1,5 +1,5 @@
 use std::{
-    collections::VecDeque, mem, sync::{
+    collections::VecDeque, mem, path::PathBuf, sync::{
         atomic::{AtomicU8, Ordering},
         Arc,
     }, time::{self, Duration, Instant}
@@ -17,10 +17,10 @@ use cpal::{traits::DeviceTrait, Device};
 use log::{debug, info};
 
 use crate::{
-    app::MidiEvent, audio::{
+    app::{ControlEvent, MidiEvent}, audio::{
         analysis::{self, BASS_FRAMES, BASS_PEAK_FRAMES},
         defs::{AudioConverter, AudioThreadControlSignal},
-    }, config::Config, dmx::DmxUniverse, msg::{Signal, SystemMessage}, system_message, util
+    }, config::{Config, MidiConfig}, dmx::DmxUniverse, eventlog::LogLevel, metrics::METRICS, midi::{MidiClockOutput, MidiHandles, MidiLearn, MidiMapper}, msg::{Signal, SystemMessage}, system_message, util
 };
 
 pub const ROLLING_AVERAGE_LOOP_ITERATIONS: usize = 100;
@@ -59,14 +59,15 @@ fn init_converter(device: Device, config: StreamConfig) -> anyhow::Result<(Audio
 fn init_dmx(
     midi_out_sender: Sender<MidiEvent>,
     system_out: Sender<SystemMessage>,
+    patch: Option<PathBuf>,
 ) -> anyhow::Result<DmxUniverse> {
     debug!("[DMX] Trying to establish hardware link...");
-    let res = DmxUniverse::new(midi_out_sender.clone(), system_out.clone());
+    let res = DmxUniverse::new(midi_out_sender.clone(), system_out.clone(), patch.clone());
     let dmx_universe = match res {
         Ok(universe) => universe,
         Err(e) => {
             info!("[DMX] Failed to establish hardware link: {e}, using dummy...");
-            let Ok(universe) = DmxUniverse::new_dummy(midi_out_sender, system_out.clone()) else {
+            let Ok(universe) = DmxUniverse::new_dummy(midi_out_sender, system_out.clone(), patch) else {
                 bail!("[DMX] Failed to create dummy universe, exiting.");
             };
 
@@ -77,20 +78,53 @@ fn init_dmx(
     Ok(dmx_universe)
 }
 
+fn load_midi_mapper(
+    config: &MidiConfig,
+    midi_learn: &MidiLearn,
+    system_out: &Sender<SystemMessage>,
+    midi_out_sender: &Sender<MidiEvent>,
+) -> MidiMapper {
+    let mut mapper = match MidiMapper::load(config, midi_learn.clone()) {
+        Ok(mapper) => mapper,
+        Err(err) => {
+            system_out
+                .send(SystemMessage::log(LogLevel::Error, format!("[MIDI-MAP] {err:#}")))
+                .unwrap();
+            MidiMapper::new(Default::default(), config, midi_learn.clone())
+        }
+    };
+
+    // Blocks instead of dropping like the feedback, a controller stays in the wrong mode otherwise.
+    for event in mapper.take_init() {
+        midi_out_sender.send(event).unwrap();
+    }
+
+    mapper
+}
+
 pub fn run(
     device: Device,
     signal_out_0: Sender<Signal>,
     system_out: Sender<SystemMessage>,
     thread_control_signal: Arc<AtomicU8>,
-    midi_in_receiver: Receiver<MidiEvent>,
-    midi_out_sender: Sender<MidiEvent>,
+    midi: MidiHandles,
+    control_receiver: Receiver<ControlEvent>,
     config: Config,
 ) -> anyhow::Result<()> {
+    let MidiHandles {
+        input: midi_in_receiver,
+        output: midi_out_sender,
+        clock_output: midi_clock_out_sender,
+        clock: midi_clock,
+        learn: midi_learn,
+    } = midi;
+
     let (mut converter, capture ) =
-        init_converter(device, config.stream).with_context(|| "Failed to initialize audio converter")?;
+        init_converter(device, config.stream.clone()).with_context(|| "Failed to initialize audio converter")?;
 
-    let mut dmx_universe = init_dmx(midi_out_sender, system_out.clone())
-        .with_context(|| "Failed to initialize DMX universe")?;
+    let mut dmx_universe =
+        init_dmx(midi_out_sender.clone(), system_out.clone(), config.patch.clone())
+            .with_context(|| "Failed to initialize DMX universe")?;
 
     util::increase_thread_priority();
 
@@ -125,6 +159,25 @@ pub fn run(
     // Dmx last tick.
     let mut time_of_last_dmx_tick = time::Instant::now();
 
+    // MIDI clock output.
+    let clock_out_devices = config
+        .midi
+        .clock_out
+        .iter()
+        .filter_map(|name| {
+            let id = config.midi.device_id(name);
+            if id.is_none() {
+                log::warn!("[MIDI] Clock output device <{name}> is not in the device list, ignoring");
+            }
+            id
+        })
+        .collect();
+    let mut clock_output = MidiClockOutput::spawn(clock_out_devices, midi_clock_out_sender);
+
+    // MIDI mapping.
+    let mut midi_mapper =
+        load_midi_mapper(&config.midi, &midi_learn, &system_out, &midi_out_sender);
+
     loop {
         //
         // Loop control.
@@ -138,12 +191,16 @@ pub fn run(
             }
             AudioThreadControlSignal::RELOAD => {
                 system_out
-                    .send(SystemMessage::Log("[ENGINE] Reload start.".into()))
+                    .send(SystemMessage::log(LogLevel::Info, "[ENGINE] Reload start."))
                     .unwrap();
 
                 dmx_universe.reload()?;
+                // The mapper's config knows about a mapping file created by MIDI learn.
+                let midi_config = midi_mapper.config().clone();
+                midi_mapper =
+                    load_midi_mapper(&midi_config, &midi_learn, &system_out, &midi_out_sender);
                 system_out
-                    .send(SystemMessage::Log("[ENGINE] Reload complete".into()))
+                    .send(SystemMessage::log(LogLevel::Info, "[ENGINE] Reload complete"))
                     .unwrap();
                 thread_control_signal.store(AudioThreadControlSignal::CONTINUE, Ordering::Relaxed);
             }
@@ -159,6 +216,11 @@ pub fn run(
         let now = time::Instant::now();
         let loop_speed = now - loop_begin_time;
         loop_begin_time = now;
+        METRICS
+            .loop_duration_micros
+            .store(loop_speed.as_micros() as u64, Ordering::Relaxed);
+
+        clock_output.set_bpm(dmx_universe.bpm());
 
         // Constant tick.
         if now.duration_since(time_of_last_dmx_tick) > DMX_TICK_TIME {
@@ -172,9 +234,26 @@ pub fn run(
                 }
             }
 
-            let dmx_tick_duration = match dmx_universe.tick(&midi) {
-                Ok(dur) => dur,
+            let mut mapped = midi_mapper.map(midi);
+            mapped.controls.extend(control_receiver.try_iter());
+            for event in mapped.feedback {
+                if midi_out_sender.try_send(event).is_err() {
+                    log::trace!("[MIDI-MAP] Output queue is full, skipping feedback");
+                }
+            }
+            for message in mapped.system {
+                system_out.send(message).unwrap();
+            }
+
+            dmx_universe.set_beat_phase(midi_clock.beat_phase());
+
+            let dmx_tick_duration = match dmx_universe.tick(&mapped.unmapped, &mapped.controls) {
+                Ok(dur) => {
+                    METRICS.tick_duration.observe(dur);
+                    dur
+                }
                 Err(err) => {
+                    METRICS.wasm_traps.fetch_add(1, Ordering::Relaxed);
                     log::error!("[WASM] Engine crash: {err}");
                     Duration::from_micros(0)
                 }
@@ -220,6 +299,7 @@ pub fn run(
             &mut bass_samples,
             bass_modifier,
             &mut bass_peaks,
+            &midi_clock,
         )?;
 
         //
@@ -0,0 +1,246 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Notify;
+
+use crate::msg::{Topic, UnifiedMessage};
+
+pub type Consumers = Arc<Mutex<HashMap<String, Consumer>>>;
+
+//
+// Topics a consumer receives, each with an optional minimum interval between two messages of the same kind.
+// Messages above the rate are dropped, not delayed.
+//
+
+#[derive(Clone, Debug, Default)]
+pub struct Subscriptions {
+    topics: HashMap<Topic, Option<Duration>>,
+    last_sent: HashMap<&'static str, Instant>,
+}
+
+impl Subscriptions {
+    pub fn new(topics: impl IntoIterator<Item = (Topic, Option<Duration>)>) -> Self {
+        Self {
+            topics: topics.into_iter().collect(),
+            last_sent: HashMap::new(),
+        }
+    }
+
+    fn admit(&mut self, message: &UnifiedMessage, now: Instant) -> bool {
+        let (topic, kind) = message.topic();
+
+        let Some(interval) = self.topics.get(&topic) else {
+            return false;
+        };
+        let Some(interval) = interval else {
+            return true;
+        };
+
+        match self.last_sent.get(kind) {
+            Some(last) if now.duration_since(*last) < *interval => false,
+            _ => {
+                self.last_sent.insert(kind, now);
+                true
+            }
+        }
+    }
+}
+
+//
+// Bounded queue of a single consumer, the oldest message is dropped if the consumer is too slow.
+// Filled by the broadcast thread, drained by an async task or a thread.
+//
+
+struct Queue {
+    messages: VecDeque<UnifiedMessage>,
+    subscriptions: Subscriptions,
+    closed: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    capacity: usize,
+    notify: Notify,
+    available: Condvar,
+}
+
+#[derive(Clone)]
+pub struct Consumer {
+    shared: Arc<Shared>,
+}
+
+impl Consumer {
+    pub fn new(capacity: usize, subscriptions: Subscriptions) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                queue: Mutex::new(Queue {
+                    messages: VecDeque::with_capacity(capacity),
+                    subscriptions,
+                    closed: false,
+                }),
+                capacity,
+                notify: Notify::new(),
+                available: Condvar::new(),
+            }),
+        }
+    }
+
+    pub fn subscribe(&self, subscriptions: Subscriptions) {
+        let mut queue = self.shared.queue.lock().unwrap();
+
+        // Queued messages of topics which are no longer subscribed are discarded.
+        queue
+            .messages
+            .retain(|message| subscriptions.topics.contains_key(&message.topic().0));
+        queue.subscriptions = subscriptions;
+    }
+
+    /// Queues the message if it is subscribed, returns false once the consumer is closed.
+    pub fn push(&self, message: &UnifiedMessage) -> bool {
+        let mut queue = self.shared.queue.lock().unwrap();
+        if queue.closed {
+            return false;
+        }
+
+        if !queue.subscriptions.admit(message, Instant::now()) {
+            return true;
+        }
+
+        if queue.messages.len() >= self.shared.capacity {
+            queue.messages.pop_front();
+            log::trace!("[BROADCAST] Consumer queue is full, dropped the oldest message");
+        }
+        queue.messages.push_back(message.clone());
+
+        self.shared.notify.notify_one();
+        self.shared.available.notify_one();
+        true
+    }
+
+    /// Next message, `None` once the consumer is closed.
+    pub async fn recv(&self) -> Option<UnifiedMessage> {
+        loop {
+            // Created before checking the queue, so that no notification is missed.
+            let notified = self.shared.notify.notified();
+
+            {
+                let mut queue = self.shared.queue.lock().unwrap();
+                if queue.closed {
+                    return None;
+                }
+                if let Some(message) = queue.messages.pop_front() {
+                    return Some(message);
+                }
+            }
+
+            notified.await;
+        }
+    }
+
+    /// Blocking version of `recv` for consumers running on their own thread.
+    pub fn recv_blocking(&self) -> Option<UnifiedMessage> {
+        let mut queue = self.shared.queue.lock().unwrap();
+
+        loop {
+            if queue.closed {
+                return None;
+            }
+            if let Some(message) = queue.messages.pop_front() {
+                return Some(message);
+            }
+
+            queue = self.shared.available.wait(queue).unwrap();
+        }
+    }
+
+    /// Wakes up the receiving side, the broadcast thread removes closed consumers.
+    pub fn close(&self) {
+        self.shared.queue.lock().unwrap().closed = true;
+        self.shared.notify.notify_one();
+        self.shared.available.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::msg::{Signal, SystemMessage};
+
+    use super::*;
+
+    fn volume(value: u8) -> UnifiedMessage {
+        UnifiedMessage::Signal(Signal::Volume(value))
+    }
+
+    fn received_volume(consumer: &Consumer) -> Option<u8> {
+        match consumer.recv_blocking() {
+            Some(UnifiedMessage::Signal(Signal::Volume(value))) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn queued(consumer: &Consumer) -> usize {
+        consumer.shared.queue.lock().unwrap().messages.len()
+    }
+
+    #[test]
+    fn full_queues_drop_the_oldest_message() {
+        let consumer = Consumer::new(2, Subscriptions::new([(Topic::Signals, None)]));
+
+        for value in 1..=3 {
+            assert!(consumer.push(&volume(value)));
+        }
+
+        assert_eq!(received_volume(&consumer), Some(2));
+        assert_eq!(received_volume(&consumer), Some(3));
+    }
+
+    #[test]
+    fn only_subscribed_topics_are_queued() {
+        let consumer = Consumer::new(8, Subscriptions::new([(Topic::Stats, None)]));
+
+        assert!(consumer.push(&volume(1)));
+        assert!(consumer.push(&UnifiedMessage::System(SystemMessage::Heartbeat(1))));
+        assert_eq!(queued(&consumer), 1);
+
+        // Switching the topics discards the queued messages of the old ones.
+        consumer.subscribe(Subscriptions::new([(Topic::Signals, None)]));
+        assert_eq!(queued(&consumer), 0);
+    }
+
+    #[test]
+    fn rate_limits_apply_per_kind() {
+        let consumer = Consumer::new(
+            8,
+            Subscriptions::new([(Topic::Signals, Some(Duration::from_secs(60)))]),
+        );
+
+        consumer.push(&volume(1));
+        consumer.push(&volume(2));
+        consumer.push(&UnifiedMessage::Signal(Signal::Bass(3)));
+
+        assert_eq!(received_volume(&consumer), Some(1));
+        assert!(matches!(
+            consumer.recv_blocking(),
+            Some(UnifiedMessage::Signal(Signal::Bass(3)))
+        ));
+        assert_eq!(queued(&consumer), 0);
+    }
+
+    #[test]
+    fn close_wakes_up_the_receiver() {
+        let consumer = Consumer::new(8, Subscriptions::new([(Topic::Signals, None)]));
+        let receiver = {
+            let consumer = consumer.clone();
+            thread::spawn(move || consumer.recv_blocking().is_none())
+        };
+
+        consumer.close();
+        assert!(receiver.join().unwrap());
+        assert!(!consumer.push(&volume(1)));
+    }
+}
@@ -1,10 +1,11 @@
 use std::{
     fs::{self, File},
     io::Write,
+    net::ToSocketAddrs,
     path::{Path, PathBuf},
 };
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use audioviz::spectrum::config::StreamConfig;
 use log::debug;
 use serde::{Deserialize, Serialize};
@@ -13,7 +14,125 @@ use serde::{Deserialize, Serialize};
 pub struct Config {
     pub port: u16,
     pub default_audio_device: Option<String>,
+    /// Fixture patch (TOML or JSON) passed to the guest, which falls back to its builtin one if unset.
+    pub patch: Option<PathBuf>,
     pub stream: StreamConfig,
+    #[serde(default)]
+    pub midi: MidiConfig,
+    #[serde(default)]
+    pub osc: OscConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub log: LogConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct MidiConfig {
+    /// Port names (or parts of them) of the MIDI devices to connect to.
+    /// The position in this list is the device id seen by the guest.
+    pub devices: Vec<String>,
+    /// Name of the virtual input / output ports other software can connect to (Linux and macOS).
+    /// The virtual device id comes right after the ones of `devices`.
+    pub virtual_port: Option<String>,
+    /// Device whose MIDI clock overrides the tempo detected from audio.
+    pub clock_in: Option<String>,
+    /// Devices which receive MIDI clock derived from the current tempo.
+    pub clock_out: Vec<String>,
+    /// TOML file which maps MIDI and matrix inputs to named controls.
+    pub mapping: Option<PathBuf>,
+}
+
+impl Default for MidiConfig {
+    fn default() -> Self {
+        Self {
+            devices: vec!["DDJ-200".to_string()],
+            virtual_port: Some("Blaulicht".to_string()),
+            clock_in: None,
+            clock_out: vec![],
+            mapping: Some("mappings/ddj-400.toml".into()),
+        }
+    }
+}
+
+impl MidiConfig {
+    /// Resolves a configured device or virtual port name to its device id.
+    pub fn device_id(&self, name: &str) -> Option<u8> {
+        if self.virtual_port.as_deref() == Some(name) {
+            return Some(self.devices.len() as u8);
+        }
+
+        self.devices
+            .iter()
+            .position(|device| device == name)
+            .map(|id| id as u8)
+    }
+
+    /// Name of the device or virtual port with the given id.
+    pub fn device_name(&self, id: u8) -> Option<&str> {
+        match self.devices.get(id as usize) {
+            Some(name) => Some(name),
+            None if id as usize == self.devices.len() => self.virtual_port.as_deref(),
+            None => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct OscConfig {
+    /// UDP address of the OSC server, e.g. `127.0.0.1:9000`. No server is started if unset.
+    /// OSC has no authentication, other interfaces than localhost expose full control.
+    pub listen: Option<String>,
+    /// Addresses which receive the audio signals as OSC messages, e.g. `127.0.0.1:7000`.
+    pub targets: Vec<String>,
+    /// Prefix of all OSC addresses, received and sent.
+    pub prefix: String,
+}
+
+impl Default for OscConfig {
+    fn default() -> Self {
+        Self {
+            listen: None,
+            targets: vec![],
+            prefix: "/blaulicht".to_string(),
+        }
+    }
+}
+
+/// Authentication is disabled unless at least one token is set.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// Grants full control.
+    pub token: Option<String>,
+    /// Grants read-only access, e.g. for monitoring screens.
+    pub read_only_token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct LogConfig {
+    /// Number of recent entries sent to newly connected dashboards.
+    pub backlog: usize,
+    /// File the entries are appended to as JSON lines, nothing is written if unset.
+    pub file: Option<String>,
+    /// Size at which the file is rotated.
+    pub max_file_bytes: u64,
+    /// Number of rotated files to keep, e.g. `blaulicht.log.1` to `blaulicht.log.5`.
+    pub max_files: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            backlog: 500,
+            file: None,
+            max_file_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
 }
 
 impl Default for Config {
@@ -21,13 +140,47 @@ impl Default for Config {
         Self {
             port: 1234,
             default_audio_device: None,
+            patch: None,
             stream: StreamConfig {
                 // TODO: also experiment with fft resolution
                 // gravity: None, // OR: Some(100)
                 gravity: Some(100.0),
                 ..Default::default()
             },
+            midi: MidiConfig::default(),
+            osc: OscConfig::default(),
+            auth: AuthConfig::default(),
+            log: LogConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Checks the references between the sections which deserializing alone does not catch.
+    pub fn validate(&self) -> Result<()> {
+        let midi = &self.midi;
+        for name in midi.clock_in.iter().chain(&midi.clock_out) {
+            if midi.device_id(name).is_none() {
+                bail!("MIDI clock device <{name}> is not in the device list");
+            }
+        }
+
+        if let Some(path) = &midi.mapping {
+            if path.exists() {
+                crate::midi::mapping::read_mapping(path)?;
+            }
+        }
+
+        if let Some(path) = &self.patch {
+            crate::patch::read_patch(path)?;
         }
+
+        for addr in self.osc.listen.iter().chain(&self.osc.targets) {
+            addr.to_socket_addrs()
+                .with_context(|| format!("Invalid OSC address <{addr}>"))?;
+        }
+
+        Ok(())
     }
 }
 
@@ -1,6 +1,7 @@
-use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TrySendError};
 use enttecopendmx::EnttecOpenDMX;
 use std::{
+    path::PathBuf,
     sync::{
         atomic::{AtomicU8, Ordering},
         Arc,
@@ -10,14 +11,14 @@ use std::{
 };
 
 use crate::{
-    app::MidiEvent, audio::defs::AudioThreadControlSignal, config::Config, msg::{Signal, SystemMessage}, wasm::{self, TickEngine, TickInput}
+    app::{ControlEvent, MidiEvent}, audio::defs::AudioThreadControlSignal, config::Config, eventlog::LogLevel, metrics::METRICS, midi::{learn::MidiLearnStatus, MidiHandles}, msg::{BpmInfo, Signal, SystemMessage}, tempo::{Tempo, TempoStatus}, wasm::{self, TickEngine, TickInput}
 };
 
 use cpal::{traits::DeviceTrait, Device};
 use log::warn;
 
 use crate::{
-    app::FromFrontend,
+    app::{FromFrontend, MidiMessage},
     audio::{self},
     utils,
 };
@@ -26,6 +27,12 @@ pub struct DmxUniverseBasic {
     tick_engine: TickEngine,
     channels: [u8; 513],
     tick_input: TickInput,
+    // Tempo from the analysis, before the tempo of the operator is applied.
+    analyzed: BpmInfo,
+    tempo: Tempo,
+    tempo_status: Option<TempoStatus>,
+    // Last frame sent to the frontend and the REST API.
+    published: Option<[u8; 513]>,
     system_out: Sender<SystemMessage>,
 }
 
@@ -33,13 +40,18 @@ impl DmxUniverseBasic {
     fn new(
         midi_out: Sender<MidiEvent>,
         system_out: Sender<SystemMessage>,
+        patch: Option<PathBuf>,
     ) -> wasmtime::Result<Self> {
-        let tick_engine = wasm::TickEngine::create(midi_out, system_out.clone())?;
+        let tick_engine = wasm::TickEngine::create(midi_out, system_out.clone(), patch)?;
 
         Ok(Self {
             tick_engine,
             channels: [0; 513],
             tick_input: TickInput::default(),
+            analyzed: BpmInfo::default(),
+            tempo: Tempo::new(),
+            tempo_status: None,
+            published: None,
             system_out,
         })
     }
@@ -62,22 +74,53 @@ impl DmxUniverseBasic {
                 self.tick_input.bass_avg = v;
             }
             Signal::Bpm(v) => {
-                self.tick_input.bpm = v.bpm;
-                self.tick_input.time_between_beats_millis = v.time_between_beats_millis;
+                self.analyzed = v;
             }
         }
     }
 
-    fn tick(&mut self, midi: &[MidiEvent]) -> anyhow::Result<Duration> {
+    fn set_beat_phase(&mut self, phase: Option<u8>) {
+        self.tick_input.beat_phase = phase;
+    }
+
+    /// Also returns whether the frame changed and was published.
+    fn tick(
+        &mut self,
+        midi: &[MidiEvent],
+        controls: &[ControlEvent],
+    ) -> anyhow::Result<(Duration, bool)> {
         let start = Instant::now();
-        self.tick_engine.tick(self.tick_input, midi, false)?;
+
+        // Tempo controls are handled by the host, the guest only sees the resulting tempo.
+        let mut guest_controls = Vec::with_capacity(controls.len());
+        for control in controls {
+            match Tempo::is_control(&control.name) {
+                true => self.tempo.control(control, &self.analyzed, start),
+                false => guest_controls.push(control.clone()),
+            }
+        }
+        self.apply_tempo(start);
+
+        self.tick_engine
+            .tick(self.tick_input, midi, &guest_controls, false)?;
 
         for (index, value) in self.tick_engine.dmx().iter().enumerate() {
             self.channels[index] = *value;
         }
+        let published = publish_frame(&self.system_out, &self.channels, &mut self.published);
 
         let elapsed = Instant::now().duration_since(start);
-        Ok(elapsed)
+        Ok((elapsed, published))
+    }
+
+    fn apply_tempo(&mut self, now: Instant) {
+        self.tempo.apply(&mut self.tick_input, &self.analyzed, now);
+
+        let status = self.tempo.status(&self.analyzed);
+        if self.tempo_status != Some(status) {
+            self.tempo_status = Some(status);
+            self.system_out.send(SystemMessage::Tempo(status)).unwrap();
+        }
     }
 
     fn reload(&mut self) -> wasmtime::Result<()> {
@@ -87,7 +130,6 @@ impl DmxUniverseBasic {
 
 pub struct DmxUniverseDummy {
     basic: DmxUniverseBasic,
-    last_state: [u8; 513],
 }
 
 pub enum DmxUniverse {
@@ -99,8 +141,9 @@ impl DmxUniverse {
     pub fn new(
         midi_out: Sender<MidiEvent>,
         system_out: Sender<SystemMessage>,
+        patch: Option<PathBuf>,
     ) -> anyhow::Result<Self> {
-        let base = DmxUniverseBasic::new(midi_out, system_out)?;
+        let base = DmxUniverseBasic::new(midi_out, system_out, patch)?;
         let real_universe = DmxUniverseReal::new(base)?;
         Ok(Self::Real(real_universe))
     }
@@ -108,12 +151,10 @@ impl DmxUniverse {
     pub fn new_dummy(
         midi_out: Sender<MidiEvent>,
         system_out: Sender<SystemMessage>,
+        patch: Option<PathBuf>,
     ) -> wasmtime::Result<Self> {
-        let base = DmxUniverseBasic::new(midi_out, system_out)?;
-        Ok(Self::Dummy(DmxUniverseDummy {
-            basic: base,
-            last_state: [0; 513],
-        }))
+        let base = DmxUniverseBasic::new(midi_out, system_out, patch)?;
+        Ok(Self::Dummy(DmxUniverseDummy { basic: base }))
     }
 
     pub fn signal(&mut self, signal: Signal) {
@@ -123,32 +164,32 @@ impl DmxUniverse {
         }
     }
 
-    pub fn tick(&mut self, midi: &[MidiEvent]) -> anyhow::Result<Duration> {
+    /// Current tempo as seen by the guest.
+    pub fn bpm(&self) -> u8 {
         match self {
-            DmxUniverse::Dummy(ref mut dummy) => {
-                let dur = dummy.basic.tick(midi)?;
-
-                let mut modified = false;
-                for (a, b) in dummy.basic.channels.iter().zip(dummy.last_state.iter()) {
-                    if a != b {
-                        modified = true;
-                        break;
-                    }
-                }
+            DmxUniverse::Dummy(dummy) => dummy.basic.tick_input.bpm,
+            DmxUniverse::Real(dmx_universe_real) => dmx_universe_real.base.tick_input.bpm,
+        }
+    }
 
-                if modified {
-                    dummy
-                        .basic
-                        .system_out
-                        .send(SystemMessage::DMX(dummy.basic.channels.into()))
-                        .unwrap();
+    pub fn set_beat_phase(&mut self, phase: Option<u8>) {
+        match self {
+            DmxUniverse::Dummy(dummy) => dummy.basic.set_beat_phase(phase),
+            DmxUniverse::Real(dmx_universe_real) => dmx_universe_real.base.set_beat_phase(phase),
+        }
+    }
 
-                    dummy.last_state = dummy.basic.channels;
+    pub fn tick(&mut self, midi: &[MidiEvent], controls: &[ControlEvent]) -> anyhow::Result<Duration> {
+        match self {
+            DmxUniverse::Dummy(ref mut dummy) => {
+                let (dur, published) = dummy.basic.tick(midi, controls)?;
+                if published {
+                    METRICS.dmx_frames_dummy.fetch_add(1, Ordering::Relaxed);
                 }
 
                 Ok(dur)
             }
-            DmxUniverse::Real(dmx_universe_real) => dmx_universe_real.tick(midi),
+            DmxUniverse::Real(dmx_universe_real) => dmx_universe_real.tick(midi, controls),
         }
     }
 
@@ -186,8 +227,8 @@ impl DmxUniverseReal {
         self.base.signal(signal)
     }
 
-    pub fn tick(&mut self, midi: &[MidiEvent]) -> anyhow::Result<Duration> {
-        let duration = self.base.tick(midi)?;
+    pub fn tick(&mut self, midi: &[MidiEvent], controls: &[ControlEvent]) -> anyhow::Result<Duration> {
+        let (duration, _) = self.base.tick(midi, controls)?;
         self.write_to_serial();
         Ok(duration)
     }
@@ -195,7 +236,25 @@ impl DmxUniverseReal {
     fn write_to_serial(&mut self) {
         self.dmx.set_buffer(self.base.channels);
         self.dmx.render().unwrap();
+        METRICS.dmx_frames_enttec.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// Sends the frame to the frontend and the REST API if it changed since the last one.
+fn publish_frame(
+    system_out: &Sender<SystemMessage>,
+    channels: &[u8; 513],
+    published: &mut Option<[u8; 513]>,
+) -> bool {
+    if published.as_ref() == Some(channels) {
+        return false;
     }
+
+    system_out
+        .send(SystemMessage::DMX((*channels).into()))
+        .unwrap();
+    *published = Some(*channels);
+    true
 }
 
 pub fn audio_thread(
@@ -203,9 +262,8 @@ pub fn audio_thread(
     audio_thread_control_signal: Arc<AtomicU8>,
     signal_out_0: Sender<Signal>,
     system_out: Sender<SystemMessage>,
-    midi_in_receiver: Receiver<MidiEvent>,
+    midi: MidiHandles,
     midi_in_sender: Sender<MidiEvent>,
-    midi_out_sender: Sender<MidiEvent>,
     config: Config, 
 ) {
     log::info!("[SUPERVISOR] Thread started!");
@@ -219,10 +277,84 @@ pub fn audio_thread(
 
     audio_thread_control_signal.store(AudioThreadControlSignal::ABORTED, Ordering::Relaxed);
 
+    // Named controls from OSC, forwarded to the audio thread.
+    let (control_sender, control_receiver) = crossbeam_channel::bounded(256);
+
     let mut seq = 0;
+    let mut next_heartbeat = Instant::now() + heartbeat_delay;
 
     loop {
-        thread::sleep(heartbeat_delay);
+        // Handle messages as soon as they arrive, the rest of the loop runs once per heartbeat.
+        let message = match from_frontend.recv_deadline(next_heartbeat) {
+            Ok(message) => Some(message),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => {
+                log::warn!("[SUPERVISOR] Shutting down.");
+                break;
+            }
+        };
+
+        if let Some(message) = message {
+            match message {
+                FromFrontend::Reload => {
+                    if audio_thread_control_signal.load(Ordering::Relaxed)
+                        == AudioThreadControlSignal::CONTINUE
+                    {
+                        audio_thread_control_signal
+                            .store(AudioThreadControlSignal::RELOAD, Ordering::Relaxed);
+                    }
+                }
+                FromFrontend::SelectSerialDevice(dev) => {
+                    // TODO: maybe implement this
+                    // Get device by name.
+                }
+                FromFrontend::SelectInputDevice(dev) => {
+                    // Get device by name.
+                    audio_device = dev;
+                    device_changed = true;
+                }
+                FromFrontend::MatrixControl(control) => {
+                    // 255 is for the builtin device.
+                    midi_in_sender
+                        .send(MidiEvent {
+                            device: control.device,
+                            message: MidiMessage::Raw {
+                                status: control.y,
+                                data0: control.x,
+                                data1: control.value as u8,
+                            },
+                        })
+                        .unwrap();
+                }
+                FromFrontend::MidiLearn(request) => {
+                    log::info!("[MIDI-MAP] Learning {:?}", request.target);
+                    let target = request.target.clone();
+                    midi.learn.start(request);
+                    system_out
+                        .send(SystemMessage::MidiLearn(MidiLearnStatus::Waiting { target }))
+                        .unwrap();
+                }
+                FromFrontend::MidiLearnCancel => {
+                    if midi.learn.cancel() {
+                        system_out
+                            .send(SystemMessage::MidiLearn(MidiLearnStatus::Cancelled))
+                            .unwrap();
+                    }
+                }
+                FromFrontend::Control(control) => match control_sender.try_send(control) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(control)) => {
+                        log::warn!("[SUPERVISOR] Control queue is full, dropping {control:?}");
+                    }
+                    Err(TrySendError::Disconnected(_)) => unreachable!(),
+                },
+            }
+        }
+
+        if Instant::now() < next_heartbeat {
+            continue;
+        }
+        next_heartbeat = Instant::now() + heartbeat_delay;
 
         if system_out.send(SystemMessage::Heartbeat(seq)).is_err() {
             warn!("[SUPERVISOR] Shutting down...");
@@ -230,47 +362,12 @@ pub fn audio_thread(
         };
         seq += 1;
 
-        match from_frontend.try_recv() {
-            Ok(FromFrontend::Reload) => {
-                if audio_thread_control_signal.load(Ordering::Relaxed)
-                    == AudioThreadControlSignal::CONTINUE
-                {
-                    audio_thread_control_signal
-                        .store(AudioThreadControlSignal::RELOAD, Ordering::Relaxed);
-                }
-            }
-            Ok(FromFrontend::SelectSerialDevice(dev)) => {
-                // TODO: maybe implement this
-                // Get device by name.
-            }
-            Ok(FromFrontend::SelectInputDevice(dev)) => {
-                // Get device by name.
-                audio_device = dev;
-                device_changed = true;
-            }
-            Ok(FromFrontend::MatrixControl(control)) => {
-                // 255 is for the builtin device.
-                midi_in_sender
-                    .send(MidiEvent {
-                        device: control.device,
-                        status: control.y,
-                        data0: control.x,
-                        data1: control.value as u8,
-                    })
-                    .unwrap();
-            }
-            Err(TryRecvError::Disconnected) => {
-                log::warn!("[SUPERVISOR] Shutting down.");
-                break;
-            }
-            Err(TryRecvError::Empty) => {}
-        };
-
         // Check if the thread crashed and attempt to restart it.
         if audio_thread_control_signal.load(Ordering::Relaxed) == AudioThreadControlSignal::CRASHED
             && audio_device.is_some()
         {
             thread::sleep(Duration::from_secs(2));
+            METRICS.audio_thread_restarts.fetch_add(1, Ordering::Relaxed);
             device_changed = true;
         }
 
@@ -294,8 +391,8 @@ pub fn audio_thread(
 
                 let sys = sys.clone();
 
-                let midi_recv = midi_in_receiver.clone();
-                let midi_send = midi_out_sender.clone();
+                let midi = midi.clone();
+                let control_recv = control_receiver.clone();
                 let config = config.clone();
 
                 thread::spawn(move || {
@@ -307,19 +404,19 @@ pub fn audio_thread(
                         sig_0,
                         sys.clone(),
                         audio_thread_control_signal.clone(),
-                        midi_recv,
-                        midi_send,
+                        midi,
+                        control_recv,
                         config,
                     ) {
                         // TODO: handle the audio backend error.
-                        sys.send(SystemMessage::Log(format!("[audio] {err}")))
+                        sys.send(SystemMessage::log(LogLevel::Error, format!("[audio] {err}")))
                             .unwrap();
 
                         audio_thread_control_signal
                             .store(AudioThreadControlSignal::CRASHED, Ordering::Relaxed);
                     }
 
-                    sys.send(SystemMessage::Log("[audio] Thread died.".into()))
+                    sys.send(SystemMessage::log(LogLevel::Warn, "[audio] Thread died."))
                         .unwrap();
                 });
             }
@@ -330,8 +427,102 @@ pub fn audio_thread(
                 audio_device.clone().unwrap().name().unwrap()
             );
 
-            sys.send(SystemMessage::Log("[audio] Thread started.".to_string()))
+            sys.send(SystemMessage::log(LogLevel::Info, "[audio] Thread started."))
                 .unwrap();
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use actix_web::{
+        http::StatusCode,
+        test::{call_service, init_service, read_body_json, TestRequest},
+        web::Data,
+        App,
+    };
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        eventlog::EventLog,
+        routes::{self, AppState, SystemStatus},
+    };
+
+    fn frame() -> [u8; 513] {
+        let mut channels = [0; 513];
+        channels[1] = 255;
+        channels[512] = 42;
+        channels
+    }
+
+    // What the broadcast thread does with the messages of the engine.
+    fn status(system_in: &Receiver<SystemMessage>) -> Arc<Mutex<SystemStatus>> {
+        let mut status = SystemStatus::default();
+        for message in system_in.try_iter() {
+            status.update(&message);
+        }
+        Arc::new(Mutex::new(status))
+    }
+
+    async fn get_dmx(status: Arc<Mutex<SystemStatus>>) -> (StatusCode, Value) {
+        let (system_out, _) = crossbeam_channel::unbounded();
+        let data = Data::new(AppState {
+            from_frontend_sender: crossbeam_channel::unbounded().0,
+            to_frontend_consumers: Default::default(),
+            config: Arc::new(Mutex::new(Config::default())),
+            config_path: String::new(),
+            status,
+            system_out,
+            event_log: EventLog::new(&Default::default()).unwrap(),
+        });
+
+        let app = init_service(App::new().app_data(data).service(routes::get_dmx)).await;
+        let request = TestRequest::get().uri("/api/dmx").to_request();
+        let response = call_service(&app, request).await;
+
+        let code = response.status();
+        (code, read_body_json(response).await)
+    }
+
+    #[test]
+    fn publishes_changed_frames_only() {
+        let (system_out, system_in) = crossbeam_channel::unbounded();
+        let mut published = None;
+
+        // The first frame is published even if it is dark.
+        assert!(publish_frame(&system_out, &[0; 513], &mut published));
+        assert!(!publish_frame(&system_out, &[0; 513], &mut published));
+        assert!(publish_frame(&system_out, &frame(), &mut published));
+        assert!(!publish_frame(&system_out, &frame(), &mut published));
+
+        let frames: Vec<_> = system_in
+            .try_iter()
+            .map(|message| match message {
+                SystemMessage::DMX(channels) => *channels,
+                _ => panic!("unexpected message"),
+            })
+            .collect();
+        assert_eq!(frames, [[0; 513], frame()]);
+    }
+
+    #[actix_web::test]
+    async fn api_serves_the_published_frame() {
+        let (system_out, system_in) = crossbeam_channel::unbounded();
+
+        let (code, _) = get_dmx(status(&system_in)).await;
+        assert_eq!(code, StatusCode::SERVICE_UNAVAILABLE);
+
+        publish_frame(&system_out, &frame(), &mut None);
+
+        let (code, body) = get_dmx(status(&system_in)).await;
+        assert_eq!(code, StatusCode::OK);
+
+        let channels = body["data"].as_array().unwrap();
+        assert_eq!(channels.len(), 512);
+        assert_eq!(channels[0], 255);
+        assert_eq!(channels[511], 42);
+    }
+}
@@ -0,0 +1,311 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config::LogConfig;
+
+//
+// Structured log of host and guest messages.
+// Recent entries are kept in memory for new dashboards, all of them optionally go to a rotating file.
+//
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// Level passed by the guest, unknown values are treated as info.
+    pub fn from_guest(level: i32) -> Self {
+        match level {
+            0 => Self::Trace,
+            1 => Self::Debug,
+            3 => Self::Warn,
+            4 => Self::Error,
+            _ => Self::Info,
+        }
+    }
+}
+
+impl From<LogLevel> for log::Level {
+    fn from(value: LogLevel) -> Self {
+        match value {
+            LogLevel::Trace => log::Level::Trace,
+            LogLevel::Debug => log::Level::Debug,
+            LogLevel::Info => log::Level::Info,
+            LogLevel::Warn => log::Level::Warn,
+            LogLevel::Error => log::Level::Error,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LogSource {
+    Host,
+    Wasm,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LogEntry {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub source: LogSource,
+    pub level: LogLevel,
+    pub message: String,
+}
+
+impl LogEntry {
+    pub fn new(source: LogSource, level: LogLevel, message: impl Into<String>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis() as u64)
+            .unwrap_or_default();
+
+        Self {
+            timestamp,
+            source,
+            level,
+            message: message.into(),
+        }
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl LogFile {
+    fn open(path: PathBuf, max_size: u64, max_files: usize) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open log file <{}>", path.display()))?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size,
+            max_files,
+        })
+    }
+
+    fn write(&mut self, entry: &LogEntry) -> Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        self.file.write_all(&line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    // `blaulicht.log` becomes `blaulicht.log.1`, the oldest file is removed.
+    fn rotate(&mut self) -> Result<()> {
+        let rotated = |index: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{index}"));
+            PathBuf::from(name)
+        };
+
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.max_files).rev() {
+                if rotated(index).exists() {
+                    fs::rename(rotated(index), rotated(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, rotated(1))?;
+        }
+
+        *self = Self::open(self.path.clone(), self.max_size, self.max_files)?;
+        Ok(())
+    }
+}
+
+struct Inner {
+    backlog: VecDeque<LogEntry>,
+    capacity: usize,
+    file: Option<LogFile>,
+}
+
+#[derive(Clone)]
+pub struct EventLog(Arc<Mutex<Inner>>);
+
+impl EventLog {
+    pub fn new(config: &LogConfig) -> Result<Self> {
+        let file = match &config.file {
+            Some(path) => Some(LogFile::open(
+                PathBuf::from(path),
+                config.max_file_bytes,
+                config.max_files,
+            )?),
+            None => None,
+        };
+
+        Ok(Self(Arc::new(Mutex::new(Inner {
+            backlog: VecDeque::with_capacity(config.backlog),
+            capacity: config.backlog,
+            file,
+        }))))
+    }
+
+    pub fn push(&self, entry: &LogEntry) {
+        let mut inner = self.0.lock().unwrap();
+
+        if let Some(file) = &mut inner.file {
+            if let Err(err) = file.write(entry) {
+                log::error!("[LOG] Failed to write log file, disabling it: {err:#}");
+                inner.file = None;
+            }
+        }
+
+        if inner.capacity == 0 {
+            return;
+        }
+        if inner.backlog.len() == inner.capacity {
+            inner.backlog.pop_front();
+        }
+        inner.backlog.push_back(entry.clone());
+    }
+
+    /// Recent entries, oldest first.
+    pub fn backlog(&self) -> Vec<LogEntry> {
+        self.0.lock().unwrap().backlog.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn entry(message: &str) -> LogEntry {
+        LogEntry::new(LogSource::Host, LogLevel::Info, message)
+    }
+
+    // Removed again when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("blaulicht-{name}-{}", process::id()));
+            let _ = fs::remove_dir_all(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn lines(path: PathBuf) -> Vec<String> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<LogEntry>(line).unwrap().message)
+            .collect()
+    }
+
+    #[test]
+    fn backlog_keeps_the_newest_entries() {
+        let log = EventLog::new(&LogConfig {
+            backlog: 2,
+            ..LogConfig::default()
+        })
+        .unwrap();
+
+        for message in ["a", "b", "c"] {
+            log.push(&entry(message));
+        }
+
+        let messages: Vec<_> = log.backlog().into_iter().map(|e| e.message).collect();
+        assert_eq!(messages, ["b", "c"]);
+    }
+
+    #[test]
+    fn empty_backlog() {
+        let log = EventLog::new(&LogConfig {
+            backlog: 0,
+            ..LogConfig::default()
+        })
+        .unwrap();
+
+        log.push(&entry("a"));
+        assert!(log.backlog().is_empty());
+    }
+
+    #[test]
+    fn files_are_rotated() {
+        let dir = TempDir::new("rotate");
+        let path = dir.0.join("logs/blaulicht.log");
+        let line_len = serde_json::to_vec(&entry("a")).unwrap().len() as u64 + 1;
+
+        // Two lines per file, two rotated files.
+        let log = EventLog::new(&LogConfig {
+            backlog: 0,
+            file: Some(path.to_string_lossy().to_string()),
+            max_file_bytes: line_len * 2,
+            max_files: 2,
+        })
+        .unwrap();
+
+        for message in ["a", "b", "c", "d", "e", "f", "g"] {
+            log.push(&entry(message));
+        }
+
+        let rotated = |index: usize| dir.0.join(format!("logs/blaulicht.log.{index}"));
+        assert_eq!(lines(path.clone()), ["g"]);
+        assert_eq!(lines(rotated(1)), ["e", "f"]);
+        assert_eq!(lines(rotated(2)), ["c", "d"]);
+        assert!(!rotated(3).exists());
+    }
+
+    #[test]
+    fn files_without_rotated_copies_start_over() {
+        let dir = TempDir::new("truncate");
+        let path = dir.0.join("blaulicht.log");
+        let line_len = serde_json::to_vec(&entry("a")).unwrap().len() as u64 + 1;
+
+        let log = EventLog::new(&LogConfig {
+            backlog: 0,
+            file: Some(path.to_string_lossy().to_string()),
+            max_file_bytes: line_len,
+            max_files: 0,
+        })
+        .unwrap();
+
+        log.push(&entry("a"));
+        log.push(&entry("b"));
+        assert_eq!(lines(path), ["b"]);
+    }
+}
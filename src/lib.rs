@@ -2,11 +2,18 @@
 
 pub mod app;
 pub mod audio;
+pub mod broadcast;
 pub mod config;
 pub mod dmx;
+pub mod eventlog;
 pub mod routes;
 pub mod utils;
 pub mod wasm;
 pub mod midi;
+pub mod osc;
+pub mod patch;
+pub mod profile;
+pub mod tempo;
 pub mod util;
-pub mod msg;
\ No newline at end of file
+pub mod msg;
+pub mod metrics;
\ No newline at end of file
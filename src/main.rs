@@ -11,17 +11,21 @@ use actix_web::{App, HttpServer};
 use anyhow::{bail, Context};
 use blaulicht::app::FromFrontend;
 use blaulicht::audio::defs::AudioThreadControlSignal;
-use blaulicht::msg::{SystemMessage, UnifiedMessage};
-use blaulicht::routes::AppState;
+use blaulicht::broadcast::{Consumer, Consumers, Subscriptions};
+use blaulicht::eventlog::{EventLog, LogLevel, LogSource};
+use blaulicht::msg::{SystemMessage, Topic, UnifiedMessage};
+use blaulicht::routes::{AppState, SystemStatus};
 use blaulicht::utils::device_from_name;
-use blaulicht::{config, dmx, midi, routes};
-use crossbeam_channel::{Sender, TryRecvError};
+use blaulicht::{config, dmx, midi, osc, routes};
+use crossbeam_channel::TryRecvError;
 use env_logger::Env;
 use libc::system;
 use log::{error, info};
 use notify::event::{DataChange, ModifyKind};
 use notify::{Event, RecursiveMode, Watcher};
 
+const OSC_QUEUE_CAPACITY: usize = 64;
+
 #[actix_web::main]
 async fn main() -> anyhow::Result<()> {
     let config_filepath = "./config.toml";
@@ -46,6 +50,10 @@ async fn main() -> anyhow::Result<()> {
 
     let (midi_in_sender, midi_in_receiver) = crossbeam_channel::bounded(100);
     let (midi_out_sender, midi_out_receiver) = crossbeam_channel::bounded(10);
+    // Clock pulses must not wait behind feedback, the MIDI thread sends them first.
+    let (midi_clock_out_sender, midi_clock_out_receiver) = crossbeam_channel::bounded(64);
+    let midi_clock = midi::MidiClock::new();
+    let midi_learn = midi::MidiLearn::new();
 
     //
     // Read config file.
@@ -69,13 +77,20 @@ async fn main() -> anyhow::Result<()> {
 
     let send = midi_in_sender.clone();
     let sys_out = system_out.clone();
+    let midi_config = cfg.midi.clone();
+    let clock = midi_clock.clone();
     thread::spawn(move || {
-        match midi::midi(send, midi_out_receiver.clone()) {
+        match midi::midi(
+            send,
+            midi_out_receiver.clone(),
+            midi_clock_out_receiver.clone(),
+            midi_config,
+            clock,
+        ) {
             Ok(_) => panic!("Unreachable."),
             Err(err) => {
                 let msg = format!("MIDI thread crashed! {err:?}");
-                log::error!("{msg}");
-                sys_out.send(SystemMessage::Log(msg)).unwrap();
+                sys_out.send(SystemMessage::log(LogLevel::Error, msg)).unwrap();
             }
         }
 
@@ -83,6 +98,7 @@ async fn main() -> anyhow::Result<()> {
         // Allows the dev to se what MIDI messages are sent to the device.
         loop {
             thread::sleep(Duration::from_millis(50));
+            midi_clock_out_receiver.try_iter().for_each(drop);
             match midi_out_receiver.try_recv() {
                 Ok(_midi) => {
                     // TODO: include if required
@@ -103,15 +119,21 @@ async fn main() -> anyhow::Result<()> {
         let audio_thread_control_signal = audio_thread_control_signal.clone();
         let send = midi_in_sender.clone();
         let cfg = cfg.clone();
+        let midi = midi::MidiHandles {
+            input: midi_in_receiver,
+            output: midi_out_sender,
+            clock_output: midi_clock_out_sender,
+            clock: midi_clock,
+            learn: midi_learn,
+        };
         thread::spawn(move || {
             dmx::audio_thread(
                 from_frontend_receiver,
                 audio_thread_control_signal,
                 app_signal_out,
                 system_out,
-                midi_in_receiver,
+                midi,
                 send,
-                midi_out_sender,
                 cfg,
             )
         });
@@ -121,51 +143,86 @@ async fn main() -> anyhow::Result<()> {
     // End audio.
     //
 
-    let consumers: Arc<Mutex<HashMap<String, Sender<UnifiedMessage>>>> =
-        Arc::new(Mutex::new(HashMap::new()));
+    let consumers: Consumers = Arc::new(Mutex::new(HashMap::new()));
+    let event_log = EventLog::new(&cfg.log)?;
+
+    let status = Arc::new(Mutex::new(SystemStatus::default()));
 
     let consumers2 = consumers.clone();
+    let status2 = status.clone();
+    let event_log2 = event_log.clone();
     thread::spawn(move || loop {
-        //
-        // System messages.
-        //
-        let system_res = app_system_receiver.try_recv();
-        match system_res {
-            Ok(res) => {
-                if let SystemMessage::Log(content) = &res {
-                    log::info!("{content}")
-                }
-                let consumers = consumers2.lock().unwrap();
-                for c in consumers.values() {
-                    if c.send(UnifiedMessage::System(res.clone())).is_err() {
-                        continue;
+        let message = crossbeam_channel::select! {
+            //
+            // System messages.
+            //
+            recv(app_system_receiver) -> res => match res {
+                Ok(res) => {
+                    // Guest logs are already written by the wasm import.
+                    if let SystemMessage::Log(entry) = &res {
+                        if entry.source == LogSource::Host {
+                            log::log!(entry.level.into(), "{}", entry.message);
+                        }
                     }
+                    status2.lock().unwrap().update(&res);
+                    UnifiedMessage::System(res)
                 }
-            }
-            Err(crossbeam_channel::TryRecvError::Empty) => {}
-            Err(crossbeam_channel::TryRecvError::Disconnected) => unreachable!(),
+                Err(_) => break,
+            },
+            //
+            // Signal messages.
+            //
+            recv(app_signal_receiver) -> res => match res {
+                Ok(res) => UnifiedMessage::Signal(res),
+                Err(_) => break,
+            },
+        };
+
+        // Logged under the consumer lock, so that new websockets get each entry exactly once,
+        // either in the backlog or from their queue.
+        let mut consumers = consumers2.lock().unwrap();
+        if let UnifiedMessage::System(SystemMessage::Log(entry)) = &message {
+            event_log2.push(entry);
         }
 
-        //
-        // Signal messages.
-        //
-        let signal_res = app_signal_receiver.try_recv();
-        match signal_res {
-            Ok(res) => {
-                let consumers = consumers2.lock().unwrap();
-                for c in consumers.values() {
-                    if c.send(UnifiedMessage::Signal(res.clone())).is_err() {
-                        continue;
-                    }
-                }
+        // Closed consumers are dropped right away.
+        consumers.retain(|_, consumer| consumer.push(&message));
+    });
+
+    //
+    // OSC server and signal publisher.
+    //
+
+    if cfg.osc.listen.is_some() {
+        let osc_config = cfg.osc.clone();
+        let send = from_frontend_sender.clone();
+        let sys_out = system_out.clone();
+        thread::spawn(move || {
+            if let Err(err) = osc::server(osc_config, send) {
+                let msg = format!("[OSC] Server crashed! {err:#}");
+                sys_out.send(SystemMessage::log(LogLevel::Error, msg)).unwrap();
             }
-            Err(crossbeam_channel::TryRecvError::Empty) => {}
-            Err(crossbeam_channel::TryRecvError::Disconnected) => {
-                log::warn!("[BROADCAST] Shutting down.");
-                break;
+        });
+    }
+
+    if !cfg.osc.targets.is_empty() {
+        let consumer = Consumer::new(
+            OSC_QUEUE_CAPACITY,
+            Subscriptions::new([(Topic::Signals, None)]),
+        );
+        consumers
+            .lock()
+            .unwrap()
+            .insert("osc".to_string(), consumer.clone());
+
+        let osc_config = cfg.osc.clone();
+        thread::spawn(move || {
+            if let Err(err) = osc::publisher(osc_config, consumer.clone()) {
+                log::error!("[OSC] Publisher crashed! {err:#}");
             }
-        }
-    });
+            consumer.close();
+        });
+    }
 
     //
     // Filesystem firmware watcher.
@@ -181,6 +238,13 @@ async fn main() -> anyhow::Result<()> {
 
             // TODO: make path configurable via env or config file.
             watcher.watch(Path::new("./wasm/output.wasm"), RecursiveMode::NonRecursive)?;
+
+            // Repatching is applied by reloading the guest.
+            if let Some(patch) = &cfg.patch {
+                if let Err(err) = watcher.watch(patch, RecursiveMode::NonRecursive) {
+                    log::warn!("Not watching patch <{}>: {err}", patch.display());
+                }
+            }
             Some(watcher)
         }
         false => None,
@@ -209,6 +273,9 @@ async fn main() -> anyhow::Result<()> {
         to_frontend_consumers: consumers,
         config: Arc::new(Mutex::new(cfg.clone())),
         config_path: config_filepath.to_string(),
+        status,
+        system_out: system_out.clone(),
+        event_log,
     });
 
     let server = HttpServer::new(move || {
@@ -218,6 +285,16 @@ async fn main() -> anyhow::Result<()> {
             // HTML endpoints
             .service(routes::get_index)
             .service(routes::get_dash)
+            // REST endpoints
+            .service(routes::get_config)
+            .service(routes::patch_config)
+            .service(routes::validate_config)
+            .service(routes::get_audio_devices)
+            .service(routes::put_audio_device)
+            .service(routes::post_reload)
+            .service(routes::get_dmx)
+            .service(routes::get_status)
+            .service(routes::get_metrics)
             // API endpoints
             .route("/api/ws", web::get().to(routes::ws_handler))
             .route("/api/ws/sink", web::get().to(routes::binary_ws_handler))
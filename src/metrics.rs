@@ -0,0 +1,182 @@
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+//
+// Process wide metrics, rendered in the Prometheus text format on `/metrics`.
+// Everything is a plain atomic so that the audio thread never blocks on a scrape.
+//
+
+pub static METRICS: Metrics = Metrics::new();
+
+/// Upper bounds of the histogram buckets in microseconds, `+Inf` is implicit.
+const BUCKETS_MICROS: [u64; 10] = [
+    100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000,
+];
+
+pub struct Histogram {
+    // Not cumulative, the counts are summed up when rendering.
+    buckets: [AtomicU64; BUCKETS_MICROS.len() + 1],
+    sum_micros: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const fn new() -> Self {
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+        Self {
+            buckets: [ZERO; BUCKETS_MICROS.len() + 1],
+            sum_micros: ZERO,
+            count: ZERO,
+        }
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let micros = duration.as_micros() as u64;
+        let bucket = BUCKETS_MICROS
+            .iter()
+            .position(|bound| micros <= *bound)
+            .unwrap_or(BUCKETS_MICROS.len());
+
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+
+        let mut cumulative = 0;
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let le = match BUCKETS_MICROS.get(index) {
+                Some(bound) => seconds(*bound).to_string(),
+                None => "+Inf".to_string(),
+            };
+            let _ = writeln!(out, "{name}_bucket{{le=\"{le}\"}} {cumulative}");
+        }
+
+        let sum = seconds(self.sum_micros.load(Ordering::Relaxed));
+        let _ = writeln!(out, "{name}_sum {sum}");
+        let _ = writeln!(out, "{name}_count {}", self.count.load(Ordering::Relaxed));
+    }
+}
+
+pub struct Metrics {
+    pub tick_duration: Histogram,
+    /// Duration of the last iteration of the audio loop.
+    pub loop_duration_micros: AtomicU64,
+    pub wasm_traps: AtomicU64,
+    pub dmx_frames_enttec: AtomicU64,
+    /// Frames of the dummy output, only counted when they changed.
+    pub dmx_frames_dummy: AtomicU64,
+    pub midi_events_in: AtomicU64,
+    pub midi_events_out: AtomicU64,
+    pub websocket_clients: AtomicU64,
+    pub audio_thread_restarts: AtomicU64,
+}
+
+impl Metrics {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const fn new() -> Self {
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+        Self {
+            tick_duration: Histogram::new(),
+            loop_duration_micros: ZERO,
+            wasm_traps: ZERO,
+            dmx_frames_enttec: ZERO,
+            dmx_frames_dummy: ZERO,
+            midi_events_in: ZERO,
+            midi_events_out: ZERO,
+            websocket_clients: ZERO,
+            audio_thread_restarts: ZERO,
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        self.tick_duration.render(
+            &mut out,
+            "blaulicht_tick_duration_seconds",
+            "Duration of a wasm engine tick.",
+        );
+
+        gauge(
+            &mut out,
+            "blaulicht_loop_duration_seconds",
+            "Duration of the last audio loop iteration.",
+            &seconds(self.loop_duration_micros.load(Ordering::Relaxed)).to_string(),
+        );
+
+        counter(
+            &mut out,
+            "blaulicht_wasm_traps_total",
+            "Ticks which failed in the wasm engine.",
+            &[("", &self.wasm_traps)],
+        );
+
+        counter(
+            &mut out,
+            "blaulicht_dmx_frames_total",
+            "DMX frames sent per output.",
+            &[
+                ("output=\"enttec\"", &self.dmx_frames_enttec),
+                ("output=\"dummy\"", &self.dmx_frames_dummy),
+            ],
+        );
+
+        counter(
+            &mut out,
+            "blaulicht_midi_events_total",
+            "MIDI events received from and sent to devices.",
+            &[
+                ("direction=\"in\"", &self.midi_events_in),
+                ("direction=\"out\"", &self.midi_events_out),
+            ],
+        );
+
+        gauge(
+            &mut out,
+            "blaulicht_websocket_clients",
+            "Connected websocket clients.",
+            &self.websocket_clients.load(Ordering::Relaxed).to_string(),
+        );
+
+        counter(
+            &mut out,
+            "blaulicht_audio_thread_restarts_total",
+            "Restarts of the audio thread after a crash.",
+            &[("", &self.audio_thread_restarts)],
+        );
+
+        out
+    }
+}
+
+fn seconds(micros: u64) -> f64 {
+    micros as f64 / 1_000_000.0
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    let _ = writeln!(out, "{name} {value}");
+}
+
+fn counter(out: &mut String, name: &str, help: &str, values: &[(&str, &AtomicU64)]) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} counter");
+
+    for (labels, value) in values {
+        let value = value.load(Ordering::Relaxed);
+        let _ = match labels.is_empty() {
+            true => writeln!(out, "{name} {value}"),
+            false => writeln!(out, "{name}{{{labels}}} {value}"),
+        };
+    }
+}
@@ -0,0 +1,425 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TrySendError};
+
+use crate::{
+    app::{MidiEvent, MidiMessage},
+    msg::BpmInfo,
+    util,
+};
+
+// MIDI clock always runs at 24 pulses per quarter note.
+pub const PULSES_PER_BEAT: u32 = 24;
+
+// Average the tempo over one beat worth of pulses.
+const PULSE_HISTORY_LEN: usize = PULSES_PER_BEAT as usize;
+
+// The clock is considered lost if no pulse arrived within this duration.
+const CLOCK_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Transport {
+    // No start / stop was received yet, pulses are counted anyway.
+    Unknown,
+    Running,
+    Stopped,
+}
+
+struct ClockState {
+    transport: Transport,
+    last_pulse: Option<Instant>,
+    pulse_intervals: VecDeque<Duration>,
+    // Pulses since the last start message.
+    pulses: u32,
+}
+
+//
+// Follows the MIDI clock of a single input device.
+// Shared between the MIDI input callback (writer) and the audio thread (reader).
+//
+
+#[derive(Clone)]
+pub struct MidiClock {
+    state: Arc<Mutex<ClockState>>,
+}
+
+impl Default for MidiClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MidiClock {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ClockState {
+                transport: Transport::Unknown,
+                last_pulse: None,
+                pulse_intervals: VecDeque::with_capacity(PULSE_HISTORY_LEN),
+                pulses: 0,
+            })),
+        }
+    }
+
+    /// Feeds a raw MIDI message into the clock, everything except realtime messages is ignored.
+    pub fn handle(&self, message: &[u8], now: Instant) {
+        let mut state = self.state.lock().unwrap();
+
+        match message.first() {
+            Some(0xF8) => {
+                if let Some(last) = state.last_pulse {
+                    let interval = now.duration_since(last);
+
+                    // A gap means that the clock was interrupted, start averaging from scratch.
+                    if interval > CLOCK_TIMEOUT {
+                        state.pulse_intervals.clear();
+                    } else {
+                        if state.pulse_intervals.len() >= PULSE_HISTORY_LEN {
+                            state.pulse_intervals.pop_front();
+                        }
+                        state.pulse_intervals.push_back(interval);
+                    }
+                }
+
+                state.last_pulse = Some(now);
+                if state.transport != Transport::Stopped {
+                    state.pulses = state.pulses.wrapping_add(1);
+                }
+            }
+            Some(0xFA) => {
+                log::debug!("[MIDI-CLOCK] Start");
+                state.transport = Transport::Running;
+                state.pulses = 0;
+            }
+            Some(0xFB) => {
+                log::debug!("[MIDI-CLOCK] Continue");
+                state.transport = Transport::Running;
+            }
+            Some(0xFC) => {
+                log::debug!("[MIDI-CLOCK] Stop");
+                state.transport = Transport::Stopped;
+            }
+            _ => {}
+        }
+    }
+
+    /// Tempo of the external clock, `None` if no clock is received.
+    pub fn tempo(&self) -> Option<BpmInfo> {
+        let state = self.state.lock().unwrap();
+
+        if state
+            .last_pulse
+            .map_or(true, |last| last.elapsed() > CLOCK_TIMEOUT)
+            || state.pulse_intervals.len() < PULSE_HISTORY_LEN / 2
+        {
+            return None;
+        }
+
+        let pulse =
+            state.pulse_intervals.iter().sum::<Duration>() / state.pulse_intervals.len() as u32;
+        let beat = pulse * PULSES_PER_BEAT;
+
+        Some(BpmInfo {
+            bpm: (60.0 / beat.as_secs_f64()).round().min(u8::MAX as f64) as u8,
+            time_between_beats_millis: beat.as_millis().min(u16::MAX as u128) as u16,
+        })
+    }
+
+    /// Position inside the current beat (0-255), `None` if the transport is stopped or no clock is received.
+    pub fn beat_phase(&self) -> Option<u8> {
+        self.tempo()?;
+
+        let state = self.state.lock().unwrap();
+        if state.transport == Transport::Stopped {
+            return None;
+        }
+
+        let pulse = state.pulses % PULSES_PER_BEAT;
+        Some((pulse * 256 / PULSES_PER_BEAT) as u8)
+    }
+}
+
+//
+// Emits MIDI clock derived from the current BPM.
+// Runs on its own thread which sleeps until the next pulse is due, the audio loop only
+// forwards tempo changes. Pulses go out on the dedicated clock channel of the MIDI thread.
+//
+
+// Tempo changes waiting for the clock thread.
+const TEMPO_QUEUE_LEN: usize = 16;
+
+/// Handle of the clock thread, which stops once the handle is dropped.
+pub struct MidiClockOutput {
+    tempo: Option<Sender<u8>>,
+    bpm: u8,
+}
+
+impl MidiClockOutput {
+    /// Does not start a thread if there are no devices.
+    pub fn spawn(devices: Vec<u8>, clock_out: Sender<MidiEvent>) -> Self {
+        if devices.is_empty() {
+            return Self {
+                tempo: None,
+                bpm: 0,
+            };
+        }
+
+        let (tempo, tempo_receiver) = crossbeam_channel::bounded(TEMPO_QUEUE_LEN);
+        thread::spawn(move || MidiClockGenerator::new(devices).run(tempo_receiver, clock_out));
+
+        Self {
+            tempo: Some(tempo),
+            bpm: 0,
+        }
+    }
+
+    pub fn set_bpm(&mut self, bpm: u8) {
+        let Some(tempo) = &self.tempo else {
+            return;
+        };
+
+        // Retried on the next call if the queue is full.
+        if bpm != self.bpm && tempo.try_send(bpm).is_ok() {
+            self.bpm = bpm;
+        }
+    }
+}
+
+struct MidiClockGenerator {
+    devices: Vec<u8>,
+    bpm: u8,
+    next_pulse: Option<Instant>,
+}
+
+impl MidiClockGenerator {
+    fn new(devices: Vec<u8>) -> Self {
+        Self {
+            devices,
+            bpm: 0,
+            next_pulse: None,
+        }
+    }
+
+    fn run(mut self, tempo: Receiver<u8>, clock_out: Sender<MidiEvent>) {
+        util::increase_thread_priority();
+
+        loop {
+            // Without a running clock, there is nothing to do until the tempo changes.
+            let received = match self.next_pulse {
+                Some(next_pulse) => tempo.recv_deadline(next_pulse),
+                None => tempo.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+
+            match received {
+                Ok(bpm) => self.bpm = bpm,
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            self.poll(Instant::now(), &clock_out);
+        }
+
+        if self.next_pulse.is_some() {
+            self.send(MidiMessage::Stop, &clock_out);
+        }
+        log::debug!("[MIDI-CLOCK] Output stopped");
+    }
+
+    fn poll(&mut self, now: Instant, clock_out: &Sender<MidiEvent>) {
+        // No tempo, stop the followers.
+        if self.bpm == 0 {
+            if self.next_pulse.take().is_some() {
+                self.send(MidiMessage::Stop, clock_out);
+            }
+            return;
+        }
+
+        let pulse_interval = Duration::from_secs(60) / (self.bpm as u32 * PULSES_PER_BEAT);
+
+        let next_pulse = match self.next_pulse {
+            Some(next_pulse) => next_pulse,
+            None => {
+                self.send(MidiMessage::Start, clock_out);
+                now
+            }
+        };
+
+        if now < next_pulse {
+            self.next_pulse = Some(next_pulse);
+            return;
+        }
+
+        self.send(MidiMessage::Clock, clock_out);
+
+        // Do not try to catch up on missed pulses, this would only burst.
+        let next_pulse = next_pulse + pulse_interval;
+        self.next_pulse = Some(if next_pulse < now {
+            now + pulse_interval
+        } else {
+            next_pulse
+        });
+    }
+
+    /// Pulses are skipped while the queue is full, Start and Stop wait for room.
+    fn send(&self, message: MidiMessage, clock_out: &Sender<MidiEvent>) {
+        for device in &self.devices {
+            let event = MidiEvent {
+                device: *device,
+                message: message.clone(),
+            };
+
+            if message != MidiMessage::Clock {
+                // Only fails once the MIDI thread is gone.
+                let _ = clock_out.send(event);
+                continue;
+            }
+
+            match clock_out.try_send(event) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    log::trace!("[MIDI-CLOCK] Clock queue is full, skipping {message:?}");
+                }
+                Err(TrySendError::Disconnected(_)) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(receiver: &Receiver<MidiEvent>) -> Vec<MidiMessage> {
+        receiver.try_iter().map(|event| event.message).collect()
+    }
+
+    #[test]
+    fn follows_the_pulses_of_a_clock() {
+        let clock = MidiClock::new();
+        // 125 BPM, the last pulse arrives right now.
+        let pulse = Duration::from_millis(20);
+        let pulses = PULSES_PER_BEAT + 6;
+        let start = Instant::now() - pulse * pulses;
+
+        clock.handle(&[0xFA], start);
+        assert!(clock.tempo().is_none());
+
+        for pulse_index in 1..=pulses {
+            clock.handle(&[0xF8], start + pulse * pulse_index);
+        }
+
+        let tempo = clock.tempo().unwrap();
+        assert_eq!(tempo.bpm, 125);
+        assert_eq!(tempo.time_between_beats_millis, 480);
+        assert_eq!(clock.beat_phase(), Some(64));
+    }
+
+    #[test]
+    fn stop_holds_the_pulse_count() {
+        let clock = MidiClock::new();
+        let now = Instant::now();
+
+        clock.handle(&[0xFA], now);
+        clock.handle(&[0xF8], now);
+        clock.handle(&[0xFC], now);
+        clock.handle(&[0xF8], now);
+        assert_eq!(clock.state.lock().unwrap().pulses, 1);
+
+        clock.handle(&[0xFB], now);
+        clock.handle(&[0xF8], now);
+        assert_eq!(clock.state.lock().unwrap().pulses, 2);
+    }
+
+    #[test]
+    fn generator_starts_pulses_and_stops() {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let mut generator = MidiClockGenerator::new(vec![1]);
+        let start = Instant::now();
+
+        generator.poll(start, &sender);
+        assert!(messages(&receiver).is_empty());
+
+        // 125 BPM, a pulse every 20 ms.
+        generator.bpm = 125;
+        generator.poll(start, &sender);
+        assert_eq!(
+            messages(&receiver),
+            [MidiMessage::Start, MidiMessage::Clock]
+        );
+        assert_eq!(
+            generator.next_pulse,
+            Some(start + Duration::from_millis(20))
+        );
+
+        generator.poll(start + Duration::from_millis(10), &sender);
+        assert!(messages(&receiver).is_empty());
+
+        generator.poll(start + Duration::from_millis(20), &sender);
+        assert_eq!(messages(&receiver), [MidiMessage::Clock]);
+
+        generator.bpm = 0;
+        generator.poll(start + Duration::from_millis(30), &sender);
+        assert_eq!(messages(&receiver), [MidiMessage::Stop]);
+        assert_eq!(generator.next_pulse, None);
+    }
+
+    #[test]
+    fn generator_skips_missed_pulses() {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let mut generator = MidiClockGenerator::new(vec![1, 2]);
+        let start = Instant::now();
+
+        generator.bpm = 125;
+        generator.poll(start, &sender);
+        receiver.try_iter().for_each(drop);
+
+        let late = start + Duration::from_millis(100);
+        generator.poll(late, &sender);
+        assert_eq!(
+            messages(&receiver),
+            [MidiMessage::Clock, MidiMessage::Clock]
+        );
+        assert_eq!(generator.next_pulse, Some(late + Duration::from_millis(20)));
+    }
+
+    #[test]
+    fn transport_waits_for_a_full_queue() {
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        let mut generator = MidiClockGenerator::new(vec![1]);
+        let start = Instant::now();
+
+        // The pulse does not fit behind Start and is skipped.
+        generator.bpm = 125;
+        generator.poll(start, &sender);
+
+        generator.bpm = 0;
+        let stopping = thread::spawn(move || generator.poll(start, &sender));
+
+        assert_eq!(receiver.recv().unwrap().message, MidiMessage::Start);
+        assert_eq!(receiver.recv().unwrap().message, MidiMessage::Stop);
+        stopping.join().unwrap();
+    }
+
+    #[test]
+    fn clock_thread_stops_with_its_handle() {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let mut output = MidiClockOutput::spawn(vec![3], sender);
+
+        output.set_bpm(120);
+        let first = receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(first.device, 3);
+        assert_eq!(first.message, MidiMessage::Start);
+
+        drop(output);
+        let stop = receiver
+            .iter()
+            .map(|event| event.message)
+            .find(|message| *message == MidiMessage::Stop);
+        assert_eq!(stop, Some(MidiMessage::Stop));
+    }
+}
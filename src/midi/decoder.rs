@@ -0,0 +1,292 @@
+use crate::app::{MidiEvent, MidiMessage};
+
+use super::MidiError;
+
+// Controllers with a special meaning for (N)RPN parameter selection.
+const CC_DATA_ENTRY_MSB: u8 = 6;
+const CC_DATA_ENTRY_LSB: u8 = 38;
+const CC_NRPN_LSB: u8 = 98;
+const CC_NRPN_MSB: u8 = 99;
+const CC_RPN_LSB: u8 = 100;
+const CC_RPN_MSB: u8 = 101;
+
+#[derive(Default, Clone, Copy)]
+struct ChannelState {
+    // Last MSB received for each 14-bit controller (CC 0-31).
+    cc_msb: [Option<u8>; 32],
+    nrpn_parameter_msb: Option<u8>,
+    nrpn_parameter: Option<u16>,
+    nrpn_value_msb: Option<u8>,
+}
+
+//
+// Turns raw bytes of a single device into `MidiEvent`s.
+// Keeps per-channel state so that 14-bit CC pairs and NRPN sequences can be combined.
+// The raw control changes are always emitted as well, so plain 7-bit mappings keep working.
+//
+
+pub struct MidiDecoder {
+    device: u8,
+    channels: [ChannelState; 16],
+}
+
+impl MidiDecoder {
+    pub fn new(device: u8) -> Self {
+        Self {
+            device,
+            channels: [ChannelState::default(); 16],
+        }
+    }
+
+    pub fn decode(&mut self, bytes: &[u8]) -> Result<Vec<MidiEvent>, MidiError> {
+        let message = wmidi::MidiMessage::try_from(bytes)
+            .map_err(|e| MidiError::Decode(format!("{e:?} in {bytes:02X?}")))?;
+
+        let messages = match message {
+            wmidi::MidiMessage::NoteOff(channel, note, velocity) => vec![MidiMessage::NoteOff {
+                channel: channel.index(),
+                note: note.into(),
+                velocity: velocity.into(),
+            }],
+            wmidi::MidiMessage::NoteOn(channel, note, velocity) => vec![MidiMessage::NoteOn {
+                channel: channel.index(),
+                note: note.into(),
+                velocity: velocity.into(),
+            }],
+            wmidi::MidiMessage::PolyphonicKeyPressure(channel, note, pressure) => {
+                vec![MidiMessage::PolyPressure {
+                    channel: channel.index(),
+                    note: note.into(),
+                    pressure: pressure.into(),
+                }]
+            }
+            wmidi::MidiMessage::ControlChange(channel, function, value) => {
+                self.control_change(channel.index(), function.0.into(), value.into())
+            }
+            wmidi::MidiMessage::ProgramChange(channel, program) => {
+                vec![MidiMessage::ProgramChange {
+                    channel: channel.index(),
+                    program: program.into(),
+                }]
+            }
+            wmidi::MidiMessage::ChannelPressure(channel, pressure) => {
+                vec![MidiMessage::ChannelPressure {
+                    channel: channel.index(),
+                    pressure: pressure.into(),
+                }]
+            }
+            wmidi::MidiMessage::PitchBendChange(channel, value) => {
+                vec![MidiMessage::PitchBend {
+                    channel: channel.index(),
+                    value: value.into(),
+                }]
+            }
+            wmidi::MidiMessage::SysEx(_) | wmidi::MidiMessage::OwnedSysEx(_) => {
+                vec![MidiMessage::SysEx(bytes.to_vec())]
+            }
+            // Transport is forwarded, clock pulses are consumed by the `MidiClock`.
+            wmidi::MidiMessage::Start => vec![MidiMessage::Start],
+            wmidi::MidiMessage::Continue => vec![MidiMessage::Continue],
+            wmidi::MidiMessage::Stop => vec![MidiMessage::Stop],
+            // Other system common and realtime messages are not forwarded.
+            _ => vec![],
+        };
+
+        Ok(messages
+            .into_iter()
+            .map(|message| MidiEvent {
+                device: self.device,
+                message,
+            })
+            .collect())
+    }
+
+    fn control_change(&mut self, channel: u8, control: u8, value: u8) -> Vec<MidiMessage> {
+        let state = &mut self.channels[channel as usize];
+        let mut messages = vec![MidiMessage::ControlChange {
+            channel,
+            control,
+            value,
+        }];
+
+        match control {
+            CC_NRPN_MSB => {
+                state.nrpn_parameter_msb = Some(value);
+                state.nrpn_parameter = None;
+                state.nrpn_value_msb = None;
+            }
+            CC_NRPN_LSB => {
+                if let Some(msb) = state.nrpn_parameter_msb {
+                    state.nrpn_parameter = Some(((msb as u16) << 7) | value as u16);
+                    state.nrpn_value_msb = None;
+                }
+            }
+            CC_RPN_MSB | CC_RPN_LSB => {
+                // Data entry now belongs to an RPN, which is not decoded.
+                state.nrpn_parameter_msb = None;
+                state.nrpn_parameter = None;
+                state.nrpn_value_msb = None;
+            }
+            CC_DATA_ENTRY_MSB if state.nrpn_parameter.is_some() => {
+                state.nrpn_value_msb = Some(value);
+                messages.push(MidiMessage::Nrpn {
+                    channel,
+                    parameter: state.nrpn_parameter.unwrap(),
+                    value: (value as u16) << 7,
+                });
+            }
+            CC_DATA_ENTRY_LSB if state.nrpn_parameter.is_some() => {
+                if let Some(msb) = state.nrpn_value_msb {
+                    messages.push(MidiMessage::Nrpn {
+                        channel,
+                        parameter: state.nrpn_parameter.unwrap(),
+                        value: ((msb as u16) << 7) | value as u16,
+                    });
+                }
+            }
+            0..=31 => state.cc_msb[control as usize] = Some(value),
+            32..=63 => {
+                if let Some(msb) = state.cc_msb[(control - 32) as usize] {
+                    messages.push(MidiMessage::ControlChange14 {
+                        channel,
+                        control: control - 32,
+                        value: ((msb as u16) << 7) | value as u16,
+                    });
+                }
+            }
+            _ => {}
+        }
+
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(decoder: &mut MidiDecoder, bytes: &[u8]) -> Vec<MidiMessage> {
+        decoder
+            .decode(bytes)
+            .unwrap()
+            .into_iter()
+            .map(|event| event.message)
+            .collect()
+    }
+
+    #[test]
+    fn channel_messages() {
+        let mut decoder = MidiDecoder::new(2);
+
+        let events = decoder.decode(&[0x91, 60, 100]).unwrap();
+        assert_eq!(events[0].device, 2);
+        assert_eq!(
+            events[0].message,
+            MidiMessage::NoteOn {
+                channel: 1,
+                note: 60,
+                velocity: 100
+            }
+        );
+        assert_eq!(
+            decode(&mut decoder, &[0xC3, 5]),
+            [MidiMessage::ProgramChange {
+                channel: 3,
+                program: 5
+            }]
+        );
+        assert_eq!(
+            decode(&mut decoder, &[0xD0, 42]),
+            [MidiMessage::ChannelPressure {
+                channel: 0,
+                pressure: 42
+            }]
+        );
+        assert_eq!(
+            decode(&mut decoder, &[0xE0, 0x00, 0x40]),
+            [MidiMessage::PitchBend {
+                channel: 0,
+                value: 0x2000
+            }]
+        );
+    }
+
+    #[test]
+    fn sysex_and_transport() {
+        let mut decoder = MidiDecoder::new(0);
+
+        assert_eq!(
+            decode(&mut decoder, &[0xF0, 0x7D, 0x01, 0xF7]),
+            [MidiMessage::SysEx(vec![0xF0, 0x7D, 0x01, 0xF7])]
+        );
+        assert_eq!(decode(&mut decoder, &[0xFA]), [MidiMessage::Start]);
+        assert_eq!(decode(&mut decoder, &[0xF8]), []);
+    }
+
+    #[test]
+    fn invalid_bytes_are_an_error() {
+        let mut decoder = MidiDecoder::new(0);
+
+        assert!(decoder.decode(&[]).is_err());
+        assert!(decoder.decode(&[0x90, 60]).is_err());
+    }
+
+    #[test]
+    fn cc_pairs_are_combined() {
+        let mut decoder = MidiDecoder::new(0);
+
+        // The LSB alone is a plain 7-bit controller.
+        assert_eq!(decode(&mut decoder, &[0xB0, 51, 10]).len(), 1);
+
+        decode(&mut decoder, &[0xB0, 19, 0x40]);
+        assert_eq!(
+            decode(&mut decoder, &[0xB0, 51, 0x01]),
+            [
+                MidiMessage::ControlChange {
+                    channel: 0,
+                    control: 51,
+                    value: 0x01
+                },
+                MidiMessage::ControlChange14 {
+                    channel: 0,
+                    control: 19,
+                    value: 0x2001
+                },
+            ]
+        );
+
+        // The MSB is kept per channel.
+        assert_eq!(decode(&mut decoder, &[0xB1, 51, 0x01]).len(), 1);
+    }
+
+    #[test]
+    fn nrpn_sequences_are_combined() {
+        let mut decoder = MidiDecoder::new(0);
+
+        decode(&mut decoder, &[0xB0, CC_NRPN_MSB, 0x01]);
+        decode(&mut decoder, &[0xB0, CC_NRPN_LSB, 0x02]);
+        assert_eq!(
+            decode(&mut decoder, &[0xB0, CC_DATA_ENTRY_MSB, 0x10])[1],
+            MidiMessage::Nrpn {
+                channel: 0,
+                parameter: 0x82,
+                value: 0x10 << 7
+            }
+        );
+        assert_eq!(
+            decode(&mut decoder, &[0xB0, CC_DATA_ENTRY_LSB, 0x05])[1],
+            MidiMessage::Nrpn {
+                channel: 0,
+                parameter: 0x82,
+                value: (0x10 << 7) | 0x05
+            }
+        );
+
+        // Data entry of an RPN is not an NRPN value.
+        decode(&mut decoder, &[0xB0, CC_RPN_MSB, 0x00]);
+        assert_eq!(
+            decode(&mut decoder, &[0xB0, CC_DATA_ENTRY_MSB, 0x10]).len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::{MidiEvent, MidiMessage};
+
+use super::mapping::{ControlMode, InputMatch};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum MidiLearnTarget {
+    /// Reuses name and mode of the control already mapped to this cell.
+    Matrix { x: u8, y: u8 },
+    /// The mode is derived from the learned input if omitted.
+    Control {
+        name: String,
+        #[serde(default)]
+        mode: Option<ControlMode>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct MidiLearnRequest {
+    pub target: MidiLearnTarget,
+    /// Mapping file the learned control is saved to.
+    pub mapping_path: PathBuf,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "state")]
+pub enum MidiLearnStatus {
+    Waiting {
+        target: MidiLearnTarget,
+    },
+    Learned {
+        name: String,
+        device: Option<String>,
+        input: InputMatch,
+        mode: ControlMode,
+    },
+    Cancelled,
+    Failed {
+        message: String,
+    },
+}
+
+//
+// Pending learn request, set by the supervisor and completed by the mapper in the audio thread.
+//
+
+#[derive(Clone, Default)]
+pub struct MidiLearn {
+    pending: Arc<Mutex<Option<MidiLearnRequest>>>,
+}
+
+impl MidiLearn {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts learning, replaces a request which is still pending.
+    pub fn start(&self, request: MidiLearnRequest) {
+        *self.pending.lock().unwrap() = Some(request);
+    }
+
+    /// Returns whether there was a pending request.
+    pub fn cancel(&self) -> bool {
+        self.pending.lock().unwrap().take().is_some()
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending.lock().unwrap().is_some()
+    }
+
+    pub(super) fn take(&self) -> Option<MidiLearnRequest> {
+        self.pending.lock().unwrap().take()
+    }
+}
+
+//
+// Picks the input to bind from the events received in one tick.
+// A fader sends plain CCs before the 14-bit value, so the more specific inputs win.
+//
+
+pub(super) fn learnable_input(events: &[MidiEvent]) -> Option<(u8, InputMatch)> {
+    events
+        .iter()
+        .filter_map(|event| {
+            let (priority, input) = match event.message {
+                MidiMessage::Nrpn {
+                    channel, parameter, ..
+                } => (3, InputMatch::Nrpn { channel, parameter }),
+                MidiMessage::ControlChange14 {
+                    channel, control, ..
+                } => (2, InputMatch::Cc14 { channel, control }),
+                MidiMessage::PitchBend { channel, .. } => (1, InputMatch::PitchBend { channel }),
+                MidiMessage::ControlChange {
+                    channel, control, ..
+                } => (0, InputMatch::Cc { channel, control }),
+                MidiMessage::NoteOn { channel, note, .. } => (0, InputMatch::Note { channel, note }),
+                _ => return None,
+            };
+
+            Some((priority, (event.device, input)))
+        })
+        .max_by_key(|(priority, _)| *priority)
+        .map(|(_, input)| input)
+}
@@ -0,0 +1,801 @@
+use std::{collections::HashMap, fs, mem, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{ControlEvent, ControlValue, MidiEvent, MidiMessage},
+    config::MidiConfig,
+    msg::{SystemMessage, WasmControlsSet},
+};
+
+use super::learn::{self, MidiLearn, MidiLearnRequest, MidiLearnStatus, MidiLearnTarget};
+
+// Device id of the control matrix in the dashboard.
+pub const BUILTIN_DEVICE: u8 = 255;
+
+//
+// Mapping file format.
+//
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MidiMappingFile {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sysex: Vec<SysExMapping>,
+    #[serde(default, rename = "control")]
+    pub controls: Vec<ControlMapping>,
+}
+
+/// Sent once the mapping is loaded, e.g. to set the LED mode of a controller.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SysExMapping {
+    /// Name of the device as given in `midi.devices`.
+    pub device: String,
+    /// The complete message, including 0xF0 and 0xF7.
+    pub data: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ControlMapping {
+    /// Name of the action the guest receives, e.g. `strobe.brightness`.
+    pub name: String,
+    /// Name of the device as given in `midi.devices`, any device if omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    pub input: InputMatch,
+    pub mode: ControlMode,
+    #[serde(default)]
+    pub curve: FaderCurve,
+    /// Output range of a fader.
+    #[serde(default = "default_range")]
+    pub range: [f32; 2],
+    #[serde(default)]
+    pub encoding: EncoderEncoding,
+    /// LED feedback for toggles and momentary buttons.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feedback: Option<Feedback>,
+}
+
+fn default_range() -> [f32; 2] {
+    [0.0, 1.0]
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputMatch {
+    Note { channel: u8, note: u8 },
+    Cc { channel: u8, control: u8 },
+    // MSB controller number (0-31), the LSB is `control + 32`.
+    Cc14 { channel: u8, control: u8 },
+    Nrpn { channel: u8, parameter: u16 },
+    PitchBend { channel: u8 },
+    // A cell of the control matrix in the dashboard.
+    Matrix { x: u8, y: u8 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlMode {
+    /// Every press flips the state.
+    Toggle,
+    /// On while held. Matrix cells only send the press.
+    Momentary,
+    /// Absolute value, scaled by `curve` into `range`.
+    Fader,
+    /// Relative steps, decoded using `encoding`.
+    Encoder,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FaderCurve {
+    #[default]
+    Linear,
+    Exponential,
+    Logarithmic,
+}
+
+// 14-bit inputs encode the same way, with 8192 as the half of the range and bit 13 as the sign.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EncoderEncoding {
+    /// 1..63 is up, 127..65 is down.
+    #[default]
+    TwosComplement,
+    /// 64 is the center, above is up.
+    Offset,
+    /// Bit 6 is the sign, the rest is the magnitude.
+    SignMagnitude,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Feedback {
+    #[serde(default = "default_feedback_on")]
+    pub on: u8,
+    #[serde(default)]
+    pub off: u8,
+}
+
+fn default_feedback_on() -> u8 {
+    127
+}
+
+impl FaderCurve {
+    fn apply(&self, value: f32) -> f32 {
+        match self {
+            FaderCurve::Linear => value,
+            FaderCurve::Exponential => value * value,
+            FaderCurve::Logarithmic => value.sqrt(),
+        }
+    }
+}
+
+impl EncoderEncoding {
+    // `max` is the maximum of the input, 7-bit unless it is above 127.
+    fn decode(&self, value: u16, max: u16) -> i32 {
+        let range = if max > 0x7F { 0x4000 } else { 0x80 };
+        let half = range / 2;
+        let value = value as i32 & (range - 1);
+
+        match self {
+            EncoderEncoding::TwosComplement if value >= half => value - range,
+            EncoderEncoding::TwosComplement => value,
+            EncoderEncoding::Offset => value - half,
+            EncoderEncoding::SignMagnitude if value & half != 0 => -(value & (half - 1)),
+            EncoderEncoding::SignMagnitude => value,
+        }
+    }
+}
+
+impl InputMatch {
+    //
+    // Returns the raw value and its maximum if the message belongs to this input.
+    //
+
+    fn value_of(&self, device: u8, message: &MidiMessage) -> Option<(u16, u16)> {
+        match (*self, message) {
+            (InputMatch::Matrix { x, y }, MidiMessage::Raw { status, data0, data1 })
+                if device == BUILTIN_DEVICE && *status == y && *data0 == x =>
+            {
+                Some((*data1 as u16, 1))
+            }
+            (
+                InputMatch::Note { channel, note },
+                MidiMessage::NoteOn {
+                    channel: c,
+                    note: n,
+                    velocity,
+                },
+            ) if channel == *c && note == *n => Some((*velocity as u16, 127)),
+            (
+                InputMatch::Note { channel, note },
+                MidiMessage::NoteOff {
+                    channel: c, note: n, ..
+                },
+            ) if channel == *c && note == *n => Some((0, 127)),
+            (
+                InputMatch::Cc { channel, control },
+                MidiMessage::ControlChange {
+                    channel: c,
+                    control: n,
+                    value,
+                },
+            ) if channel == *c && control == *n => Some((*value as u16, 127)),
+            (
+                InputMatch::Cc14 { channel, control },
+                MidiMessage::ControlChange14 {
+                    channel: c,
+                    control: n,
+                    value,
+                },
+            ) if channel == *c && control == *n => Some((*value, 0x3FFF)),
+            (
+                InputMatch::Nrpn { channel, parameter },
+                MidiMessage::Nrpn {
+                    channel: c,
+                    parameter: p,
+                    value,
+                },
+            ) if channel == *c && parameter == *p => Some((*value, 0x3FFF)),
+            (InputMatch::PitchBend { channel }, MidiMessage::PitchBend { channel: c, value })
+                if channel == *c =>
+            {
+                Some((*value, 0x3FFF))
+            }
+            _ => None,
+        }
+    }
+
+    //
+    // The plain controllers a 14-bit controller is made of, they are swallowed as well.
+    //
+
+    fn covers(&self, message: &MidiMessage) -> bool {
+        match (*self, message) {
+            (
+                InputMatch::Cc14 { channel, control },
+                MidiMessage::ControlChange {
+                    channel: c,
+                    control: n,
+                    ..
+                },
+            ) => channel == *c && (*n == control || *n == control + 32),
+            _ => false,
+        }
+    }
+
+    fn feedback_message(&self, value: u8) -> Option<MidiMessage> {
+        match *self {
+            InputMatch::Note { channel, note } => Some(MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity: value,
+            }),
+            InputMatch::Cc { channel, control } => Some(MidiMessage::ControlChange {
+                channel,
+                control,
+                value,
+            }),
+            _ => None,
+        }
+    }
+}
+
+//
+// Translates raw MIDI and matrix events into named control events for the guest.
+//
+
+pub struct MidiMapper {
+    config: MidiConfig,
+    learn: MidiLearn,
+    controls: Vec<(Option<u8>, ControlMapping)>,
+    // SysEx messages of the mapping file, not sent yet.
+    init: Vec<MidiEvent>,
+    // Current state of the toggles by control name, shared by all inputs of the same control.
+    toggles: HashMap<String, bool>,
+}
+
+pub struct MappedEvents {
+    /// Events without a mapping, still passed to the guest as they are.
+    pub unmapped: Vec<MidiEvent>,
+    pub controls: Vec<ControlEvent>,
+    /// LED feedback to be sent to the devices.
+    pub feedback: Vec<MidiEvent>,
+    /// Feedback for the control matrix and MIDI learn results for the dashboard.
+    pub system: Vec<SystemMessage>,
+}
+
+impl MidiMapper {
+    pub fn new(file: MidiMappingFile, config: &MidiConfig, learn: MidiLearn) -> Self {
+        let mut mapper = Self {
+            config: config.clone(),
+            learn,
+            controls: vec![],
+            init: vec![],
+            toggles: HashMap::new(),
+        };
+
+        for sysex in file.sysex {
+            mapper.add_sysex(sysex);
+        }
+        for control in file.controls {
+            mapper.add(control);
+        }
+
+        mapper
+    }
+
+    /// Loads the mapping file configured in `midi.mapping`, an empty mapping is used if there is none.
+    pub fn load(config: &MidiConfig, learn: MidiLearn) -> Result<Self> {
+        let file = match &config.mapping {
+            Some(path) if path.exists() => read_mapping(path)?,
+            _ => MidiMappingFile::default(),
+        };
+
+        log::info!("[MIDI-MAP] Loaded {} control(s)", file.controls.len());
+        Ok(Self::new(file, config, learn))
+    }
+
+    pub fn config(&self) -> &MidiConfig {
+        &self.config
+    }
+
+    fn add(&mut self, control: ControlMapping) {
+        match &control.device {
+            None => self.controls.push((None, control)),
+            Some(name) => match self.config.device_id(name) {
+                Some(id) => self.controls.push((Some(id), control)),
+                None => {
+                    log::warn!(
+                        "[MIDI-MAP] Unknown device <{name}> for control <{}>, ignoring",
+                        control.name
+                    );
+                }
+            },
+        }
+    }
+
+    fn add_sysex(&mut self, sysex: SysExMapping) {
+        if !matches!(sysex.data.as_slice(), [0xF0, data @ .., 0xF7] if data.iter().all(|b| *b < 0x80))
+        {
+            log::warn!(
+                "[MIDI-MAP] Invalid SysEx for <{}>, ignoring: {:02X?}",
+                sysex.device,
+                sysex.data
+            );
+            return;
+        }
+
+        match self.config.device_id(&sysex.device) {
+            Some(device) => self.init.push(MidiEvent {
+                device,
+                message: MidiMessage::SysEx(sysex.data),
+            }),
+            None => log::warn!(
+                "[MIDI-MAP] Unknown device <{}> for SysEx, ignoring",
+                sysex.device
+            ),
+        }
+    }
+
+    /// The SysEx messages of the mapping file, only returned once.
+    pub fn take_init(&mut self) -> Vec<MidiEvent> {
+        mem::take(&mut self.init)
+    }
+
+    pub fn map(&mut self, mut events: Vec<MidiEvent>) -> MappedEvents {
+        let mut mapped = MappedEvents {
+            unmapped: vec![],
+            controls: vec![],
+            feedback: vec![],
+            system: vec![],
+        };
+
+        // The events which completed a learn request are not passed on.
+        if self.learn.is_pending() {
+            if let Some((device, input)) = learn::learnable_input(&events) {
+                if let Some(request) = self.learn.take() {
+                    let status = self.complete_learn(request, device, input);
+                    mapped.system.push(SystemMessage::MidiLearn(status));
+                    events.clear();
+                }
+            }
+        }
+
+        for event in events {
+            let mut handled = false;
+
+            for (index, (device, control)) in self.controls.iter().enumerate() {
+                if device.is_some_and(|device| device != event.device) {
+                    continue;
+                }
+
+                if control.input.covers(&event.message) {
+                    handled = true;
+                    continue;
+                }
+
+                let Some((raw, max)) = control.input.value_of(event.device, &event.message)
+                else {
+                    continue;
+                };
+                handled = true;
+
+                let is_matrix = matches!(control.input, InputMatch::Matrix { .. });
+                // Matrix cells send their current state on every click, so every message is a press.
+                let pressed = is_matrix || raw > 0;
+
+                let value = match control.mode {
+                    ControlMode::Toggle => {
+                        if !pressed {
+                            continue;
+                        }
+
+                        let state = self.toggles.entry(control.name.clone()).or_default();
+                        *state = !*state;
+                        ControlValue::Bool(*state)
+                    }
+                    ControlMode::Momentary => ControlValue::Bool(pressed),
+                    ControlMode::Fader => {
+                        let [min, max_out] = control.range;
+                        let normalized = control.curve.apply(raw as f32 / max as f32);
+                        ControlValue::Float(min + normalized * (max_out - min))
+                    }
+                    ControlMode::Encoder => {
+                        ControlValue::Relative(control.encoding.decode(raw, max))
+                    }
+                };
+
+                if let ControlValue::Bool(on) = value {
+                    self.feedback(&mut mapped, &control.name, index, event.device, on);
+                }
+
+                mapped.controls.push(ControlEvent {
+                    name: control.name.clone(),
+                    value,
+                });
+            }
+
+            if !handled {
+                mapped.unmapped.push(event);
+            }
+        }
+
+        mapped
+    }
+
+    //
+    // Updates the LEDs of every input bound to the same control name.
+    // Inputs without a configured device only get feedback when they triggered the change.
+    //
+
+    fn feedback(
+        &self,
+        mapped: &mut MappedEvents,
+        name: &str,
+        source_index: usize,
+        source_device: u8,
+        on: bool,
+    ) {
+        for (index, (device, control)) in self.controls.iter().enumerate() {
+            let Some(feedback) = control.feedback else {
+                continue;
+            };
+
+            if control.name != name {
+                continue;
+            }
+
+            match control.input {
+                InputMatch::Matrix { x, y } => {
+                    mapped
+                        .system
+                        .push(SystemMessage::WasmControlsSet(WasmControlsSet {
+                            x,
+                            y,
+                            value: on,
+                        }))
+                }
+                input => {
+                    let device = match device {
+                        Some(device) => *device,
+                        None if index == source_index => source_device,
+                        None => continue,
+                    };
+
+                    let level = if on { feedback.on } else { feedback.off };
+                    if let Some(message) = input.feedback_message(level) {
+                        mapped.feedback.push(MidiEvent { device, message });
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl MidiMapper {
+    fn complete_learn(
+        &mut self,
+        request: MidiLearnRequest,
+        device: u8,
+        input: InputMatch,
+    ) -> MidiLearnStatus {
+        let (name, mode, feedback) = match request.target {
+            MidiLearnTarget::Control { name, mode } => {
+                let mode = mode.unwrap_or(match input {
+                    InputMatch::Note { .. } => ControlMode::Toggle,
+                    _ => ControlMode::Fader,
+                });
+                let feedback = match mode {
+                    ControlMode::Toggle | ControlMode::Momentary => Some(Feedback {
+                        on: default_feedback_on(),
+                        off: 0,
+                    }),
+                    ControlMode::Fader | ControlMode::Encoder => None,
+                };
+                (name, mode, feedback)
+            }
+            MidiLearnTarget::Matrix { x, y } => {
+                let cell = self.controls.iter().find(|(_, control)| {
+                    control.input == InputMatch::Matrix { x, y }
+                });
+
+                match cell {
+                    Some((_, control)) => (control.name.clone(), control.mode, control.feedback),
+                    None => {
+                        return MidiLearnStatus::Failed {
+                            message: format!("No control is mapped to the matrix cell ({x}, {y})"),
+                        }
+                    }
+                }
+            }
+        };
+
+        let control = ControlMapping {
+            name: name.clone(),
+            device: self.config.device_name(device).map(str::to_string),
+            input,
+            mode,
+            curve: FaderCurve::default(),
+            range: default_range(),
+            encoding: EncoderEncoding::default(),
+            feedback,
+        };
+
+        if let Err(err) = append_mapping(&request.mapping_path, control.clone()) {
+            log::error!("[MIDI-MAP] {err:#}");
+            return MidiLearnStatus::Failed {
+                message: format!("{err:#}"),
+            };
+        }
+
+        if self.config.mapping.is_none() {
+            self.config.mapping = Some(request.mapping_path);
+        }
+
+        log::info!("[MIDI-MAP] Learned <{name}>: {input:?} on device {device}");
+
+        let status = MidiLearnStatus::Learned {
+            name,
+            device: control.device.clone(),
+            input,
+            mode,
+        };
+        self.add(control);
+        status
+    }
+}
+
+// Comments in the mapping file are not preserved.
+fn append_mapping(path: &Path, control: ControlMapping) -> Result<()> {
+    let mut file = match path.exists() {
+        true => read_mapping(path)?,
+        false => MidiMappingFile::default(),
+    };
+    file.controls.push(control);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, toml::to_string_pretty(&file)?)
+        .with_context(|| format!("Failed to write MIDI mapping {}", path.to_string_lossy()))?;
+    Ok(())
+}
+
+pub fn read_mapping(path: &Path) -> Result<MidiMappingFile> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read MIDI mapping {}", path.to_string_lossy()))?;
+    let file = toml::from_str(&content)
+        .with_context(|| format!("Invalid MIDI mapping {}", path.to_string_lossy()))?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapper(file: &str) -> MidiMapper {
+        let config = MidiConfig {
+            devices: vec!["DDJ-400".to_string()],
+            ..MidiConfig::default()
+        };
+        MidiMapper::new(toml::from_str(file).unwrap(), &config, MidiLearn::new())
+    }
+
+    #[test]
+    fn sysex_is_sent_once() {
+        let mut mapper = mapper(
+            r#"
+            [[sysex]]
+            device = "DDJ-400"
+            data = [0xF0, 0x7D, 0x01, 0xF7]
+
+            [[sysex]]
+            device = "Blaulicht"
+            data = [0xF0, 0xF7]
+            "#,
+        );
+
+        let init: Vec<_> = mapper
+            .take_init()
+            .into_iter()
+            .map(|event| (event.device, event.message))
+            .collect();
+        assert_eq!(
+            init,
+            [
+                (0, MidiMessage::SysEx(vec![0xF0, 0x7D, 0x01, 0xF7])),
+                (1, MidiMessage::SysEx(vec![0xF0, 0xF7])),
+            ]
+        );
+        assert!(mapper.take_init().is_empty());
+    }
+
+    #[test]
+    fn invalid_sysex_is_ignored() {
+        let mut mapper = mapper(
+            r#"
+            [[sysex]]
+            device = "DDJ-400"
+            data = [0x7D, 0x01, 0xF7]
+
+            [[sysex]]
+            device = "DDJ-400"
+            data = [0xF0, 0x80, 0xF7]
+
+            [[sysex]]
+            device = "DDJ-200"
+            data = [0xF0, 0x01, 0xF7]
+            "#,
+        );
+
+        assert!(mapper.take_init().is_empty());
+    }
+
+    fn cc14(control: u8, value: u16) -> MidiEvent {
+        MidiEvent {
+            device: 0,
+            message: MidiMessage::ControlChange14 {
+                channel: 0,
+                control,
+                value,
+            },
+        }
+    }
+
+    fn cc(control: u8, value: u8) -> MidiEvent {
+        MidiEvent {
+            device: 0,
+            message: MidiMessage::ControlChange {
+                channel: 0,
+                control,
+                value,
+            },
+        }
+    }
+
+    fn values(mapped: MappedEvents) -> Vec<ControlValue> {
+        mapped
+            .controls
+            .into_iter()
+            .map(|control| control.value)
+            .collect()
+    }
+
+    #[test]
+    fn curves() {
+        assert_eq!(FaderCurve::Linear.apply(0.25), 0.25);
+        assert_eq!(FaderCurve::Exponential.apply(0.5), 0.25);
+        assert_eq!(FaderCurve::Logarithmic.apply(0.25), 0.5);
+        for curve in [FaderCurve::Exponential, FaderCurve::Logarithmic] {
+            assert_eq!(curve.apply(0.0), 0.0);
+            assert_eq!(curve.apply(1.0), 1.0);
+        }
+    }
+
+    #[test]
+    fn encoder_decoding_7_bit() {
+        use EncoderEncoding::*;
+
+        assert_eq!(TwosComplement.decode(1, 127), 1);
+        assert_eq!(TwosComplement.decode(127, 127), -1);
+        assert_eq!(TwosComplement.decode(64, 127), -64);
+        assert_eq!(Offset.decode(65, 127), 1);
+        assert_eq!(Offset.decode(60, 127), -4);
+        assert_eq!(SignMagnitude.decode(3, 127), 3);
+        assert_eq!(SignMagnitude.decode(0x43, 127), -3);
+    }
+
+    #[test]
+    fn encoder_decoding_14_bit() {
+        use EncoderEncoding::*;
+
+        assert_eq!(TwosComplement.decode(0x0081, 0x3FFF), 129);
+        assert_eq!(TwosComplement.decode(0x3FFF, 0x3FFF), -1);
+        assert_eq!(Offset.decode(0x2000 + 200, 0x3FFF), 200);
+        assert_eq!(Offset.decode(0x2000 - 3, 0x3FFF), -3);
+        assert_eq!(SignMagnitude.decode(0x2005, 0x3FFF), -5);
+        assert_eq!(SignMagnitude.decode(0x0105, 0x3FFF), 0x105);
+    }
+
+    #[test]
+    fn faders_are_scaled_into_the_range() {
+        let mut mapper = mapper(
+            r#"
+            [[control]]
+            name = "fader"
+            input = { type = "cc14", channel = 0, control = 19 }
+            mode = "fader"
+            curve = "exponential"
+            range = [10.0, 20.0]
+            "#,
+        );
+
+        // The plain controllers of the pair are swallowed.
+        let mapped = mapper.map(vec![cc(19, 64), cc(51, 0), cc14(19, 0x3FFF)]);
+        assert!(mapped.unmapped.is_empty());
+        assert_eq!(values(mapped), [ControlValue::Float(20.0)]);
+
+        let mapped = mapper.map(vec![cc14(19, 0)]);
+        assert_eq!(values(mapped), [ControlValue::Float(10.0)]);
+    }
+
+    #[test]
+    fn encoders_use_the_full_14_bit_value() {
+        let mut mapper = mapper(
+            r#"
+            [[control]]
+            name = "encoder"
+            input = { type = "cc14", channel = 0, control = 20 }
+            mode = "encoder"
+            encoding = "offset"
+            "#,
+        );
+
+        // Truncated to 7 bits, this was 0x2100 & 0x7F = 0, i.e. -64.
+        let mapped = mapper.map(vec![cc14(20, 0x2100)]);
+        assert_eq!(values(mapped), [ControlValue::Relative(256)]);
+    }
+
+    #[test]
+    fn toggles_send_feedback_to_all_inputs_of_the_control() {
+        let mut mapper = mapper(
+            r#"
+            [[control]]
+            name = "toggle"
+            device = "DDJ-400"
+            input = { type = "note", channel = 0, note = 1 }
+            mode = "toggle"
+            feedback = { on = 100 }
+
+            [[control]]
+            name = "toggle"
+            input = { type = "matrix", x = 2, y = 3 }
+            mode = "toggle"
+            feedback = {}
+            "#,
+        );
+
+        let note = |velocity| MidiEvent {
+            device: 0,
+            message: MidiMessage::NoteOn {
+                channel: 0,
+                note: 1,
+                velocity,
+            },
+        };
+
+        let mapped = mapper.map(vec![note(127), note(0)]);
+        assert_eq!(mapped.feedback.len(), 1);
+        assert_eq!(
+            mapped.feedback[0].message,
+            MidiMessage::NoteOn {
+                channel: 0,
+                note: 1,
+                velocity: 100
+            }
+        );
+        assert_eq!(mapped.system.len(), 1);
+        assert_eq!(values(mapped), [ControlValue::Bool(true)]);
+
+        let mapped = mapper.map(vec![note(127)]);
+        assert_eq!(
+            mapped.feedback[0].message,
+            MidiMessage::NoteOn {
+                channel: 0,
+                note: 1,
+                velocity: 0
+            }
+        );
+        assert_eq!(values(mapped), [ControlValue::Bool(false)]);
+    }
+
+    // The default config points at it.
+    #[test]
+    fn shipped_mapping_loads() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("mappings/ddj-400.toml");
+
+        let file = read_mapping(&path).unwrap();
+        assert!(!file.controls.is_empty());
+    }
+}
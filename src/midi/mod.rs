@@ -0,0 +1,248 @@
+use crossbeam_channel::{Receiver, Sender};
+use midir::{Ignore, MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use crate::app::MidiEvent;
+use crate::config::MidiConfig;
+use crate::metrics::METRICS;
+
+mod clock;
+mod decoder;
+pub mod learn;
+pub mod mapping;
+pub use clock::{MidiClock, MidiClockOutput};
+pub use decoder::MidiDecoder;
+pub use learn::MidiLearn;
+pub use mapping::MidiMapper;
+
+/// The MIDI channels and state shared by the supervisor and the audio thread.
+#[derive(Clone)]
+pub struct MidiHandles {
+    /// Events from the controllers.
+    pub input: Receiver<MidiEvent>,
+    /// Events to the controllers.
+    pub output: Sender<MidiEvent>,
+    /// Clock and transport messages, sent on their own channel.
+    pub clock_output: Sender<MidiEvent>,
+    pub clock: MidiClock,
+    pub learn: MidiLearn,
+}
+
+#[derive(Debug)]
+pub enum MidiError {
+    DeviceNotFound,
+    Decode(String),
+    Other(String),
+}
+
+pub fn midi(
+    signal_from_controller_sender: Sender<MidiEvent>,
+    signal_to_controller_receiver: Receiver<MidiEvent>,
+    clock_to_controller_receiver: Receiver<MidiEvent>,
+    config: MidiConfig,
+    clock: MidiClock,
+) -> Result<(), MidiError> {
+    log::trace!("[MIDI] Started thread");
+
+    let connections = &config.devices;
+
+    if let Some(name) = &config.clock_in {
+        if config.device_id(name).is_none() {
+            log::warn!("[MIDI] Clock input device <{name}> is not in the device list, ignoring");
+        }
+    }
+
+    let mut conns_out = HashMap::new();
+    let mut conns_in = HashMap::new();
+
+    for (device_id, conn) in connections.iter().enumerate() {
+        let clock = (config.clock_in.as_ref() == Some(conn)).then(|| clock.clone());
+        let callback =
+            input_callback(device_id as u8, signal_from_controller_sender.clone(), clock);
+
+        // A missing controller must not take down the virtual port and the other devices.
+        match connect_device(conn, callback) {
+            Ok((conn_in, conn_out)) => {
+                conns_out.insert(device_id, conn_out);
+                conns_in.insert(device_id, conn_in);
+            }
+            Err(err) => {
+                log::warn!("[MIDI] Could not connect to <{conn}>: {err:?}");
+            }
+        }
+    }
+
+    //
+    // Virtual ports, their device id comes right after the hardware devices.
+    //
+
+    if let Some(name) = &config.virtual_port {
+        let device_id = connections.len();
+        let clock = (config.clock_in.as_ref() == Some(name)).then(|| clock.clone());
+        let callback =
+            input_callback(device_id as u8, signal_from_controller_sender.clone(), clock);
+
+        match create_virtual_ports(name, callback) {
+            Ok((conn_in, conn_out)) => {
+                log::info!("[MIDI] Created virtual ports <{name}> as device {device_id}");
+                conns_out.insert(device_id, conn_out);
+                conns_in.insert(device_id, conn_in);
+            }
+            Err(err) => {
+                log::warn!("[MIDI] Could not create virtual ports <{name}>: {err:?}");
+            }
+        }
+    }
+
+    // Block on the receivers, sleeping in between would add jitter to the outgoing MIDI clock.
+    loop {
+        // Clock pulses first, a burst of feedback must not delay them.
+        let sig = match clock_to_controller_receiver.try_recv() {
+            Ok(sig) => Ok(sig),
+            Err(_) => crossbeam_channel::select! {
+                recv(clock_to_controller_receiver) -> sig => sig,
+                recv(signal_to_controller_receiver) -> sig => sig,
+            },
+        };
+        let Ok(sig) = sig else {
+            break;
+        };
+
+        let c = conns_out.get_mut(&(sig.device as usize));
+
+        match c {
+            Some(c) => {
+                for message in sig.message.to_messages() {
+                    match c.send(&message) {
+                        Ok(()) => {
+                            METRICS.midi_events_out.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Err(err) => {
+                            log::error!("[MIDI-OUT] Failed to send {message:02X?}: {err}");
+                        }
+                    }
+                }
+            }
+            None => {
+                log::error!("MIDI output not found: {}", sig.device)
+            },
+        };
+    }
+
+    log::warn!("[MIDI] Terminating...");
+
+    Ok(())
+}
+
+fn input_callback(
+    device_id: u8,
+    send: Sender<MidiEvent>,
+    clock: Option<MidiClock>,
+) -> impl FnMut(u64, &[u8], &mut ()) + Send + 'static {
+    let mut decoder = MidiDecoder::new(device_id);
+
+    move |_, message, _| {
+        if let Some(clock) = &clock {
+            clock.handle(message, Instant::now());
+        }
+
+        // Clock pulses are too frequent to be logged.
+        if message != [0xF8] {
+            log::trace!("[MIDI-IN] received: {message:02X?}");
+        }
+
+        let events = match decoder.decode(message) {
+            Ok(events) => events,
+            Err(err) => {
+                log::warn!("[MIDI-IN] Ignoring message: {err:?}");
+                return;
+            }
+        };
+
+        for event in events {
+            METRICS.midi_events_in.fetch_add(1, Ordering::Relaxed);
+            if let Err(err) = send.send(event) {
+                log::warn!("[MIDI-IN] Dropping event: {err}");
+            }
+        }
+    }
+}
+
+fn connect_device(
+    conn: &str,
+    callback: impl FnMut(u64, &[u8], &mut ()) + Send + 'static,
+) -> Result<(MidiInputConnection<()>, MidiOutputConnection), MidiError> {
+    let mut midi_in =
+        MidiInput::new("ddj-listener").map_err(|e| MidiError::Other(e.to_string()))?;
+    midi_in.ignore(Ignore::None);
+    let in_ports = midi_in.ports();
+
+    let in_port = in_ports
+        .iter()
+        .find(|p| midi_in.port_name(p).unwrap().contains(conn))
+        .ok_or(MidiError::DeviceNotFound)?;
+
+    log::debug!(
+        "[MIDI-IN] Connecting to: {}",
+        midi_in
+            .port_name(in_port)
+            .map_err(|e| MidiError::Other(e.to_string()))?
+    );
+
+    let conn_in = midi_in
+        .connect(in_port, "ddj-read", callback, ())
+        .map_err(|e| MidiError::Other(e.to_string()))?;
+
+    let midi_out = MidiOutput::new("ddj-sender").map_err(|e| MidiError::Other(e.to_string()))?;
+    let out_ports = midi_out.ports();
+    let out_port = out_ports
+        .iter()
+        .find(|p| midi_out.port_name(p).unwrap().contains(conn))
+        .ok_or(MidiError::DeviceNotFound)?;
+
+    log::trace!(
+        "[MIDI-OUT] Connecting to: {}",
+        midi_out
+            .port_name(out_port)
+            .map_err(|e| MidiError::Other(e.to_string()))?
+    );
+
+    let conn_out = midi_out
+        .connect(out_port, "ddj-send")
+        .map_err(|e| MidiError::Other(e.to_string()))?;
+
+    Ok((conn_in, conn_out))
+}
+
+#[cfg(unix)]
+fn create_virtual_ports(
+    name: &str,
+    callback: impl FnMut(u64, &[u8], &mut ()) + Send + 'static,
+) -> Result<(MidiInputConnection<()>, MidiOutputConnection), MidiError> {
+    use midir::os::unix::{VirtualInput, VirtualOutput};
+
+    let mut midi_in = MidiInput::new(name).map_err(|e| MidiError::Other(e.to_string()))?;
+    midi_in.ignore(Ignore::None);
+    let conn_in = midi_in
+        .create_virtual(&format!("{name} In"), callback, ())
+        .map_err(|e| MidiError::Other(e.to_string()))?;
+
+    let midi_out = MidiOutput::new(name).map_err(|e| MidiError::Other(e.to_string()))?;
+    let conn_out = midi_out
+        .create_virtual(&format!("{name} Out"))
+        .map_err(|e| MidiError::Other(e.to_string()))?;
+
+    Ok((conn_in, conn_out))
+}
+
+#[cfg(not(unix))]
+fn create_virtual_ports(
+    _name: &str,
+    _callback: impl FnMut(u64, &[u8], &mut ()) + Send + 'static,
+) -> Result<(MidiInputConnection<()>, MidiOutputConnection), MidiError> {
+    Err(MidiError::Other(
+        "virtual ports are not supported on this platform".to_string(),
+    ))
+}
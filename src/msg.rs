@@ -1,9 +1,15 @@
 use std::time::Duration;
 
 use cpal::{Device, HostId};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Serialize, Debug)]
+use crate::{
+    eventlog::{LogEntry, LogLevel, LogSource},
+    midi::learn::MidiLearnStatus,
+    tempo::TempoStatus,
+};
+
+#[derive(Clone, Serialize, Debug, Default)]
 pub struct BpmInfo {
     pub bpm: u8,
     pub time_between_beats_millis: u16,
@@ -19,21 +25,21 @@ pub enum Signal {
     Volume(u8),
 }
 
-#[derive(Clone, Serialize)]
+#[derive(Clone, Serialize, Debug)]
 pub struct WasmControlsLog {
     pub x: u8,
     pub y: u8,
     pub value: String,
 }
 
-#[derive(Clone, Serialize)]
+#[derive(Clone, Serialize, Debug)]
 pub struct WasmControlsSet {
     pub x: u8,
     pub y: u8,
     pub value: bool,
 }
 
-#[derive(Clone, Serialize)]
+#[derive(Clone, Serialize, Debug)]
 pub struct WasmControlsConfig {
     pub x: u8,
     pub y: u8,
@@ -43,24 +49,76 @@ pub struct WasmControlsConfig {
 pub enum SystemMessage {
     // System.
     Heartbeat(usize),
-    Log(String),
-    WasmLog(String),
+    Log(LogEntry),
     // Controls.
     WasmControlsLog(WasmControlsLog),
     WasmControlsSet(WasmControlsSet),
     WasmControlsConfig(WasmControlsConfig),
+    MidiLearn(MidiLearnStatus),
     // Performance.
     LoopSpeed(Duration),
     TickSpeed(Duration),
     // Audio.
     AudioSelected(Option<Device>),
     AudioDevicesView(Vec<(HostId, Device)>),
+    /// Tempo after tap tempo and overrides, sent when it changes.
+    Tempo(TempoStatus),
     // DMX.
     DMX(Box<[u8; 513]>),
 }
 
+impl SystemMessage {
+    /// Log entry of the host.
+    pub fn log(level: LogLevel, message: impl Into<String>) -> Self {
+        Self::Log(LogEntry::new(LogSource::Host, level, message))
+    }
+}
+
 #[derive(Clone)]
 pub enum UnifiedMessage {
     Signal(Signal),
     System(SystemMessage),
-}
\ No newline at end of file
+}
+
+/// Groups of messages consumers subscribe to.
+#[derive(Serialize, Deserialize, Hash, Eq, PartialEq, Clone, Copy, Debug)]
+pub enum Topic {
+    Signals,
+    Logs,
+    Controls,
+    Dmx,
+    Stats,
+}
+
+impl UnifiedMessage {
+    /// Topic and kind of the message, rate limits apply per kind.
+    pub fn topic(&self) -> (Topic, &'static str) {
+        match self {
+            Self::Signal(signal) => (
+                Topic::Signals,
+                match signal {
+                    Signal::Bpm(_) => "Bpm",
+                    Signal::BeatVolume(_) => "BeatVolume",
+                    Signal::Bass(_) => "Bass",
+                    Signal::BassAvgShort(_) => "BassAvgShort",
+                    Signal::BassAvg(_) => "BassAvg",
+                    Signal::Volume(_) => "Volume",
+                },
+            ),
+            Self::System(system) => match system {
+                SystemMessage::Heartbeat(_) => (Topic::Stats, "Heartbeat"),
+                SystemMessage::Log(_) => (Topic::Logs, "Log"),
+                SystemMessage::WasmControlsLog(_) => (Topic::Controls, "WasmControlsLog"),
+                SystemMessage::WasmControlsSet(_) => (Topic::Controls, "WasmControlsSet"),
+                SystemMessage::WasmControlsConfig(_) => (Topic::Controls, "WasmControlsConfig"),
+                SystemMessage::MidiLearn(_) => (Topic::Controls, "MidiLearn"),
+                SystemMessage::LoopSpeed(_) => (Topic::Stats, "LoopSpeed"),
+                SystemMessage::TickSpeed(_) => (Topic::Stats, "TickSpeed"),
+                SystemMessage::AudioSelected(_) => (Topic::Stats, "AudioSelected"),
+                SystemMessage::AudioDevicesView(_) => (Topic::Stats, "AudioDevicesView"),
+                SystemMessage::Tempo(_) => (Topic::Stats, "Tempo"),
+                SystemMessage::DMX(_) => (Topic::Dmx, "Dmx"),
+            },
+        }
+    }
+}
@@ -0,0 +1,390 @@
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+use anyhow::{Context, Result};
+use crossbeam_channel::Sender;
+use rosc::{decoder, encoder, OscMessage, OscPacket, OscType};
+
+use crate::{
+    app::{ControlEvent, ControlValue, FromFrontend, MatrixEvent},
+    broadcast::Consumer,
+    config::OscConfig,
+    midi::mapping::BUILTIN_DEVICE,
+    msg::{Signal, UnifiedMessage},
+    utils::device_from_name,
+};
+
+//
+// OSC server, maps incoming messages onto the same actions as the dashboard.
+// Messages are not authenticated, the server should only listen on localhost or a trusted network.
+//
+// <prefix>/reload
+// <prefix>/audio/device   [s]        no argument deselects the device
+// <prefix>/serial/device  [s]
+// <prefix>/matrix         i i [T|F]  x, y and the button state (pressed if omitted)
+// <prefix>/control/<name> f|i|T|F    floats are faders, ints and bools are buttons
+// <prefix>/encoder/<name> i          encoder steps, e.g. /encoder/tempo.nudge
+//
+
+pub fn server(config: OscConfig, from_frontend: Sender<FromFrontend>) -> Result<()> {
+    let Some(listen) = &config.listen else {
+        return Ok(());
+    };
+
+    let socket =
+        UdpSocket::bind(listen).with_context(|| format!("Failed to bind OSC socket <{listen}>"))?;
+    log::info!("[OSC] Listening on <{listen}>");
+    if socket.local_addr().is_ok_and(|addr| !addr.ip().is_loopback()) {
+        log::warn!("[OSC] <{listen}> is reachable from the network, OSC is not authenticated");
+    }
+
+    serve(&socket, &config.prefix, &from_frontend);
+    Ok(())
+}
+
+// Handles messages until the receiving side is gone.
+fn serve(socket: &UdpSocket, prefix: &str, from_frontend: &Sender<FromFrontend>) {
+    let mut buf = [0; decoder::MTU];
+
+    loop {
+        let (len, addr) = match socket.recv_from(&mut buf) {
+            Ok(res) => res,
+            Err(err) => {
+                log::warn!("[OSC] Failed to receive: {err}");
+                continue;
+            }
+        };
+
+        let packet = match decoder::decode_udp(&buf[..len]) {
+            Ok((_, packet)) => packet,
+            Err(err) => {
+                log::warn!("[OSC] Ignoring invalid packet from {addr}: {err:?}");
+                continue;
+            }
+        };
+
+        for message in flatten(packet) {
+            log::trace!("[OSC] received: {message:?}");
+
+            let Some(action) = map_message(prefix, message) else {
+                continue;
+            };
+
+            if from_frontend.send(action).is_err() {
+                log::warn!("[OSC] Shutting down.");
+                return;
+            }
+        }
+    }
+}
+
+// Bundles are applied right away, time tags are ignored.
+fn flatten(packet: OscPacket) -> Vec<OscMessage> {
+    match packet {
+        OscPacket::Message(message) => vec![message],
+        OscPacket::Bundle(bundle) => bundle.content.into_iter().flat_map(flatten).collect(),
+    }
+}
+
+fn map_message(prefix: &str, message: OscMessage) -> Option<FromFrontend> {
+    let Some(path) = message.addr.strip_prefix(prefix) else {
+        log::debug!("[OSC] Ignoring address outside of <{prefix}>: {}", message.addr);
+        return None;
+    };
+
+    let action = match (path, message.args.as_slice()) {
+        ("/reload", _) => FromFrontend::Reload,
+        ("/audio/device", []) => FromFrontend::SelectInputDevice(None),
+        ("/audio/device", [OscType::String(name)]) => match device_from_name(name.clone()) {
+            Some(device) => FromFrontend::SelectInputDevice(Some(device)),
+            None => {
+                log::warn!("[OSC] No such audio device: <{name}>");
+                return None;
+            }
+        },
+        ("/serial/device", []) => FromFrontend::SelectSerialDevice(None),
+        ("/serial/device", [OscType::String(name)]) => {
+            FromFrontend::SelectSerialDevice(Some(name.clone()))
+        }
+        ("/matrix", [x, y, rest @ ..]) => {
+            let value = match rest {
+                [] => true,
+                [value] => as_bool(value)?,
+                _ => return invalid(&message),
+            };
+
+            FromFrontend::MatrixControl(MatrixEvent {
+                device: BUILTIN_DEVICE,
+                x: as_int(x)?.try_into().ok()?,
+                y: as_int(y)?.try_into().ok()?,
+                value,
+            })
+        }
+        (path, [value]) if path.starts_with("/control/") => {
+            let value = match value {
+                OscType::Float(value) => ControlValue::Float(*value),
+                OscType::Double(value) => ControlValue::Float(*value as f32),
+                value => ControlValue::Bool(as_bool(value)?),
+            };
+
+            FromFrontend::Control(ControlEvent {
+                name: path.trim_start_matches("/control/").to_string(),
+                value,
+            })
+        }
+        (path, [value]) if path.starts_with("/encoder/") => FromFrontend::Control(ControlEvent {
+            name: path.trim_start_matches("/encoder/").to_string(),
+            value: ControlValue::Relative(as_int(value)?.try_into().ok()?),
+        }),
+        _ => return invalid(&message),
+    };
+
+    Some(action)
+}
+
+fn invalid(message: &OscMessage) -> Option<FromFrontend> {
+    log::warn!("[OSC] Unknown message: {} {:?}", message.addr, message.args);
+    None
+}
+
+fn as_int(value: &OscType) -> Option<i64> {
+    match value {
+        OscType::Int(value) => Some(*value as i64),
+        OscType::Long(value) => Some(*value),
+        OscType::Float(value) => Some(*value as i64),
+        _ => None,
+    }
+}
+
+fn as_bool(value: &OscType) -> Option<bool> {
+    match value {
+        OscType::Bool(value) => Some(*value),
+        value => as_int(value).map(|value| value != 0),
+    }
+}
+
+//
+// Publishes the audio signals to the configured targets.
+// Levels are sent as floats from 0 to 1, the BPM as int.
+//
+
+pub fn publisher(config: OscConfig, signals: Consumer) -> Result<()> {
+    let targets: Vec<SocketAddr> = config
+        .targets
+        .iter()
+        .filter_map(|target| match target.to_socket_addrs() {
+            Ok(mut addrs) => addrs.next(),
+            Err(err) => {
+                log::warn!("[OSC] Invalid target <{target}>: {err}");
+                None
+            }
+        })
+        .collect();
+
+    if targets.is_empty() {
+        return Ok(());
+    }
+
+    let socket = UdpSocket::bind("0.0.0.0:0").with_context(|| "Failed to bind OSC socket")?;
+    log::info!("[OSC] Publishing signals to {targets:?}");
+
+    while let Some(message) = signals.recv_blocking() {
+        let UnifiedMessage::Signal(signal) = message else {
+            continue;
+        };
+
+        let (path, arg) = match signal {
+            Signal::Bpm(info) => ("/bpm", OscType::Int(info.bpm as i32)),
+            Signal::BeatVolume(value) => ("/beat_volume", level(value)),
+            Signal::Bass(value) => ("/bass", level(value)),
+            Signal::BassAvgShort(value) => ("/bass_avg_short", level(value)),
+            Signal::BassAvg(value) => ("/bass_avg", level(value)),
+            Signal::Volume(value) => ("/volume", level(value)),
+        };
+
+        let packet = OscPacket::Message(OscMessage {
+            addr: format!("{}{path}", config.prefix),
+            args: vec![arg],
+        });
+
+        let buf = match encoder::encode(&packet) {
+            Ok(buf) => buf,
+            Err(err) => {
+                log::error!("[OSC] Failed to encode {packet:?}: {err:?}");
+                continue;
+            }
+        };
+
+        for target in &targets {
+            // Nobody listening on the other side is not worth a log line per signal.
+            if let Err(err) = socket.send_to(&buf, target) {
+                log::trace!("[OSC] Failed to send to {target}: {err}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn level(value: u8) -> OscType {
+    OscType::Float(value as f32 / u8::MAX as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::*;
+
+    const PREFIX: &str = "/blaulicht";
+
+    fn map(addr: &str, args: Vec<OscType>) -> Option<FromFrontend> {
+        map_message(
+            PREFIX,
+            OscMessage {
+                addr: addr.to_string(),
+                args,
+            },
+        )
+    }
+
+    fn control(addr: &str, args: Vec<OscType>) -> Option<ControlEvent> {
+        match map(addr, args)? {
+            FromFrontend::Control(control) => Some(control),
+            _ => panic!("not a control"),
+        }
+    }
+
+    fn matrix(args: Vec<OscType>) -> Option<(u8, u8, u8, bool)> {
+        match map("/blaulicht/matrix", args)? {
+            FromFrontend::MatrixControl(event) => {
+                Some((event.device, event.x, event.y, event.value))
+            }
+            _ => panic!("not a matrix event"),
+        }
+    }
+
+    #[test]
+    fn ignores_other_prefixes() {
+        assert!(map("/other/reload", vec![]).is_none());
+        assert!(map("/blaulichter/reload", vec![]).is_none());
+        assert!(map("/reload", vec![]).is_none());
+    }
+
+    #[test]
+    fn reload() {
+        assert!(matches!(
+            map("/blaulicht/reload", vec![]),
+            Some(FromFrontend::Reload)
+        ));
+    }
+
+    #[test]
+    fn matrix_buttons() {
+        use OscType::*;
+
+        assert_eq!(
+            matrix(vec![Int(2), Int(3)]),
+            Some((BUILTIN_DEVICE, 2, 3, true))
+        );
+        assert_eq!(
+            matrix(vec![Int(2), Int(3), Bool(false)]),
+            Some((BUILTIN_DEVICE, 2, 3, false))
+        );
+        assert_eq!(
+            matrix(vec![Float(1.0), Long(4), Int(1)]),
+            Some((BUILTIN_DEVICE, 1, 4, true))
+        );
+
+        // Out of range and missing coordinates.
+        assert_eq!(matrix(vec![Int(256), Int(0)]), None);
+        assert_eq!(matrix(vec![Int(-1), Int(0)]), None);
+        assert_eq!(matrix(vec![Int(0)]), None);
+    }
+
+    #[test]
+    fn controls() {
+        use OscType::*;
+
+        let event = |name: &str, value| {
+            Some(ControlEvent {
+                name: name.to_string(),
+                value,
+            })
+        };
+
+        assert_eq!(
+            control("/blaulicht/control/strobe.toggle", vec![Bool(true)]),
+            event("strobe.toggle", ControlValue::Bool(true))
+        );
+        assert_eq!(
+            control("/blaulicht/control/strobe.toggle", vec![Int(0)]),
+            event("strobe.toggle", ControlValue::Bool(false))
+        );
+        assert_eq!(
+            control("/blaulicht/control/strobe.brightness", vec![Float(0.5)]),
+            event("strobe.brightness", ControlValue::Float(0.5))
+        );
+        assert_eq!(
+            control("/blaulicht/control/strobe.brightness", vec![Double(0.25)]),
+            event("strobe.brightness", ControlValue::Float(0.25))
+        );
+        assert_eq!(
+            control("/blaulicht/encoder/tempo.nudge", vec![Int(-2)]),
+            event("tempo.nudge", ControlValue::Relative(-2))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        use OscType::*;
+
+        let on = || String("on".to_string());
+        let cases = [
+            ("/blaulicht/control/strobe.toggle", vec![on()]),
+            ("/blaulicht/control/strobe.toggle", vec![Nil]),
+            ("/blaulicht/control/strobe.toggle", vec![]),
+            ("/blaulicht/control/strobe.toggle", vec![Int(1), Int(1)]),
+            ("/blaulicht/encoder/tempo.nudge", vec![Bool(true)]),
+            ("/blaulicht/encoder/tempo.nudge", vec![Long(i64::MAX)]),
+            ("/blaulicht/matrix", vec![Int(0), Int(0), on()]),
+            ("/blaulicht/matrix", vec![Int(0), Int(0), Int(1), Int(1)]),
+            ("/blaulicht/serial/device", vec![Int(1)]),
+            ("/blaulicht/unknown", vec![]),
+        ];
+
+        for (addr, args) in cases {
+            assert!(map(addr, args.clone()).is_none(), "{addr} {args:?}");
+        }
+    }
+
+    #[test]
+    fn receives_over_udp() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        // Ends once the test is done and the next packet arrives, or with the test process.
+        thread::spawn(move || serve(&socket, PREFIX, &sender));
+
+        let packet = OscPacket::Message(OscMessage {
+            addr: "/blaulicht/control/strobe.brightness".to_string(),
+            args: vec![OscType::Float(0.75)],
+        });
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .send_to(&encoder::encode(&packet).unwrap(), addr)
+            .unwrap();
+
+        let message = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        let FromFrontend::Control(control) = message else {
+            panic!("not a control");
+        };
+        assert_eq!(
+            control,
+            ControlEvent {
+                name: "strobe.brightness".to_string(),
+                value: ControlValue::Float(0.75),
+            }
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+use crate::profile;
+
+//
+// Fixture patch, passed to the guest as JSON on every (re)load.
+// The file is TOML unless its extension is `.json`.
+// Profiles from `profile_dir` (relative to the patch) are merged into `profiles`.
+// Once the `cue_file` (relative to the patch) exists, its scenes and cue lists replace the ones
+// of the patch. The guest writes all of them to it when a scene is recorded.
+// Fixtures may only use profiles that are known after the merge.
+//
+
+const GROUPS: [&str; 3] = ["strobe_groups", "mood_groups", "dimmer_groups"];
+
+fn is_json(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some("json")
+}
+
+fn read_value(path: &Path, what: &str) -> Result<Value> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {what} <{}>", path.display()))?;
+
+    let value = match is_json(path) {
+        true => serde_json::from_str(&content)?,
+        false => toml::from_str(&content)?,
+    };
+    Ok(value)
+}
+
+fn resolve(patch: &Path, relative: &Value, key: &str) -> Result<PathBuf> {
+    let Some(relative) = relative.as_str() else {
+        bail!("`{key}` must be a path");
+    };
+    Ok(patch.parent().unwrap_or(Path::new(".")).join(relative))
+}
+
+pub fn read_patch(path: &Path) -> Result<Vec<u8>> {
+    let mut patch = read_value(path, "patch")?;
+
+    let Some(object) = patch.as_object_mut() else {
+        bail!("Patch <{}> is not a table", path.display());
+    };
+
+    if let Some(file) = object.remove("cue_file") {
+        let file = resolve(path, &file, "cue_file")?;
+
+        if file.exists() {
+            let mut cues = read_value(&file, "cue file")?;
+            for key in ["scenes", "cue_lists"] {
+                if let Some(value) = cues.get_mut(key) {
+                    object.insert(key.to_string(), value.take());
+                }
+            }
+        }
+    }
+
+    if let Some(dir) = object.remove("profile_dir") {
+        let dir = resolve(path, &dir, "profile_dir")?;
+
+        let profiles = object
+            .entry("profiles")
+            .or_insert_with(|| Value::Object(Default::default()));
+        let Some(profiles) = profiles.as_object_mut() else {
+            bail!("`profiles` must be a table");
+        };
+
+        for (name, profile) in profile::load_profiles(&dir)? {
+            // Profiles written into the patch take precedence.
+            if profiles.contains_key(&name) {
+                log::warn!("[PATCH] Profile <{name}> is defined in the patch, ignoring the file");
+                continue;
+            }
+            profiles.insert(name, serde_json::to_value(profile)?);
+        }
+    }
+
+    check_profiles(&patch)?;
+
+    serde_json::to_vec(&patch).with_context(|| "Failed to serialize patch")
+}
+
+fn check_profiles(patch: &Value) -> Result<()> {
+    let fixtures = GROUPS
+        .iter()
+        .filter_map(|key| patch.get(key)?.as_array())
+        .flatten()
+        .filter_map(|group| group.get("fixtures")?.as_array())
+        .flatten();
+
+    for fixture in fixtures {
+        let Some(profile) = fixture.pointer("/type/Profile/profile") else {
+            continue;
+        };
+        let known = profile
+            .as_str()
+            .is_some_and(|name| patch.get("profiles").and_then(|p| p.get(name)).is_some());
+        if !known {
+            bail!("Unknown profile {profile}");
+        }
+    }
+
+    Ok(())
+}
+
+/// The cue file of a patch, if it has one.
+pub fn cue_file(path: &Path) -> Result<Option<PathBuf>> {
+    let patch = read_value(path, "patch")?;
+    patch
+        .get("cue_file")
+        .map(|file| resolve(path, file, "cue_file"))
+        .transpose()
+}
+
+/// Writes scenes and cue lists, passed as JSON by the guest.
+pub fn write_cues(path: &Path, json: &[u8]) -> Result<()> {
+    let cues: Value = serde_json::from_slice(json).with_context(|| "Invalid cues")?;
+
+    let content = match is_json(path) {
+        true => serde_json::to_string_pretty(&cues)?,
+        false => toml::to_string_pretty(&cues)?,
+    };
+
+    fs::write(path, content)
+        .with_context(|| format!("Failed to write cue file <{}>", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    // Removed again when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("blaulicht-{name}-{}", process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(path.join("profiles")).unwrap();
+            Self(path)
+        }
+
+        fn write(&self, file: &str, content: &str) -> PathBuf {
+            let path = self.0.join(file);
+            fs::write(&path, content).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const SPOT: &str = include_str!("../profiles/led-party-tcl-spot.toml");
+
+    fn patch(profile: &str) -> String {
+        format!(
+            r#"
+            profile_dir = "profiles"
+
+            [[mood_groups]]
+            label = "Mood"
+
+            [[mood_groups.fixtures]]
+            start_channel = 1
+            type = {{ Profile = {{ profile = "{profile}" }} }}
+            "#
+        )
+    }
+
+    // The default config points at it.
+    #[test]
+    fn shipped_patch_loads() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("patch.toml");
+
+        let patch: Value = serde_json::from_slice(&read_patch(&path).unwrap()).unwrap();
+        assert!(patch.get("profile_dir").is_none());
+        assert!(patch.get("cue_file").is_none());
+        assert!(patch["profiles"].get("led-party-tcl-spot").is_some());
+    }
+
+    #[test]
+    fn merges_profiles() {
+        let dir = TempDir::new("patch-merge");
+        dir.write("profiles/spot.toml", SPOT);
+        let path = dir.write("patch.toml", &patch("spot"));
+
+        let patch: Value = serde_json::from_slice(&read_patch(&path).unwrap()).unwrap();
+        assert_eq!(patch["mood_groups"][0]["fixtures"][0]["start_channel"], 1);
+        assert!(patch["profiles"]["spot"]["channels"].is_array());
+    }
+
+    #[test]
+    fn unknown_profiles_are_rejected() {
+        let dir = TempDir::new("patch-unknown");
+        dir.write("profiles/spot.toml", SPOT);
+        let path = dir.write("patch.toml", &patch("wash"));
+
+        let err = read_patch(&path).unwrap_err();
+        assert!(err.to_string().contains("wash"), "{err}");
+    }
+
+    #[test]
+    fn duplicate_profile_names_are_rejected() {
+        let dir = TempDir::new("patch-duplicate");
+        dir.write("profiles/spot.toml", SPOT);
+        dir.write(
+            "profiles/spot.json",
+            r#"{
+                "name": "Spot",
+                "availableChannels": { "Dimmer": { "capability": { "type": "Intensity" } } },
+                "modes": [{ "name": "Basic", "channels": ["Dimmer"] }]
+            }"#,
+        );
+        let path = dir.write("patch.toml", &patch("spot"));
+
+        let err = read_patch(&path).unwrap_err();
+        assert!(err.to_string().contains("more than one file"), "{err}");
+    }
+}
@@ -0,0 +1,437 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+//
+// Data-driven fixture profiles, mirrors `wasm/src/user/config/fixture/profile.rs`.
+// Profiles are either written by hand (TOML) or imported from Open Fixture Library JSON files.
+//
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChannelKind {
+    Intensity,
+    Red,
+    Green,
+    Blue,
+    White,
+    Amber,
+    Uv,
+    Pan,
+    PanFine,
+    Tilt,
+    TiltFine,
+    Strobe,
+    ColorWheel,
+    /// Always set to its default.
+    Generic,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CapabilityKind {
+    /// Shutter open.
+    Open,
+    /// Shutter closed.
+    Closed,
+    /// Strobe, slow to fast across the range.
+    Strobe,
+    /// Color wheel slot or preset.
+    Color {
+        r: u8,
+        g: u8,
+        b: u8,
+    },
+    Other,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Capability {
+    pub start: u8,
+    pub end: u8,
+    pub kind: CapabilityKind,
+    #[serde(default)]
+    pub label: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Channel {
+    pub name: String,
+    pub kind: ChannelKind,
+    #[serde(default)]
+    pub default: u8,
+    /// Full range of pan and tilt channels in degrees.
+    #[serde(default)]
+    pub degrees: Option<f32>,
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Mode {
+    pub name: String,
+    /// Channel names by offset, `None` for unused offsets.
+    pub channels: Vec<Option<String>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Profile {
+    pub name: String,
+    pub channels: Vec<Channel>,
+    /// If empty, all channels in order form the only mode.
+    #[serde(default)]
+    pub modes: Vec<Mode>,
+}
+
+/// Loads all profiles of a directory, keyed by file name without extension.
+/// `*.toml` files are native profiles, `*.json` files are imported from the Open Fixture Library format.
+pub fn load_profiles(dir: &Path) -> Result<HashMap<String, Profile>> {
+    let mut profiles = HashMap::new();
+
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read profile directory <{}>", dir.display()))?;
+
+    for entry in entries {
+        let path = entry?.path();
+        let Some(key) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+
+        let profile = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&fs::read_to_string(&path)?)?,
+            Some("json") => import_ofl(&fs::read_to_string(&path)?)?,
+            _ => continue,
+        };
+
+        log::debug!("[PATCH] Loaded profile <{key}> from {}", path.display());
+        if profiles.insert(key.to_string(), profile).is_some() {
+            bail!("Profile <{key}> is defined by more than one file");
+        }
+    }
+
+    Ok(profiles)
+}
+
+//
+// Open Fixture Library import.
+// See https://github.com/OpenLightingProject/open-fixture-library/blob/master/docs/fixture-format.md
+// Only the channel types this engine drives are mapped, everything else becomes `Generic`.
+//
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OflFixture {
+    name: String,
+    #[serde(default)]
+    available_channels: HashMap<String, OflChannel>,
+    #[serde(default)]
+    modes: Vec<OflMode>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OflChannel {
+    #[serde(default)]
+    fine_channel_aliases: Vec<String>,
+    default_value: Option<Value>,
+    capability: Option<OflCapability>,
+    #[serde(default)]
+    capabilities: Vec<OflCapability>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OflCapability {
+    dmx_range: Option<[u16; 2]>,
+    #[serde(rename = "type")]
+    type_: String,
+    color: Option<String>,
+    shutter_effect: Option<String>,
+    #[serde(default)]
+    colors: Vec<String>,
+    angle_start: Option<String>,
+    angle_end: Option<String>,
+    comment: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OflMode {
+    name: String,
+    channels: Vec<Option<Value>>,
+}
+
+pub fn import_ofl(json: &str) -> Result<Profile> {
+    let fixture: OflFixture = serde_json::from_str(json).with_context(|| "Invalid OFL fixture")?;
+
+    if fixture.modes.is_empty() {
+        bail!("OFL fixture <{}> has no modes", fixture.name);
+    }
+
+    let mut channels = vec![];
+    for (name, channel) in &fixture.available_channels {
+        let capabilities: Vec<&OflCapability> = channel
+            .capability
+            .iter()
+            .chain(channel.capabilities.iter())
+            .collect();
+
+        let kind = capabilities
+            .first()
+            .map(|capability| channel_kind(capability))
+            .unwrap_or(ChannelKind::Generic);
+
+        channels.push(Channel {
+            name: name.clone(),
+            kind,
+            default: channel
+                .default_value
+                .as_ref()
+                .map(dmx_value)
+                .unwrap_or_default(),
+            degrees: match kind {
+                ChannelKind::Pan | ChannelKind::Tilt => {
+                    capabilities.first().and_then(|c| degrees(c))
+                }
+                _ => None,
+            },
+            capabilities: capabilities
+                .iter()
+                .filter_map(|capability| ofl_capability(capability))
+                .collect(),
+        });
+
+        // Fine channels only ever carry the low byte.
+        let fine_kind = match kind {
+            ChannelKind::Pan => ChannelKind::PanFine,
+            ChannelKind::Tilt => ChannelKind::TiltFine,
+            _ => ChannelKind::Generic,
+        };
+        for alias in &channel.fine_channel_aliases {
+            channels.push(Channel {
+                name: alias.clone(),
+                kind: fine_kind,
+                default: 0,
+                degrees: None,
+                capabilities: vec![],
+            });
+        }
+    }
+
+    let modes = fixture
+        .modes
+        .into_iter()
+        .map(|mode| Mode {
+            name: mode.name,
+            channels: mode
+                .channels
+                .into_iter()
+                .map(|channel| match channel {
+                    Some(Value::String(name)) => Some(name),
+                    // Matrix channel insert blocks are not supported.
+                    Some(other) => {
+                        log::warn!("[PATCH] Ignoring unsupported OFL mode channel {other}");
+                        None
+                    }
+                    None => None,
+                })
+                .collect(),
+        })
+        .collect();
+
+    // `availableChannels` is a map, keep the output stable.
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(Profile {
+        name: fixture.name,
+        channels,
+        modes,
+    })
+}
+
+fn channel_kind(capability: &OflCapability) -> ChannelKind {
+    match capability.type_.as_str() {
+        "Intensity" => ChannelKind::Intensity,
+        "ColorIntensity" => match capability.color.as_deref() {
+            Some("Red") => ChannelKind::Red,
+            Some("Green") => ChannelKind::Green,
+            Some("Blue") => ChannelKind::Blue,
+            Some("White" | "Warm White" | "Cold White") => ChannelKind::White,
+            Some("Amber") => ChannelKind::Amber,
+            Some("UV") => ChannelKind::Uv,
+            _ => ChannelKind::Generic,
+        },
+        "Pan" => ChannelKind::Pan,
+        "Tilt" => ChannelKind::Tilt,
+        "ShutterStrobe" | "StrobeSpeed" => ChannelKind::Strobe,
+        "ColorPreset" => ChannelKind::ColorWheel,
+        // Gobo wheels use slots as well.
+        "WheelSlot" if !capability.colors.is_empty() => ChannelKind::ColorWheel,
+        _ => ChannelKind::Generic,
+    }
+}
+
+fn ofl_capability(capability: &OflCapability) -> Option<Capability> {
+    let [start, end] = capability.dmx_range.unwrap_or([0, 255]);
+    // 16-bit ranges belong to fine channels, which are driven without capabilities.
+    if end > 255 {
+        return None;
+    }
+
+    let kind = match (
+        capability.type_.as_str(),
+        capability.shutter_effect.as_deref(),
+    ) {
+        ("ShutterStrobe", Some("Open")) => CapabilityKind::Open,
+        ("ShutterStrobe", Some("Closed")) => CapabilityKind::Closed,
+        ("ShutterStrobe", Some(_)) | ("StrobeSpeed", _) => CapabilityKind::Strobe,
+        ("WheelSlot" | "ColorPreset", _) => match capability.colors.first().and_then(|c| hex(c)) {
+            Some((r, g, b)) => CapabilityKind::Color { r, g, b },
+            None => CapabilityKind::Other,
+        },
+        _ => CapabilityKind::Other,
+    };
+
+    Some(Capability {
+        start: start as u8,
+        end: end as u8,
+        kind,
+        label: capability.comment.clone().unwrap_or_default(),
+    })
+}
+
+// Pan and tilt angles look like "540deg", ranges might start below zero.
+fn degrees(capability: &OflCapability) -> Option<f32> {
+    let angle = |angle: &Option<String>| -> Option<f32> {
+        angle.as_deref()?.strip_suffix("deg")?.parse().ok()
+    };
+
+    Some(angle(&capability.angle_end)? - angle(&capability.angle_start)?)
+}
+
+// OFL default values are either DMX values or percentages like "50%".
+fn dmx_value(value: &Value) -> u8 {
+    match value {
+        Value::Number(number) => number.as_u64().unwrap_or_default().min(255) as u8,
+        Value::String(percent) => percent
+            .trim_end_matches('%')
+            .parse::<f32>()
+            .map(|percent| (percent.clamp(0.0, 100.0) * 2.55).round() as u8)
+            .unwrap_or_default(),
+        _ => 0,
+    }
+}
+
+fn hex(color: &str) -> Option<(u8, u8, u8)> {
+    let color = color.strip_prefix('#')?;
+    // Slicing by bytes below needs ASCII.
+    if color.len() != 6 || !color.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |index: usize| u8::from_str_radix(&color[index..index + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn fixture(wheel_color: &str) -> String {
+        json!({
+            "name": "Spot",
+            "availableChannels": {
+                "Dimmer": {
+                    "defaultValue": "50%",
+                    "capability": { "type": "Intensity" }
+                },
+                "Pan": {
+                    "fineChannelAliases": ["Pan fine"],
+                    "capability": { "type": "Pan", "angleStart": "0deg", "angleEnd": "540deg" }
+                },
+                "Color": {
+                    "capabilities": [
+                        { "dmxRange": [0, 9], "type": "WheelSlot", "colors": ["#ffffff"] },
+                        { "dmxRange": [10, 19], "type": "WheelSlot", "colors": [wheel_color] }
+                    ]
+                }
+            },
+            "modes": [
+                { "name": "Basic", "channels": ["Dimmer", "Color"] },
+                { "name": "Extended", "channels": ["Dimmer", "Pan", "Pan fine", null, "Color"] }
+            ]
+        })
+        .to_string()
+    }
+
+    fn channel<'p>(profile: &'p Profile, name: &str) -> &'p Channel {
+        profile.channels.iter().find(|c| c.name == name).unwrap()
+    }
+
+    fn wheel(profile: &Profile) -> Vec<CapabilityKind> {
+        let color = channel(profile, "Color");
+        assert_eq!(color.kind, ChannelKind::ColorWheel);
+        color.capabilities.iter().map(|c| c.kind).collect()
+    }
+
+    #[test]
+    fn imports_channels_and_modes() {
+        let profile = import_ofl(&fixture("#ff8000")).unwrap();
+        assert_eq!(profile.name, "Spot");
+
+        let names: Vec<_> = profile.channels.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Color", "Dimmer", "Pan", "Pan fine"]);
+
+        let dimmer = channel(&profile, "Dimmer");
+        assert_eq!((dimmer.kind, dimmer.default), (ChannelKind::Intensity, 128));
+
+        let pan = channel(&profile, "Pan");
+        assert_eq!((pan.kind, pan.degrees), (ChannelKind::Pan, Some(540.0)));
+        assert_eq!(channel(&profile, "Pan fine").kind, ChannelKind::PanFine);
+
+        assert_eq!(
+            wheel(&profile),
+            [
+                CapabilityKind::Color {
+                    r: 255,
+                    g: 255,
+                    b: 255
+                },
+                CapabilityKind::Color {
+                    r: 255,
+                    g: 128,
+                    b: 0
+                },
+            ]
+        );
+
+        assert_eq!(profile.modes.len(), 2);
+        assert_eq!(profile.modes[1].name, "Extended");
+        assert_eq!(profile.modes[1].channels[3], None);
+    }
+
+    #[test]
+    fn fixtures_need_a_mode() {
+        let mut fixture: Value = serde_json::from_str(&fixture("#ff8000")).unwrap();
+        fixture["modes"] = json!([]);
+
+        let err = import_ofl(&fixture.to_string()).unwrap_err();
+        assert_eq!(err.to_string(), "OFL fixture <Spot> has no modes");
+    }
+
+    #[test]
+    fn bad_colors_are_not_slots() {
+        for color in ["ff8000", "#ff80", "#ff800g", "#ff8000ff"] {
+            let profile = import_ofl(&fixture(color)).unwrap();
+            assert_eq!(wheel(&profile)[1], CapabilityKind::Other, "{color}");
+        }
+    }
+
+    #[test]
+    fn non_ascii_colors_do_not_panic() {
+        // Six bytes, the first slice would end inside the `é`.
+        let profile = import_ofl(&fixture("#0é000")).unwrap();
+        assert_eq!(wheel(&profile)[1], CapabilityKind::Other);
+    }
+}
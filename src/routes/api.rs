@@ -1,5 +1,24 @@
+use std::path::PathBuf;
 
+use actix_web::{
+    get, patch, post, put,
+    web::{Data, Json},
+    HttpResponse,
+};
+use anyhow::Context;
+use cpal::{traits::DeviceTrait, Device};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    app::FromFrontend,
+    config::{self, Config},
+    metrics::METRICS,
+    tempo::TempoStatus,
+    utils::{self, device_from_name},
+};
+
+use super::{AppState, FullControl, GenericResponse, Role};
 
 //
 // BPM Functions.
@@ -16,8 +35,332 @@ pub struct BPMObject {
 //     HttpResponse::Ok().json(GenericResponse::success("updated BPM"))
 // }
 
+//
+// Configuration functions.
+// Patches are JSON merge patches (RFC 7386) applied to the current config.
+// Auth tokens are never sent to clients, patches which keep the placeholder keep the token.
+//
+
+const REDACTED_TOKEN: &str = "<redacted>";
+
+fn redact(mut config: Config) -> Config {
+    for token in [&mut config.auth.token, &mut config.auth.read_only_token] {
+        if token.is_some() {
+            *token = Some(REDACTED_TOKEN.to_string());
+        }
+    }
+    config
+}
+
+#[get("/api/config")]
+pub async fn get_config(_: Role, data: Data<AppState>) -> HttpResponse {
+    let config = redact(data.config.lock().unwrap().clone());
+    HttpResponse::Ok().json(GenericResponse::success_with_data("current config", config))
+}
+
+#[patch("/api/config")]
+pub async fn patch_config(_: FullControl, body: Json<Value>, data: Data<AppState>) -> HttpResponse {
+    let mut config = data.config.lock().unwrap();
+
+    let patched = match apply_patch(&config, body.into_inner()) {
+        Ok(patched) => patched,
+        Err(err) => {
+            return HttpResponse::BadRequest()
+                .json(GenericResponse::err("invalid config", &format!("{err:#}")))
+        }
+    };
+
+    let path = PathBuf::from(&data.config_path);
+    if let Err(err) = config::write_config(path, patched.clone()) {
+        return HttpResponse::InternalServerError()
+            .json(GenericResponse::err("failed to save config", &format!("{err:#}")));
+    }
+
+    *config = patched.clone();
+
+    // The threads were started with the old config.
+    HttpResponse::Ok().json(GenericResponse::success_with_data(
+        "updated config, restart to apply",
+        redact(patched),
+    ))
+}
+
+#[post("/api/config/validate")]
+pub async fn validate_config(_: Role, body: Json<Value>, data: Data<AppState>) -> HttpResponse {
+    let config = data.config.lock().unwrap().clone();
+
+    match apply_patch(&config, body.into_inner()) {
+        Ok(patched) => HttpResponse::Ok()
+            .json(GenericResponse::success_with_data("valid config", redact(patched))),
+        Err(err) => HttpResponse::Ok()
+            .json(GenericResponse::err("invalid config", &format!("{err:#}"))),
+    }
+}
+
+fn apply_patch(config: &Config, patch: Value) -> anyhow::Result<Config> {
+    let mut value = serde_json::to_value(config)?;
+    merge_patch(&mut value, patch);
+
+    let mut patched: Config = serde_json::from_value(value)?;
+    let auth = &mut patched.auth;
+    for (token, current) in [
+        (&mut auth.token, &config.auth.token),
+        (&mut auth.read_only_token, &config.auth.read_only_token),
+    ] {
+        if token.as_deref() == Some(REDACTED_TOKEN) {
+            token.clone_from(current);
+        }
+    }
+
+    patched.validate()?;
+    Ok(patched)
+}
+
+fn merge_patch(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    let Value::Object(target) = target else {
+        unreachable!()
+    };
+
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(&key);
+        } else {
+            merge_patch(target.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
+
 //
 // Audio device functions.
 //
 
-// TODO: maybe?
+#[get("/api/audio/devices")]
+pub async fn get_audio_devices(_: Role) -> HttpResponse {
+    let devices: Vec<String> = utils::get_input_devices_flat()
+        .iter()
+        .filter_map(|(_, device)| device.name().ok())
+        .collect();
+
+    HttpResponse::Ok().json(GenericResponse::success_with_data("audio devices", devices))
+}
+
+#[derive(Deserialize)]
+pub struct SelectAudioDevice {
+    pub device: Option<String>,
+}
+
+#[put("/api/audio/device")]
+pub async fn put_audio_device(
+    _: FullControl,
+    body: Json<SelectAudioDevice>,
+    data: Data<AppState>,
+) -> HttpResponse {
+    let device = match &body.device {
+        None => None,
+        Some(name) => match select_audio_device(&data, name) {
+            Ok(Some(device)) => Some(device),
+            Ok(None) => {
+                return HttpResponse::NotFound()
+                    .json(GenericResponse::err("failed to select audio device", "no such device"))
+            }
+            Err(err) => {
+                return HttpResponse::InternalServerError().json(GenericResponse::err(
+                    "failed to select audio device",
+                    &format!("{err:#}"),
+                ))
+            }
+        },
+    };
+
+    send(&data, FromFrontend::SelectInputDevice(device), "selected audio device")
+}
+
+/// Looks up the device and saves it as the default, the config is left untouched if there is no such device.
+pub(super) fn select_audio_device(data: &AppState, name: &str) -> anyhow::Result<Option<Device>> {
+    let Some(device) = device_from_name(name.to_string()) else {
+        return Ok(None);
+    };
+
+    log::info!("[API] Selected INPUT: <{name}>");
+
+    let mut config = data.config.lock().unwrap();
+    config.default_audio_device = Some(name.to_string());
+
+    let path = PathBuf::from(&data.config_path);
+    config::write_config(path, config.clone()).with_context(|| "Failed to save config")?;
+
+    Ok(Some(device))
+}
+
+//
+// Engine functions.
+//
+
+#[post("/api/reload")]
+pub async fn post_reload(_: FullControl, data: Data<AppState>) -> HttpResponse {
+    send(&data, FromFrontend::Reload, "reload triggered")
+}
+
+#[get("/api/dmx")]
+pub async fn get_dmx(_: Role, data: Data<AppState>) -> HttpResponse {
+    let status = data.status.lock().unwrap();
+
+    match &status.dmx {
+        // Channel 0 is the DMX start code.
+        Some(channels) => HttpResponse::Ok().json(GenericResponse::success_with_data(
+            "current DMX frame",
+            &channels[1..],
+        )),
+        None => HttpResponse::ServiceUnavailable()
+            .json(GenericResponse::err("no DMX frame", "the engine is not running")),
+    }
+}
+
+#[derive(Serialize)]
+struct StatusView {
+    heartbeat: Option<usize>,
+    heartbeat_age_millis: Option<u64>,
+    tick_speed_micros: u64,
+    loop_speed_micros: u64,
+    audio_device: Option<String>,
+    tempo: Option<TempoStatus>,
+}
+
+#[get("/api/status")]
+pub async fn get_status(_: Role, data: Data<AppState>) -> HttpResponse {
+    let status = data.status.lock().unwrap();
+
+    let view = StatusView {
+        heartbeat: status.heartbeat.map(|(seq, _)| seq),
+        heartbeat_age_millis: status
+            .heartbeat
+            .map(|(_, time)| time.elapsed().as_millis() as u64),
+        tick_speed_micros: status.tick_speed.as_micros() as u64,
+        loop_speed_micros: status.loop_speed.as_micros() as u64,
+        audio_device: status.audio_device.clone(),
+        tempo: status.tempo,
+    };
+
+    HttpResponse::Ok().json(GenericResponse::success_with_data("system status", view))
+}
+
+/// Prometheus text format.
+#[get("/metrics")]
+pub async fn get_metrics(_: Role) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(METRICS.render())
+}
+
+fn send(data: &AppState, message: FromFrontend, success: &str) -> HttpResponse {
+    match data.from_frontend_sender.send(message) {
+        Ok(()) => HttpResponse::Ok().json(GenericResponse::success(success)),
+        Err(_) => HttpResponse::InternalServerError()
+            .json(GenericResponse::err("engine is not running", "supervisor has shut down")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn with_tokens() -> Config {
+        let mut config = Config::default();
+        config.auth.token = Some("full".to_string());
+        config.auth.read_only_token = Some("read".to_string());
+        config
+    }
+
+    #[test]
+    fn redact_masks_set_tokens_only() {
+        let mut config = with_tokens();
+        config.auth.read_only_token = None;
+
+        let redacted = redact(config);
+        assert_eq!(redacted.auth.token.as_deref(), Some(REDACTED_TOKEN));
+        assert_eq!(redacted.auth.read_only_token, None);
+    }
+
+    #[test]
+    fn patch_keeps_redacted_tokens() {
+        let config = with_tokens();
+        let sent = serde_json::to_value(redact(config.clone())).unwrap();
+
+        let patched = apply_patch(&config, sent).unwrap();
+        assert_eq!(patched.auth.token.as_deref(), Some("full"));
+        assert_eq!(patched.auth.read_only_token.as_deref(), Some("read"));
+    }
+
+    #[test]
+    fn patch_replaces_tokens() {
+        let patch = json!({ "auth": { "token": "new", "read_only_token": null } });
+
+        let patched = apply_patch(&with_tokens(), patch).unwrap();
+        assert_eq!(patched.auth.token.as_deref(), Some("new"));
+        assert_eq!(patched.auth.read_only_token, None);
+    }
+
+    #[test]
+    fn merge_patch_merges_objects_and_replaces_the_rest() {
+        let mut target = json!({
+            "a": { "b": 1, "c": [1, 2] },
+            "d": "keep",
+            "e": 5,
+        });
+        merge_patch(
+            &mut target,
+            json!({ "a": { "b": 2, "c": [3] }, "e": null, "f": { "g": true } }),
+        );
+
+        assert_eq!(
+            target,
+            json!({
+                "a": { "b": 2, "c": [3] },
+                "d": "keep",
+                "f": { "g": true },
+            })
+        );
+    }
+
+    #[test]
+    fn merge_patch_replaces_non_objects() {
+        let mut target = json!({ "a": 1 });
+        merge_patch(&mut target, json!([1]));
+        assert_eq!(target, json!([1]));
+
+        let mut target = json!({ "a": 1 });
+        merge_patch(&mut target, json!({ "a": { "b": null, "c": 1 } }));
+        assert_eq!(target, json!({ "a": { "c": 1 } }));
+    }
+
+    #[test]
+    fn patch_keeps_unrelated_settings() {
+        let config = with_tokens();
+        let patch = json!({ "midi": { "devices": ["DDJ-400"] }, "osc": { "listen": null } });
+
+        let patched = apply_patch(&config, patch).unwrap();
+        assert_eq!(patched.midi.devices, ["DDJ-400"]);
+        assert_eq!(patched.midi.virtual_port, config.midi.virtual_port);
+        assert_eq!(patched.osc.listen, None);
+        assert_eq!(patched.port, config.port);
+    }
+
+    #[test]
+    fn invalid_patches_are_rejected() {
+        let config = with_tokens();
+
+        assert!(apply_patch(&config, json!({ "port": "web" })).is_err());
+        let patch = json!({ "midi": { "clock_out": ["Missing"] } });
+        assert!(apply_patch(&config, patch).is_err());
+    }
+}
@@ -0,0 +1,120 @@
+use std::future::{ready, Ready};
+
+use actix_web::{
+    dev::Payload, error::InternalError, http::StatusCode, web::Data, FromRequest, HttpRequest,
+    HttpResponse,
+};
+use serde::Deserialize;
+
+use crate::{eventlog::LogLevel, msg::SystemMessage};
+
+use super::{AppState, GenericResponse};
+
+//
+// Optional token authentication.
+// Browsers cannot set headers on websockets, so the token is also accepted as `?token=` query parameter.
+//
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Role {
+    /// May receive everything but not change anything.
+    ReadOnly,
+    FullControl,
+}
+
+/// Extractor for endpoints which change state, rejects read-only clients.
+pub struct FullControl;
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+fn request_token(req: &HttpRequest) -> Option<String> {
+    let header = req
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    if let Some(token) = header {
+        return Some(token.to_string());
+    }
+
+    actix_web::web::Query::<TokenQuery>::from_query(req.query_string())
+        .ok()
+        .and_then(|query| query.into_inner().token)
+}
+
+// Compares in constant time, the token must not be guessable from response times.
+fn token_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+fn reject(req: &HttpRequest, data: &AppState, status: StatusCode, reason: &str) -> actix_web::Error {
+    let peer = req.connection_info().peer_addr().unwrap_or("unknown").to_string();
+    let msg = format!("[AUTH] Rejected {peer} on {}: {reason}", req.path());
+    // Written to the log by the broadcast thread.
+    let _ = data.system_out.send(SystemMessage::log(LogLevel::Warn, msg));
+
+    let response =
+        HttpResponse::build(status).json(GenericResponse::err("not authorized", reason));
+
+    InternalError::from_response(reason.to_string(), response).into()
+}
+
+fn authenticate(req: &HttpRequest) -> Result<Role, actix_web::Error> {
+    let Some(data) = req.app_data::<Data<AppState>>() else {
+        return Err(actix_web::error::ErrorInternalServerError("missing app state"));
+    };
+
+    let auth = data.config.lock().unwrap().auth.clone();
+    if auth.token.is_none() && auth.read_only_token.is_none() {
+        return Ok(Role::FullControl);
+    }
+
+    let Some(token) = request_token(req) else {
+        return Err(reject(req, data, StatusCode::UNAUTHORIZED, "missing token"));
+    };
+
+    if auth.token.as_deref().is_some_and(|t| token_eq(t, &token)) {
+        return Ok(Role::FullControl);
+    }
+
+    if auth
+        .read_only_token
+        .as_deref()
+        .is_some_and(|t| token_eq(t, &token))
+    {
+        return Ok(Role::ReadOnly);
+    }
+
+    Err(reject(req, data, StatusCode::UNAUTHORIZED, "invalid token"))
+}
+
+impl FromRequest for Role {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(authenticate(req))
+    }
+}
+
+impl FromRequest for FullControl {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(authenticate(req).and_then(|role| match role {
+            Role::FullControl => Ok(FullControl),
+            Role::ReadOnly => {
+                let data = req.app_data::<Data<AppState>>();
+                Err(match data {
+                    Some(data) => reject(req, data, StatusCode::FORBIDDEN, "read-only"),
+                    None => actix_web::error::ErrorInternalServerError("missing app state"),
+                })
+            }
+        }))
+    }
+}
@@ -1,8 +1,10 @@
 mod html;
 mod api;
+mod auth;
 mod state;
 mod ws;
-pub use state::AppState;
+pub use auth::{FullControl, Role};
+pub use state::{AppState, SystemStatus};
 pub use html::*;
 pub use api::*;
 pub use ws::*;
@@ -12,6 +14,8 @@ pub struct GenericResponse<'response> {
     success: bool,
     message: &'response str,
     error: Option<&'response str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<serde_json::Value>,
 }
 
 impl<'response> GenericResponse<'response> {
@@ -20,6 +24,15 @@ impl<'response> GenericResponse<'response> {
             success: true,
             message,
             error: None,
+            data: None,
+        }
+    }
+    pub fn success_with_data(message: &'response str, data: impl serde::Serialize) -> Self {
+        Self {
+            success: true,
+            message,
+            error: None,
+            data: serde_json::to_value(data).ok(),
         }
     }
     pub fn err(message: &'response str, error: &'response str) -> Self {
@@ -27,6 +40,7 @@ impl<'response> GenericResponse<'response> {
             success: false,
             message,
             error: Some(error),
+            data: None,
         }
     }
 }
@@ -1,13 +1,50 @@
 use std::{
-    borrow::Cow, collections::HashMap, sync::{Arc, Mutex}
+    borrow::Cow, sync::{Arc, Mutex}, time::{Duration, Instant}
 };
 
-use crate::{app::FromFrontend, config::Config, msg::UnifiedMessage};
+use cpal::traits::DeviceTrait;
+
+use crate::{
+    app::FromFrontend, broadcast::Consumers, config::Config, eventlog::EventLog, msg::SystemMessage,
+    tempo::TempoStatus,
+};
 
 pub struct AppState {
     pub from_frontend_sender: crossbeam_channel::Sender<FromFrontend>,
-    pub to_frontend_consumers:
-        Arc<Mutex<HashMap<String, crossbeam_channel::Sender<UnifiedMessage>>>>,
+    pub to_frontend_consumers: Consumers,
     pub config: Arc<Mutex<Config>>,
     pub config_path: String,
+    pub status: Arc<Mutex<SystemStatus>>,
+    pub system_out: crossbeam_channel::Sender<SystemMessage>,
+    pub event_log: EventLog,
+}
+
+//
+// Latest system state, kept up to date by the broadcast thread for the REST API.
+//
+
+#[derive(Default)]
+pub struct SystemStatus {
+    pub heartbeat: Option<(usize, Instant)>,
+    pub tick_speed: Duration,
+    pub loop_speed: Duration,
+    pub audio_device: Option<String>,
+    pub tempo: Option<TempoStatus>,
+    pub dmx: Option<Box<[u8; 513]>>,
+}
+
+impl SystemStatus {
+    pub fn update(&mut self, message: &SystemMessage) {
+        match message {
+            SystemMessage::Heartbeat(seq) => self.heartbeat = Some((*seq, Instant::now())),
+            SystemMessage::TickSpeed(duration) => self.tick_speed = *duration,
+            SystemMessage::LoopSpeed(duration) => self.loop_speed = *duration,
+            SystemMessage::AudioSelected(device) => {
+                self.audio_device = device.as_ref().and_then(|d| d.name().ok());
+            }
+            SystemMessage::Tempo(status) => self.tempo = Some(*status),
+            SystemMessage::DMX(channels) => self.dmx = Some(channels.clone()),
+            _ => {}
+        }
+    }
 }
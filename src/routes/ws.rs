@@ -1,86 +1,161 @@
-use std::{path::PathBuf, str::FromStr, sync::{Arc, Mutex}};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::atomic::Ordering,
+    time::Duration,
+};
 
 // use actix::{Actor, StreamHandler};
 use actix_web::{
-    rt::{self, task::yield_now},
+    rt,
     web::{self, Data},
     HttpRequest, HttpResponse, Result,
 };
 // use actix_web_actors::ws;
-use actix_ws::AggregatedMessage;
+use actix_ws::{AggregatedMessage, Closed, Session};
+use anyhow::bail;
 use cpal::traits::DeviceTrait;
-use crossbeam_channel::TryRecvError;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
-    app::{FromFrontend, MatrixEvent}, config, msg::{Signal, SystemMessage, UnifiedMessage}, utils::device_from_name
+    app::{ControlEvent, FromFrontend, MatrixEvent}, broadcast::{Consumer, Subscriptions}, config, eventlog::{LogEntry, LogLevel}, metrics::METRICS, midi::learn::{MidiLearnRequest, MidiLearnStatus, MidiLearnTarget}, msg::{Signal, SystemMessage, Topic, UnifiedMessage, WasmControlsConfig, WasmControlsLog, WasmControlsSet}, tempo::TempoStatus
 };
 
-use super::AppState;
+use super::{api::select_audio_device, AppState, Role};
 
 // struct MyWebSocket {
 //     app_state: web::Data<AppState>,
 // }
 
+//
+// Protocol version, bump on breaking changes.
+// Keep in sync with `PROTOCOL_VERSION` in `blaulicht-web/src/lib/types.ts`.
+//
+
+pub const WS_PROTOCOL_VERSION: u32 = 2;
+
 //
 // From frontend message.
 //
 
 #[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "kind", content = "value")]
 pub enum WSFromFrontendKind {
-    SelectAudioDevice,
-    SelectSerialDevice,
+    Hello { version: u32 },
+    SelectAudioDevice(Option<String>),
+    SelectSerialDevice(Option<String>),
     Reload,
-    MatrixControl,
+    MatrixControl(MatrixEvent),
+    Control(ControlEvent),
+    MidiLearn(MidiLearnTarget),
+    MidiLearnCancel,
+    /// Replaces the subscribed topics, each with an optional max rate in messages per second and kind.
+    Subscribe(HashMap<Topic, Option<f32>>),
 }
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct WSFromFrontend {
+    /// Echoed in the reply, requests without id only get a reply on errors.
+    #[serde(default)]
+    id: Option<u64>,
+    #[serde(flatten)]
     kind: WSFromFrontendKind,
-    value: serde_json::Value,
 }
 
-fn process_ws_from_frontend(value: WSFromFrontend, data: Data<AppState>) -> FromFrontend {
-    match value.kind {
+const LEARNED_MAPPING_PATH: &str = "mappings/learned.toml";
+
+fn process_ws_from_frontend(
+    kind: WSFromFrontendKind,
+    data: &AppState,
+    consumer: &Consumer,
+    role: Role,
+) -> anyhow::Result<Option<FromFrontend>> {
+    let mutating = !matches!(
+        kind,
+        WSFromFrontendKind::Hello { .. } | WSFromFrontendKind::Subscribe(_)
+    );
+    if mutating && role == Role::ReadOnly {
+        let _ = data.system_out.send(SystemMessage::log(
+            LogLevel::Warn,
+            format!("[AUTH] Rejected read-only websocket request: {kind:?}"),
+        ));
+        bail!("Not authorized: read-only");
+    }
+
+    let message = match kind {
+        WSFromFrontendKind::Hello { version } => {
+            if version != WS_PROTOCOL_VERSION {
+                bail!("Unsupported protocol version {version}, expected {WS_PROTOCOL_VERSION}");
+            }
+            return Ok(None);
+        }
+        WSFromFrontendKind::Subscribe(topics) => {
+            consumer.subscribe(subscriptions(topics)?);
+            return Ok(None);
+        }
         WSFromFrontendKind::Reload => FromFrontend::Reload,
-        WSFromFrontendKind::MatrixControl => {
-            let control: MatrixEvent = serde_json::from_value(value.value).unwrap();
-            FromFrontend::MatrixControl(control)
+        WSFromFrontendKind::MatrixControl(control) => FromFrontend::MatrixControl(control),
+        WSFromFrontendKind::Control(control) => FromFrontend::Control(control),
+        WSFromFrontendKind::MidiLearn(target) => FromFrontend::MidiLearn(MidiLearnRequest {
+            target,
+            mapping_path: learn_mapping_path(data)?,
+        }),
+        WSFromFrontendKind::MidiLearnCancel => FromFrontend::MidiLearnCancel,
+        WSFromFrontendKind::SelectAudioDevice(None) => FromFrontend::SelectInputDevice(None),
+        WSFromFrontendKind::SelectAudioDevice(Some(device_name)) => {
+            match select_audio_device(data, &device_name)? {
+                Some(device) => FromFrontend::SelectInputDevice(Some(device)),
+                None => bail!("No such audio device: <{device_name}>"),
+            }
         }
-        WSFromFrontendKind::SelectAudioDevice => {
-            if value.value == serde_json::Value::Null {
-                FromFrontend::SelectInputDevice(None)
-            } else {
-                let serde_json::Value::String(device_name) = value.value else {
-                    panic!("not a string");
-                };
-
-                log::info!("[WS] Selected INPUT: <{}>", &device_name);
-                let device = device_from_name(device_name.clone());
+        WSFromFrontendKind::SelectSerialDevice(device) => FromFrontend::SelectSerialDevice(device),
+    };
 
-                let mut config_mut = data.config.lock().unwrap();
+    Ok(Some(message))
+}
 
-                config_mut.default_audio_device = match device {
-                    Some(_) => Some(device_name),
-                    None => None,
-                };
+const WS_QUEUE_CAPACITY: usize = 256;
 
-                let path = PathBuf::from_str(&data.config_path).unwrap();
-                config::write_config(path, config_mut.clone()).unwrap();
+// Everything except DMX frames, those are sent by the binary sink.
+fn default_subscriptions() -> Subscriptions {
+    Subscriptions::new([
+        (Topic::Signals, None),
+        (Topic::Logs, None),
+        (Topic::Controls, None),
+        (Topic::Stats, None),
+    ])
+}
 
-                FromFrontend::SelectInputDevice(device)
+fn subscriptions(topics: HashMap<Topic, Option<f32>>) -> anyhow::Result<Subscriptions> {
+    let topics = topics
+        .into_iter()
+        .map(|(topic, rate)| match rate {
+            None => Ok((topic, None)),
+            Some(rate) if rate > 0.0 && rate.is_finite() => {
+                Ok((topic, Some(Duration::from_secs_f32(1.0 / rate))))
             }
-        }
-        WSFromFrontendKind::SelectSerialDevice => {
-            if value.value == serde_json::Value::Null {
-                FromFrontend::SelectSerialDevice(None)
-            } else {
-                let device = value.value.to_string();
-                FromFrontend::SelectSerialDevice(Some(device))
-            }
-        }
+            Some(rate) => bail!("Invalid rate {rate} for {topic:?}"),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(Subscriptions::new(topics))
+}
+
+// Learned controls need a file to be saved to.
+fn learn_mapping_path(data: &AppState) -> anyhow::Result<PathBuf> {
+    let mut config_mut = data.config.lock().unwrap();
+
+    if let Some(path) = &config_mut.midi.mapping {
+        return Ok(path.clone());
     }
+
+    let path = PathBuf::from(LEARNED_MAPPING_PATH);
+    config_mut.midi.mapping = Some(path.clone());
+
+    let config_path = PathBuf::from(&data.config_path);
+    config::write_config(config_path, config_mut.clone())?;
+    Ok(path)
 }
 
 //
@@ -140,103 +215,135 @@ impl From<Signal> for WSSignal {
 //
 
 #[derive(Serialize, Debug)]
-pub enum WSSystemMessageKind {
-    Heartbeat,
-    Log,
-    WasmLog,
-    WasmControlsLog,
-    WasmControlsSet,
-    WasmControlsConfig,
-    TickSpeed,
-    LoopSpeed,
-    AudioSelected,
-    AudioDevicesView,
-    SerialSelected,
-    SerialDevicesView,
-    Dmx,
-}
-
-#[derive(Serialize, Debug)]
-pub struct WSSystemMessage {
-    kind: WSSystemMessageKind,
-    value: serde_json::Value,
+#[serde(tag = "kind", content = "value")]
+pub enum WSSystemMessage {
+    // Protocol.
+    Hello { version: u32 },
+    Reply { id: u64 },
+    Error { id: Option<u64>, message: String },
+    // System.
+    Heartbeat(usize),
+    Log(LogEntry),
+    /// Recent log entries, sent once after `Hello`.
+    LogBacklog(Vec<LogEntry>),
+    WasmControlsLog(WasmControlsLog),
+    WasmControlsSet(WasmControlsSet),
+    WasmControlsConfig(WasmControlsConfig),
+    MidiLearn(MidiLearnStatus),
+    // In microseconds.
+    TickSpeed(u64),
+    LoopSpeed(u64),
+    AudioSelected(Option<String>),
+    AudioDevicesView(Vec<String>),
+    Tempo(TempoStatus),
+    Dmx(Vec<u8>),
 }
 
 impl From<SystemMessage> for WSSystemMessage {
     fn from(value: SystemMessage) -> Self {
         match value {
-            SystemMessage::Heartbeat(seq) => Self {
-                kind: WSSystemMessageKind::Heartbeat,
-                value: serde_json::to_value(seq).unwrap(),
-            },
-            SystemMessage::Log(msg) => Self {
-                kind: WSSystemMessageKind::Log,
-                value: serde_json::to_value(msg).unwrap(),
-            },
-            SystemMessage::WasmLog(msg) => Self {
-                kind: WSSystemMessageKind::WasmLog,
-                value: serde_json::to_value(msg).unwrap(),
-            },
-            SystemMessage::WasmControlsLog(msg) => Self {
-                kind: WSSystemMessageKind::WasmControlsLog,
-                value: serde_json::to_value(msg).unwrap(),
-            },
-            SystemMessage::WasmControlsSet(msg) => Self {
-                kind: WSSystemMessageKind::WasmControlsSet,
-                value: serde_json::to_value(msg).unwrap(),
-            },
-            SystemMessage::WasmControlsConfig(msg) => Self {
-                kind: WSSystemMessageKind::WasmControlsConfig,
-                value: serde_json::to_value(msg).unwrap(),
-            },
-            SystemMessage::TickSpeed(duration) => Self {
-                kind: WSSystemMessageKind::TickSpeed,
-                value: serde_json::to_value(duration.as_micros() as u64).unwrap(),
-            },
-            SystemMessage::LoopSpeed(duration) => Self {
-                kind: WSSystemMessageKind::LoopSpeed,
-                value: serde_json::to_value(duration.as_micros() as u64).unwrap(),
-            },
-            SystemMessage::AudioSelected(device) => Self {
-                kind: WSSystemMessageKind::AudioDevicesView,
-                value: match device {
-                    Some(d) => serde_json::to_value(d.name().unwrap()).unwrap(),
-                    None => serde_json::Value::Null,
-                },
-            },
-            SystemMessage::AudioDevicesView(devs) => Self {
-                kind: WSSystemMessageKind::AudioDevicesView,
-                value: serde_json::to_value(
-                    devs.iter()
-                        .map(|d| d.1.name().unwrap())
-                        .collect::<Vec<String>>(),
-                )
-                .unwrap(),
-            },
-            SystemMessage::DMX(chans) => Self {
-                kind: WSSystemMessageKind::Dmx,
-                value: serde_json::to_value(chans.to_vec()).unwrap(),
-            },
+            SystemMessage::Heartbeat(seq) => Self::Heartbeat(seq),
+            SystemMessage::Log(entry) => Self::Log(entry),
+            SystemMessage::WasmControlsLog(msg) => Self::WasmControlsLog(msg),
+            SystemMessage::WasmControlsSet(msg) => Self::WasmControlsSet(msg),
+            SystemMessage::WasmControlsConfig(msg) => Self::WasmControlsConfig(msg),
+            SystemMessage::MidiLearn(status) => Self::MidiLearn(status),
+            SystemMessage::TickSpeed(duration) => Self::TickSpeed(duration.as_micros() as u64),
+            SystemMessage::LoopSpeed(duration) => Self::LoopSpeed(duration.as_micros() as u64),
+            SystemMessage::AudioSelected(device) => {
+                Self::AudioSelected(device.and_then(|d| d.name().ok()))
+            }
+            SystemMessage::AudioDevicesView(devs) => Self::AudioDevicesView(
+                devs.iter().filter_map(|d| d.1.name().ok()).collect(),
+            ),
+            SystemMessage::Tempo(status) => Self::Tempo(status),
+            SystemMessage::DMX(chans) => Self::Dmx(chans.to_vec()),
+        }
+    }
+}
+
+async fn send_json(session: &mut Session, message: &impl Serialize) -> Result<(), Closed> {
+    match serde_json::to_string(message) {
+        Ok(text) => session.text(text).await,
+        Err(err) => {
+            log::error!("[WS] Failed to serialize message: {err}");
+            Ok(())
+        }
+    }
+}
+
+fn handle_text(
+    text: &str,
+    data: &AppState,
+    consumer: &Consumer,
+    role: Role,
+) -> Option<WSSystemMessage> {
+    // The id is read separately, so that malformed requests still get a matching error.
+    let value: serde_json::Value = match serde_json::from_str(text) {
+        Ok(value) => value,
+        Err(err) => {
+            return Some(WSSystemMessage::Error {
+                id: None,
+                message: format!("Invalid JSON: {err}"),
+            })
+        }
+    };
+    let id = value.get("id").and_then(|id| id.as_u64());
+
+    let request: WSFromFrontend = match serde_json::from_value(value) {
+        Ok(request) => request,
+        Err(err) => {
+            return Some(WSSystemMessage::Error {
+                id,
+                message: format!("Invalid request: {err}"),
+            })
+        }
+    };
+
+    let result = process_ws_from_frontend(request.kind, data, consumer, role).and_then(|message| {
+        if let Some(message) = message {
+            data.from_frontend_sender
+                .send(message)
+                .map_err(|_| anyhow::anyhow!("Engine is not running"))?;
+        }
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => request.id.map(|id| WSSystemMessage::Reply { id }),
+        Err(err) => {
+            log::warn!("[WS] Request failed: {err:#}");
+            Some(WSSystemMessage::Error {
+                id: request.id,
+                message: format!("{err:#}"),
+            })
         }
     }
 }
 
 pub async fn ws_handler(
     req: HttpRequest,
+    role: Role,
     data: Data<AppState>,
     stream: web::Payload,
 ) -> Result<HttpResponse, actix_web::Error> {
     let (res, mut session, stream) = actix_ws::handle(&req, stream)?;
 
     // add consumer:
-    let (unified_sender, unified_receiver) = crossbeam_channel::unbounded();
-    let ip = req.connection_info().peer_addr().unwrap().to_string();
+    let consumer = Consumer::new(WS_QUEUE_CAPACITY, default_subscriptions());
+    let ip = req
+        .connection_info()
+        .peer_addr()
+        .unwrap_or("unknown")
+        .to_string();
     let id = Uuid::new_v4().to_string();
     log::trace!("[WS] new IP connected: {ip}: {id}");
-    {
+    // The broadcast thread logs under the same lock, no entry is missed or sent twice.
+    let backlog = {
         let mut consumers = data.to_frontend_consumers.lock().unwrap();
-        consumers.insert(id.clone(), unified_sender);
-    }
+        consumers.insert(id.clone(), consumer.clone());
+        data.event_log.backlog()
+    };
 
     let mut stream = stream
         .aggregate_continuations()
@@ -244,81 +351,71 @@ pub async fn ws_handler(
         .max_continuation_size(2_usize.pow(20));
 
     let mut session2 = session.clone();
-
-    let b = Arc::new(Mutex::new(true));
-
-    let a = b.clone();
+    let consumer2 = consumer.clone();
+
+    let greeting = [
+        WSSystemMessage::Hello {
+            version: WS_PROTOCOL_VERSION,
+        },
+        WSSystemMessage::LogBacklog(backlog),
+    ];
+    for message in &greeting {
+        if send_json(&mut session, message).await.is_err() {
+            // The broadcast thread drops closed consumers.
+            consumer.close();
+            return Ok(res);
+        }
+    }
 
     rt::spawn(async move {
-        // let mut last_sent_value: HashMap<WSSignalKind, u8> = HashMap::new();
-
-        loop {
-            {
-                if !*a.lock().unwrap() {
-                    break;
+        while let Some(message) = consumer2.recv().await {
+            let sent = match message {
+                UnifiedMessage::Signal(signal) => {
+                    send_json(&mut session2, &WSSignal::from(signal)).await
                 }
-            }
+                UnifiedMessage::System(system_message) => {
+                    send_json(&mut session2, &WSSystemMessage::from(system_message)).await
+                }
+            };
 
-            match unified_receiver.try_recv() {
-                Ok(sys) => match sys {
-                    UnifiedMessage::Signal(signal) => {
-                        let ws_signal = WSSignal::from(signal);
-
-                        // if let Some(prev) = last_sent_value.get(&ws_signal.kind) {
-                        // // Bigger change.
-                        // if (*prev as i16 - ws_signal.value as i16).abs() > 5 {
-                        //     last_sent_value.insert(ws_signal.kind, ws_signal.value);
-
-                        session2
-                            .text(serde_json::to_string(&ws_signal).unwrap())
-                            .await
-                            .unwrap();
-                        // }
-                        // }
-                    }
-                    UnifiedMessage::System(SystemMessage::DMX(_)) => {}
-                    UnifiedMessage::System(system_message) => {
-                        let ws_system = WSSystemMessage::from(system_message);
-                        session2
-                            .text(serde_json::to_string(&ws_system).unwrap())
-                            .await
-                            .unwrap();
-                    }
-                },
-                Err(TryRecvError::Empty) => {}
-                Err(TryRecvError::Disconnected) => unreachable!(),
+            if sent.is_err() {
+                break;
             }
-
-            yield_now().await;
         }
+
+        consumer2.close();
     });
 
     // start task but don't wait for it
-    let from_frontend_sender = data.from_frontend_sender.clone();
     rt::spawn(async move {
+        METRICS.websocket_clients.fetch_add(1, Ordering::Relaxed);
+
         // receive messages from websocket
         while let Some(msg) = stream.recv().await {
             match msg {
                 Ok(AggregatedMessage::Text(text)) => {
-                    // echo text message
-                    session.text(text.clone()).await.unwrap();
-
-                    let msg: WSFromFrontend =
-                        serde_json::from_str(text.to_string().as_str()).unwrap();
-
-                    let converted = process_ws_from_frontend(msg, data.clone());
-
-                    from_frontend_sender.send(converted).unwrap();
+                    if let Some(reply) = handle_text(&text, &data, &consumer, role) {
+                        if send_json(&mut session, &reply).await.is_err() {
+                            break;
+                        }
+                    }
                 }
 
-                Ok(AggregatedMessage::Binary(bin)) => {
-                    // echo binary message
-                    session.binary(bin).await.unwrap();
+                Ok(AggregatedMessage::Binary(_)) => {
+                    let reply = WSSystemMessage::Error {
+                        id: None,
+                        message: "Binary messages are not supported".to_string(),
+                    };
+                    if send_json(&mut session, &reply).await.is_err() {
+                        break;
+                    }
                 }
 
                 Ok(AggregatedMessage::Ping(msg)) => {
                     // respond to PING frame with PONG frame
-                    session.pong(&msg).await.unwrap();
+                    if session.pong(&msg).await.is_err() {
+                        break;
+                    }
                 }
                 Ok(AggregatedMessage::Close(e)) => {
                     break;
@@ -331,8 +428,8 @@ pub async fn ws_handler(
         }
 
         log::trace!("[WS] disconnected IP: {ip}");
-        let mut a = b.lock().unwrap();
-        *a = false;
+        consumer.close();
+        METRICS.websocket_clients.fetch_sub(1, Ordering::Relaxed);
 
         {
             let mut consumers = data.to_frontend_consumers.lock().unwrap();
@@ -345,19 +442,24 @@ pub async fn ws_handler(
 
 pub async fn binary_ws_handler(
     req: HttpRequest,
+    _: Role,
     data: Data<AppState>,
     stream: web::Payload,
 ) -> Result<HttpResponse, actix_web::Error> {
     let (res, mut session, stream) = actix_ws::handle(&req, stream)?;
 
     // add consumer:
-    let (unified_sender, unified_receiver) = crossbeam_channel::unbounded();
-    let ip = req.connection_info().peer_addr().unwrap().to_string();
+    let consumer = Consumer::new(WS_QUEUE_CAPACITY, Subscriptions::new([(Topic::Dmx, None)]));
+    let ip = req
+        .connection_info()
+        .peer_addr()
+        .unwrap_or("unknown")
+        .to_string();
     let id = Uuid::new_v4().to_string();
     log::trace!("[DMX-WS] new IP connected: {ip}: {id}");
     {
         let mut consumers = data.to_frontend_consumers.lock().unwrap();
-        consumers.insert(id.clone(), unified_sender);
+        consumers.insert(id.clone(), consumer.clone());
     }
 
     let mut stream = stream
@@ -366,45 +468,33 @@ pub async fn binary_ws_handler(
         .max_continuation_size(2_usize.pow(20));
 
     let mut session2 = session.clone();
-
-    let b = Arc::new(Mutex::new(true));
-
-    let a = b.clone();
+    let consumer2 = consumer.clone();
 
     rt::spawn(async move {
-        loop {
-            {
-                if !*a.lock().unwrap() {
+        while let Some(message) = consumer2.recv().await {
+            if let UnifiedMessage::System(SystemMessage::DMX(dat)) = message {
+                let mut vec = dat.to_vec();
+                vec.remove(0);
+                if session2.binary(vec).await.is_err() {
                     break;
                 }
             }
-
-            match unified_receiver.try_recv() {
-                Ok(sys) => {
-                    if let UnifiedMessage::System(SystemMessage::DMX(dat)) = sys {
-                        let mut vec = dat.to_vec();
-                        vec.remove(0);
-                        match session2.binary(vec).await {
-                            Ok(_) => {}
-                            Err(_) => break,
-                        }
-                    }
-                }
-                Err(TryRecvError::Empty) => {}
-                Err(TryRecvError::Disconnected) => unreachable!(),
-            }
-
-            yield_now().await;
         }
+
+        consumer2.close();
     });
 
     rt::spawn(async move {
+        METRICS.websocket_clients.fetch_add(1, Ordering::Relaxed);
+
         // receive messages from websocket
         while let Some(msg) = stream.recv().await {
             match msg {
                 Ok(AggregatedMessage::Ping(msg)) => {
                     // respond to PING frame with PONG frame
-                    session.pong(&msg).await.unwrap();
+                    if session.pong(&msg).await.is_err() {
+                        break;
+                    }
                 }
                 Ok(AggregatedMessage::Close(e)) => {
                     break;
@@ -416,8 +506,8 @@ pub async fn binary_ws_handler(
             }
         }
 
-        let mut a = b.lock().unwrap();
-        *a = false;
+        consumer.close();
+        METRICS.websocket_clients.fetch_sub(1, Ordering::Relaxed);
 
         log::trace!("[DMX-WS] disconnected IP: {ip}");
 
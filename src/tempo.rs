@@ -0,0 +1,331 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{
+    app::{ControlEvent, ControlValue},
+    msg::BpmInfo,
+    wasm::TickInput,
+};
+
+//
+// Tempo of the operator: tap tempo, manual BPM and nudging.
+// It replaces or blends with the analyzed tempo before the tick input reaches the guest.
+//
+// tempo.tap     button    every tap starts a beat, two or more set the tempo
+// tempo.bpm     fader     manual tempo, 0 follows the analyzed tempo again
+// tempo.adjust  encoder   0.5 BPM per step, starting from the current tempo
+// tempo.nudge   encoder   moves the beats 10 ms earlier (positive) or later per step,
+//                         needs the operator tempo or the phase of a MIDI clock
+// tempo.blend   fader     share of the operator tempo, 1.0 (default) replaces the analyzed one
+// tempo.follow  button    follows the analyzed tempo again
+//
+
+pub const CONTROL_PREFIX: &str = "tempo.";
+
+// Taps further apart start a new tempo.
+const TAP_TIMEOUT: Duration = Duration::from_secs(2);
+const TAP_HISTORY_LEN: usize = 8;
+
+const NUDGE_STEP_MILLIS: i32 = 10;
+const ADJUST_STEP: f32 = 0.5;
+
+// The guest receives the tempo as `u8`.
+const MIN_BPM: f32 = 20.0;
+const MAX_BPM: f32 = 255.0;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TempoSource {
+    /// Detected from the audio, or an external MIDI clock.
+    Analyzed,
+    Tap,
+    Manual,
+    /// The operator tempo mixed with the analyzed one.
+    Blend,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct TempoStatus {
+    pub bpm: f32,
+    pub source: TempoSource,
+}
+
+pub struct Tempo {
+    taps: VecDeque<Instant>,
+    // Operator tempo and whether it was tapped or set.
+    bpm: Option<(f32, TempoSource)>,
+    // Start of a beat of the operator tempo, set by taps.
+    anchor: Instant,
+    // Shifts the beat phase of every source, reset by taps.
+    nudge_millis: i32,
+    blend: f32,
+    // Whether the last tick had a beat phase, only the operator tempo has one otherwise.
+    external_phase: bool,
+}
+
+impl Default for Tempo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tempo {
+    pub fn new() -> Self {
+        Self {
+            taps: VecDeque::with_capacity(TAP_HISTORY_LEN),
+            bpm: None,
+            anchor: Instant::now(),
+            nudge_millis: 0,
+            blend: 1.0,
+            external_phase: false,
+        }
+    }
+
+    pub fn is_control(name: &str) -> bool {
+        name.starts_with(CONTROL_PREFIX)
+    }
+
+    pub fn control(&mut self, event: &ControlEvent, analyzed: &BpmInfo, now: Instant) {
+        match (event.name.trim_start_matches(CONTROL_PREFIX), event.value) {
+            ("tap", ControlValue::Bool(true)) => self.tap(now),
+            ("tap" | "follow", ControlValue::Bool(false)) => {}
+            ("bpm", ControlValue::Float(bpm)) if bpm <= 0.0 => self.follow(),
+            ("bpm", ControlValue::Float(bpm)) => self.set_bpm(bpm, TempoSource::Manual),
+            ("adjust", ControlValue::Relative(steps)) => {
+                let current = match self.bpm {
+                    Some((bpm, _)) => bpm,
+                    None => analyzed.bpm as f32,
+                };
+                self.set_bpm(current + steps as f32 * ADJUST_STEP, TempoSource::Manual);
+            }
+            ("nudge", ControlValue::Relative(steps)) => self.nudge(steps, analyzed),
+            ("blend", ControlValue::Float(blend)) => self.blend = blend.clamp(0.0, 1.0),
+            ("follow", ControlValue::Bool(true)) => self.follow(),
+            _ => log::warn!("[TEMPO] Unknown control: {event:?}"),
+        }
+    }
+
+    fn tap(&mut self, now: Instant) {
+        if self
+            .taps
+            .back()
+            .is_some_and(|last| now.duration_since(*last) > TAP_TIMEOUT)
+        {
+            self.taps.clear();
+        }
+        if self.taps.len() >= TAP_HISTORY_LEN {
+            self.taps.pop_front();
+        }
+        self.taps.push_back(now);
+        self.anchor = now;
+        self.nudge_millis = 0;
+
+        if let (Some(first), true) = (self.taps.front(), self.taps.len() > 1) {
+            let interval = now.duration_since(*first).as_secs_f32() / (self.taps.len() - 1) as f32;
+            self.set_bpm(60.0 / interval, TempoSource::Tap);
+        }
+    }
+
+    fn nudge(&mut self, steps: i32, analyzed: &BpmInfo) {
+        let source = self.status(analyzed).source;
+        if matches!(source, TempoSource::Analyzed | TempoSource::Blend) && !self.external_phase {
+            log::warn!("[TEMPO] Nothing to nudge, tap the tempo or follow a MIDI clock");
+            return;
+        }
+
+        self.nudge_millis += steps * NUDGE_STEP_MILLIS;
+        log::info!("[TEMPO] Nudged by {} ms", self.nudge_millis);
+    }
+
+    fn set_bpm(&mut self, bpm: f32, source: TempoSource) {
+        let bpm = bpm.clamp(MIN_BPM, MAX_BPM);
+        log::info!("[TEMPO] {bpm:.1} BPM ({source:?})");
+        self.bpm = Some((bpm, source));
+    }
+
+    fn follow(&mut self) {
+        if self.bpm.take().is_some() {
+            log::info!("[TEMPO] Following the analyzed tempo");
+        }
+        self.taps.clear();
+    }
+
+    pub fn status(&self, analyzed: &BpmInfo) -> TempoStatus {
+        match self.bpm {
+            None => TempoStatus {
+                bpm: analyzed.bpm as f32,
+                source: TempoSource::Analyzed,
+            },
+            Some((bpm, source)) if self.blend >= 1.0 || analyzed.bpm == 0 => {
+                TempoStatus { bpm, source }
+            }
+            Some((bpm, _)) => TempoStatus {
+                bpm: analyzed.bpm as f32 * (1.0 - self.blend) + bpm * self.blend,
+                source: TempoSource::Blend,
+            },
+        }
+    }
+
+    /// Sets the tempo of the tick input. The full operator tempo replaces the beat phase with its
+    /// own grid, so that the beats follow the taps. Otherwise the incoming phase is kept.
+    pub fn apply(&mut self, input: &mut TickInput, analyzed: &BpmInfo, now: Instant) {
+        self.external_phase = input.beat_phase.is_some();
+        let status = self.status(analyzed);
+
+        let beat_millis = match status.source {
+            TempoSource::Analyzed => {
+                input.bpm = analyzed.bpm;
+                input.time_between_beats_millis = analyzed.time_between_beats_millis;
+                analyzed.time_between_beats_millis as f32
+            }
+            _ => {
+                let beat_millis = 60_000.0 / status.bpm;
+                input.bpm = status.bpm.round() as u8;
+                input.time_between_beats_millis = beat_millis.round() as u16;
+                beat_millis
+            }
+        };
+        if beat_millis <= 0.0 {
+            return;
+        }
+
+        let nudge = self.nudge_millis as f32 / beat_millis;
+        input.beat_phase = match status.source {
+            TempoSource::Tap | TempoSource::Manual => {
+                let elapsed = now.duration_since(self.anchor).as_secs_f32() * 1000.0;
+                Some(to_phase(elapsed / beat_millis + nudge))
+            }
+            TempoSource::Analyzed | TempoSource::Blend => input
+                .beat_phase
+                .map(|phase| to_phase(phase as f32 / 256.0 + nudge)),
+        };
+    }
+}
+
+// Position inside the beat (0-255) of a position in beats.
+fn to_phase(beats: f32) -> u8 {
+    (beats.rem_euclid(1.0) * 256.0).min(255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANALYZED: BpmInfo = BpmInfo {
+        bpm: 120,
+        time_between_beats_millis: 500,
+    };
+
+    fn event(name: &str, value: ControlValue) -> ControlEvent {
+        ControlEvent {
+            name: format!("{CONTROL_PREFIX}{name}"),
+            value,
+        }
+    }
+
+    fn tap_at(tempo: &mut Tempo, start: Instant, millis: &[u64]) {
+        for millis in millis {
+            let now = start + Duration::from_millis(*millis);
+            tempo.control(&event("tap", ControlValue::Bool(true)), &ANALYZED, now);
+        }
+    }
+
+    fn input(beat_phase: Option<u8>) -> TickInput {
+        TickInput {
+            beat_phase,
+            ..TickInput::default()
+        }
+    }
+
+    #[test]
+    fn follows_the_analyzed_tempo_by_default() {
+        let mut tempo = Tempo::new();
+        let mut input = input(Some(42));
+        tempo.apply(&mut input, &ANALYZED, Instant::now());
+
+        assert_eq!(tempo.status(&ANALYZED).source, TempoSource::Analyzed);
+        assert_eq!(input.bpm, 120);
+        assert_eq!(input.time_between_beats_millis, 500);
+        assert_eq!(input.beat_phase, Some(42));
+    }
+
+    #[test]
+    fn taps_set_the_tempo_and_the_grid() {
+        let start = Instant::now();
+        let mut tempo = Tempo::new();
+        tap_at(&mut tempo, start, &[0, 400, 800, 1200]);
+
+        let status = tempo.status(&ANALYZED);
+        assert_eq!(status.source, TempoSource::Tap);
+        assert!((status.bpm - 150.0).abs() < 0.01);
+
+        // Half a beat after the last tap.
+        let mut input = input(None);
+        tempo.apply(&mut input, &ANALYZED, start + Duration::from_millis(1400));
+        assert_eq!(input.bpm, 150);
+        assert_eq!(input.time_between_beats_millis, 400);
+        assert_eq!(input.beat_phase, Some(128));
+    }
+
+    #[test]
+    fn taps_after_the_timeout_start_over() {
+        let start = Instant::now();
+        let mut tempo = Tempo::new();
+        tap_at(&mut tempo, start, &[0, 1000, 5000, 5500]);
+
+        assert!((tempo.status(&ANALYZED).bpm - 120.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn blend_mixes_the_tempo_and_keeps_the_phase() {
+        let now = Instant::now();
+        let mut tempo = Tempo::new();
+        tempo.control(&event("bpm", ControlValue::Float(140.0)), &ANALYZED, now);
+        tempo.control(&event("blend", ControlValue::Float(0.5)), &ANALYZED, now);
+
+        let mut input = input(Some(64));
+        tempo.apply(&mut input, &ANALYZED, now);
+
+        let status = tempo.status(&ANALYZED);
+        assert_eq!(status.source, TempoSource::Blend);
+        assert!((status.bpm - 130.0).abs() < 0.01);
+        assert_eq!(input.bpm, 130);
+        assert_eq!(input.beat_phase, Some(64));
+    }
+
+    #[test]
+    fn nudge_shifts_the_incoming_phase() {
+        let now = Instant::now();
+        let mut tempo = Tempo::new();
+        tempo.apply(&mut input(Some(0)), &ANALYZED, now);
+
+        // 50 ms of a 500 ms beat.
+        tempo.control(&event("nudge", ControlValue::Relative(5)), &ANALYZED, now);
+        let mut input = input(Some(0));
+        tempo.apply(&mut input, &ANALYZED, now);
+        assert_eq!(input.beat_phase, Some(25));
+    }
+
+    #[test]
+    fn nudge_without_a_phase_is_ignored() {
+        let now = Instant::now();
+        let mut tempo = Tempo::new();
+        tempo.apply(&mut input(None), &ANALYZED, now);
+
+        tempo.control(&event("nudge", ControlValue::Relative(5)), &ANALYZED, now);
+        assert_eq!(tempo.nudge_millis, 0);
+    }
+
+    #[test]
+    fn zero_bpm_follows_the_analyzed_tempo() {
+        let now = Instant::now();
+        let mut tempo = Tempo::new();
+        tempo.control(&event("bpm", ControlValue::Float(100.0)), &ANALYZED, now);
+        tempo.control(&event("bpm", ControlValue::Float(0.0)), &ANALYZED, now);
+
+        assert_eq!(tempo.status(&ANALYZED).source, TempoSource::Analyzed);
+    }
+}
@@ -1,10 +1,10 @@
 use crossbeam_channel::Sender;
-use std::{net::UdpSocket, time::Instant};
+use std::{net::UdpSocket, path::PathBuf, time::Instant};
 
 use wasmtime::*;
 
 use crate::{
-    app::MidiEvent, msg::{SystemMessage, WasmControlsConfig, WasmControlsLog, WasmControlsSet},
+    app::{ControlEvent, ControlValue, MidiEvent, MidiMessage}, eventlog::{LogEntry, LogLevel, LogSource}, msg::{SystemMessage, WasmControlsConfig, WasmControlsLog, WasmControlsSet}, patch,
 };
 
 #[derive(Clone, Copy)]
@@ -17,10 +17,12 @@ pub struct TickInput {
     pub bass_avg: u8,
     pub bpm: u8,
     pub time_between_beats_millis: u16,
+    // Only known while following a MIDI clock.
+    pub beat_phase: Option<u8>,
 }
 
 impl TickInput {
-    fn serialize(&self, timer_start: Instant, initial: bool) -> [i32; 9] {
+    fn serialize(&self, timer_start: Instant, initial: bool) -> [i32; 10] {
         [
             Instant::now().duration_since(timer_start).as_millis() as i32,
             self.volume.into(),
@@ -30,11 +32,140 @@ impl TickInput {
             self.bass_avg.into(),
             self.bpm.into(),
             self.time_between_beats_millis.into(),
+            self.beat_phase.map_or(-1, i32::from),
             initial as i32,
         ]
     }
 }
 
+//
+// MIDI events are passed to the guest as a stream of records:
+// [device: u8, kind: u8, payload length: u16 (LE), payload...]
+// The kinds must be kept in sync with `decode_midi` in the guest.
+//
+
+impl MidiEvent {
+    fn serialize(&self, buf: &mut Vec<u8>) {
+        let (kind, payload): (u8, Vec<u8>) = match &self.message {
+            MidiMessage::Raw {
+                status,
+                data0,
+                data1,
+            } => (0, vec![*status, *data0, *data1]),
+            MidiMessage::NoteOff {
+                channel,
+                note,
+                velocity,
+            } => (1, vec![*channel, *note, *velocity]),
+            MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => (2, vec![*channel, *note, *velocity]),
+            MidiMessage::PolyPressure {
+                channel,
+                note,
+                pressure,
+            } => (3, vec![*channel, *note, *pressure]),
+            MidiMessage::ControlChange {
+                channel,
+                control,
+                value,
+            } => (4, vec![*channel, *control, *value]),
+            MidiMessage::ProgramChange { channel, program } => (5, vec![*channel, *program]),
+            MidiMessage::ChannelPressure { channel, pressure } => (6, vec![*channel, *pressure]),
+            MidiMessage::PitchBend { channel, value } => {
+                let [lo, hi] = value.to_le_bytes();
+                (7, vec![*channel, lo, hi])
+            }
+            MidiMessage::ControlChange14 {
+                channel,
+                control,
+                value,
+            } => {
+                let [lo, hi] = value.to_le_bytes();
+                (8, vec![*channel, *control, lo, hi])
+            }
+            MidiMessage::Nrpn {
+                channel,
+                parameter,
+                value,
+            } => {
+                let [p_lo, p_hi] = parameter.to_le_bytes();
+                let [v_lo, v_hi] = value.to_le_bytes();
+                (9, vec![*channel, p_lo, p_hi, v_lo, v_hi])
+            }
+            MidiMessage::SysEx(data) => (10, data.clone()),
+            MidiMessage::Clock => (11, vec![]),
+            MidiMessage::Start => (12, vec![]),
+            MidiMessage::Continue => (13, vec![]),
+            MidiMessage::Stop => (14, vec![]),
+        };
+
+        write_record(buf, self.device, kind, &payload);
+    }
+}
+
+//
+// Control events from the MIDI mapping share the stream with a dedicated kind.
+// Payload: [value kind: u8, value: 4 bytes (LE), name...]
+//
+
+const CONTROL_EVENT_KIND: u8 = 15;
+
+impl ControlEvent {
+    fn serialize(&self, buf: &mut Vec<u8>) {
+        let (value_kind, value) = match self.value {
+            ControlValue::Bool(value) => (0, (value as u32).to_le_bytes()),
+            ControlValue::Float(value) => (1, value.to_le_bytes()),
+            ControlValue::Relative(value) => (2, value.to_le_bytes()),
+        };
+
+        let mut payload = Vec::with_capacity(5 + self.name.len());
+        payload.push(value_kind);
+        payload.extend_from_slice(&value);
+        payload.extend_from_slice(self.name.as_bytes());
+
+        write_record(buf, 0, CONTROL_EVENT_KIND, &payload);
+    }
+}
+
+fn write_record(buf: &mut Vec<u8>, device: u8, kind: u8, payload: &[u8]) {
+    buf.push(device);
+    buf.push(kind);
+    buf.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    buf.extend_from_slice(payload);
+}
+
+fn guest_log(
+    mut caller: Caller<'_, ()>,
+    system_out: &Sender<SystemMessage>,
+    level: LogLevel,
+    str_pointer: i32,
+    str_len: i32,
+) {
+    let memory = caller
+        .get_export("memory")
+        .and_then(|export| export.into_memory())
+        .expect("Failed to find memory");
+
+    let mut buffer = vec![0u8; str_len as usize];
+    memory
+        .read(&caller, str_pointer as usize, &mut buffer)
+        .expect("Failed to read memory");
+
+    let received_string = String::from_utf8_lossy(&buffer).to_string();
+
+    log::debug!("[WASM] {received_string}");
+
+    system_out
+        .send(SystemMessage::Log(LogEntry::new(
+            LogSource::Wasm,
+            level,
+            received_string,
+        )))
+        .expect("Failed to send log message");
+}
 
 pub struct TickEngine {
     timer_start: Instant,
@@ -43,6 +174,7 @@ pub struct TickEngine {
     wasm: Option<WasmEngine>,
     midi_out: Sender<MidiEvent>,
     system_out: Sender<SystemMessage>,
+    patch: Option<PathBuf>,
 }
 
 pub struct WasmEngine {
@@ -53,8 +185,16 @@ pub struct WasmEngine {
 
 const DMX_LEN: usize = 513;
 
+const MIDI_ARRAY_OFFSET: usize = 0x80000;
+// The data array begins right after the MIDI array.
+const MIDI_ARRAY_MAX_LEN: usize = 0x10000;
+
 impl TickEngine {
-    pub fn create(midi_out: Sender<MidiEvent>, system_out: Sender<SystemMessage>) -> Result<Self> {
+    pub fn create(
+        midi_out: Sender<MidiEvent>,
+        system_out: Sender<SystemMessage>,
+        patch: Option<PathBuf>,
+    ) -> Result<Self> {
         let mut engine = TickEngine {
             timer_start: Instant::now(),
             data: vec![0; 1000],
@@ -62,6 +202,7 @@ impl TickEngine {
             wasm: None,
             midi_out,
             system_out,
+            patch,
         };
 
         engine.init_wasm()?;
@@ -118,36 +259,33 @@ impl TickEngine {
                 socket
                     .send_to(&body_buffer, target_addr.clone())
                     .unwrap_or_else(|e| {
-                        so.send(SystemMessage::Log(format!(
-                            "UDP error: SEND to {target_addr}: {e}"
-                        )))
+                        so.send(SystemMessage::log(
+                            LogLevel::Warn,
+                            format!("UDP error: SEND to {target_addr}: {e}"),
+                        ))
                         .expect("Failed to send log message");
                         0
                     });
             },
         )?;
 
+        // Plain `log` is kept for guests built before log levels existed.
         let so = self.system_out.clone();
         linker.func_wrap(
             "blaulicht",
             "log",
-            move |mut caller: Caller<'_, ()>, str_pointer: i32, str_len: i32| {
-                let memory = caller
-                    .get_export("memory")
-                    .and_then(|export| export.into_memory())
-                    .expect("Failed to find memory");
-
-                let mut buffer = vec![0u8; str_len as usize];
-                memory
-                    .read(&caller, str_pointer as usize, &mut buffer)
-                    .expect("Failed to read memory");
-
-                let received_string = String::from_utf8_lossy(&buffer).to_string();
-
-                log::debug!("[WASM] {received_string}");
+            move |caller: Caller<'_, ()>, str_pointer: i32, str_len: i32| {
+                guest_log(caller, &so, LogLevel::Info, str_pointer, str_len);
+            },
+        )?;
 
-                so.send(SystemMessage::WasmLog(received_string))
-                    .expect("Failed to send log message");
+        let so = self.system_out.clone();
+        linker.func_wrap(
+            "blaulicht",
+            "log_level",
+            move |caller: Caller<'_, ()>, level: i32, str_pointer: i32, str_len: i32| {
+                let level = LogLevel::from_guest(level);
+                guest_log(caller, &so, level, str_pointer, str_len);
             },
         )?;
 
@@ -205,6 +343,90 @@ impl TickEngine {
             },
         )?;
 
+        //
+        // Fixture patch, read again on every reload.
+        // The guest asks for the length first and then for the JSON itself.
+        //
+
+        let patch = match &self.patch {
+            Some(path) => match patch::read_patch(path) {
+                Ok(patch) => patch,
+                Err(err) => {
+                    self.system_out
+                        .send(SystemMessage::log(
+                            LogLevel::Error,
+                            format!("[WASM] Not passing the patch to the guest: {err:#}"),
+                        ))
+                        .expect("Failed to send log message");
+                    vec![]
+                }
+            },
+            None => vec![],
+        };
+
+        let patch_len = patch.len() as i32;
+        linker.func_wrap("blaulicht", "patch_len", move || patch_len)?;
+
+        linker.func_wrap(
+            "blaulicht",
+            "patch_read",
+            move |mut caller: Caller<'_, ()>, pointer: i32, len: i32| {
+                let memory = caller
+                    .get_export("memory")
+                    .and_then(|export| export.into_memory())
+                    .expect("Failed to find memory");
+
+                let len = (len.max(0) as usize).min(patch.len());
+                memory
+                    .write(&mut caller, pointer as usize, &patch[..len])
+                    .expect("Failed to write memory");
+            },
+        )?;
+
+        //
+        // Cues recorded by the guest, written to the cue file of the patch.
+        //
+
+        let cue_file = match &self.patch {
+            Some(path) => patch::cue_file(path).unwrap_or_else(|err| {
+                log::warn!("[WASM] Not resolving the cue file: {err:#}");
+                None
+            }),
+            None => None,
+        };
+
+        let so = self.system_out.clone();
+        linker.func_wrap(
+            "blaulicht",
+            "cues_store",
+            move |mut caller: Caller<'_, ()>, pointer: i32, len: i32| {
+                let memory = caller
+                    .get_export("memory")
+                    .and_then(|export| export.into_memory())
+                    .expect("Failed to find memory");
+
+                let mut buffer = vec![0u8; len as usize];
+                memory
+                    .read(&caller, pointer as usize, &mut buffer)
+                    .expect("Failed to read memory");
+
+                let result = match &cue_file {
+                    Some(path) => patch::write_cues(path, &buffer)
+                        .map(|_| format!("[WASM] Stored cues in <{}>", path.display())),
+                    None => Err(anyhow::anyhow!("the patch has no `cue_file`")),
+                };
+
+                let message = match result {
+                    Ok(message) => SystemMessage::log(LogLevel::Info, message),
+                    Err(err) => SystemMessage::log(
+                        LogLevel::Error,
+                        format!("[WASM] Not storing cues: {err:#}"),
+                    ),
+                };
+                so.send(message).expect("Failed to send log message");
+            },
+        )?;
+
         let mo = self.midi_out.clone();
         linker.func_wrap(
             "blaulicht",
@@ -212,9 +434,11 @@ impl TickEngine {
             move |device: i32, status: i32, kind: i32, value: i32| {
                 mo.send(MidiEvent {
                     device: device as u8,
-                    status: status as u8,
-                    data0: kind as u8,
-                    data1: value as u8,
+                    message: MidiMessage::Raw {
+                        status: status as u8,
+                        data0: kind as u8,
+                        data1: value as u8,
+                    },
                 })
                 .unwrap();
             },
@@ -264,8 +488,10 @@ impl TickEngine {
                 bass_avg: 0,
                 bpm: 0,
                 time_between_beats_millis: 0,
+                beat_phase: None,
             },
             &[],
+            &[],
             true,
         )
     }
@@ -274,6 +500,7 @@ impl TickEngine {
         &mut self,
         input: TickInput,
         midi_events: &[MidiEvent],
+        controls: &[ControlEvent],
         initial: bool,
     ) -> Result<()> {
         let wasm = self.wasm.as_mut().unwrap();
@@ -306,30 +533,36 @@ impl TickEngine {
         //
         // MIDI array.
         //
-        let midi_array_offset = 0x80000; // TODO: make this offset a const.
-        let midi_array_len = midi_events.len() as i32;
+        let mut midi_array_bytes = Vec::new();
+        let mut dropped_midi_events = 0;
+
+        for event in midi_events {
+            let record_start = midi_array_bytes.len();
+            event.serialize(&mut midi_array_bytes);
 
-        if midi_array_len > 100 {
-            panic!("TOO many MIDI events!");
+            if midi_array_bytes.len() > MIDI_ARRAY_MAX_LEN {
+                midi_array_bytes.truncate(record_start);
+                dropped_midi_events += 1;
+            }
         }
 
-        let mut midi_array_bytes = Vec::new();
+        for control in controls {
+            let record_start = midi_array_bytes.len();
+            control.serialize(&mut midi_array_bytes);
+
+            if midi_array_bytes.len() > MIDI_ARRAY_MAX_LEN {
+                midi_array_bytes.truncate(record_start);
+                dropped_midi_events += 1;
+            }
+        }
 
-        let midi_events_packed: Vec<u32> = midi_events
-            .iter()
-            .map(|event| {
-                ((event.device as u32) << 24)
-                    | (event.status as u32) << 16
-                    | (event.data0 as u32) << 8
-                    | (event.data1 as u32)
-            })
-            .collect::<Vec<u32>>();
-
-        for &num in &midi_events_packed {
-            midi_array_bytes.extend_from_slice(&num.to_le_bytes());
+        if dropped_midi_events > 0 {
+            log::warn!("[WASM] MIDI array is full, dropped {dropped_midi_events} event(s)");
         }
+
+        let midi_array_len = midi_array_bytes.len() as i32;
         wasm.memory
-            .write(&mut wasm.store, midi_array_offset, &midi_array_bytes)?;
+            .write(&mut wasm.store, MIDI_ARRAY_OFFSET, &midi_array_bytes)?;
 
         // for &num in &self.dmx {
         //     dmx_array_bytes.extend_from_slice(&num.to_le_bytes());
@@ -361,7 +594,7 @@ impl TickEngine {
                 DMX_LEN as i32,
                 data_array_offset,
                 data_array_len as i32,
-                midi_array_offset as i32,
+                MIDI_ARRAY_OFFSET as i32,
                 midi_array_len,
             ),
         )?;
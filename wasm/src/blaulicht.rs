@@ -2,6 +2,7 @@
 #[link(wasm_import_module = "blaulicht")]
 extern "C" {
     fn log(ptr: *const u8, len: usize);
+    fn log_level(level: u8, ptr: *const u8, len: usize);
     fn udp(
         target_addr_ptr: *const u8,
         target_addr_len: usize,
@@ -12,6 +13,9 @@ extern "C" {
     fn controls_log(x: u8, y: u8, ptr: *const u8, len: usize);
     fn controls_set(x: u8, y: u8, value: bool);
     fn controls_config(x: u8, y: u8);
+    fn patch_len() -> usize;
+    fn patch_read(ptr: *mut u8, len: usize);
+    fn cues_store(ptr: *const u8, len: usize);
 }
 
 pub fn bl_midi_safe(device: u8, status: u8, data0: u8, data1: u8) {
@@ -23,6 +27,38 @@ pub fn bl_log(msg: &str) {
     unsafe { log(msg.as_ptr(), msg.len()) }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum LogLevel {
+    Trace = 0,
+    Debug = 1,
+    Info = 2,
+    Warn = 3,
+    Error = 4,
+}
+
+/// Log a string with a level, `bl_log` logs as info.
+pub fn bl_log_level(level: LogLevel, msg: &str) {
+    unsafe { log_level(level as u8, msg.as_ptr(), msg.len()) }
+}
+
+/// Fixture patch of the host as JSON, `None` if the host has none.
+pub fn bl_patch() -> Option<Vec<u8>> {
+    let len = unsafe { patch_len() };
+    if len == 0 {
+        return None;
+    }
+
+    let mut buf = vec![0; len];
+    unsafe { patch_read(buf.as_mut_ptr(), len) };
+    Some(buf)
+}
+
+/// Saves scenes and cue lists as JSON to the cue file of the host patch.
+pub fn bl_cues_store(json: &[u8]) {
+    unsafe { cues_store(json.as_ptr(), json.len()) }
+}
+
 pub fn bl_udp(addr: &str, body: &[u8]) {
     unsafe { udp(addr.as_ptr(), addr.len(), body.as_ptr(), body.len()) }
 }
@@ -55,6 +91,17 @@ pub unsafe fn _get_array(array_pointer: *mut u8, array_length: usize) -> &'stati
     slice
 }
 
+#[doc(hidden)]
+pub unsafe fn _get_bytes(array_pointer: *const u8, array_length: usize) -> &'static [u8] {
+    // Safety: This is unsafe because we're dealing with raw pointers.
+    let slice = unsafe {
+        assert!(!array_pointer.is_null(), "Pointer is null");
+        std::slice::from_raw_parts(array_pointer, array_length)
+    };
+
+    slice
+}
+
 #[doc(hidden)]
 pub unsafe fn _get_array_u32(array_pointer: *const u32, array_length: usize) -> &'static [u32] {
     // Safety: This is unsafe because we're dealing with raw pointers.
@@ -76,6 +123,8 @@ pub struct TickInput {
     pub bass_avg: u8,
     pub bpm: u8,
     pub time_between_beats_millis: u16,
+    // Position inside the current beat (0-255), only known while following a MIDI clock.
+    pub beat_phase: Option<u8>,
     pub initial: bool,
 }
 
@@ -94,28 +143,65 @@ impl From<u8> for MidiDevice {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum MidiMessage {
+    // Passed through undecoded, used by the builtin control matrix.
+    Raw { status: u8, data0: u8, data1: u8 },
+    NoteOff { channel: u8, note: u8, velocity: u8 },
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    PolyPressure { channel: u8, note: u8, pressure: u8 },
+    ControlChange { channel: u8, control: u8, value: u8 },
+    ProgramChange { channel: u8, program: u8 },
+    ChannelPressure { channel: u8, pressure: u8 },
+    PitchBend { channel: u8, value: u16 },
+    // MSB (CC 0-31) and LSB (CC 32-63) pair combined into one value.
+    ControlChange14 { channel: u8, control: u8, value: u16 },
+    Nrpn { channel: u8, parameter: u16, value: u16 },
+    // Complete message including the 0xF0 / 0xF7 framing.
+    SysEx(Vec<u8>),
+    // System realtime messages used for clock and transport sync.
+    Clock,
+    Start,
+    Continue,
+    Stop,
+}
+
+#[derive(Debug, Clone)]
 pub struct MidiEvent {
     pub device: MidiDevice,
-    pub status: u8,
-    pub kind: u8,
-    pub value: u8,
+    pub message: MidiMessage,
+}
+
+/// Named control produced by the host MIDI mapping, e.g. `strobe.brightness`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlEvent {
+    pub name: String,
+    pub value: ControlValue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlValue {
+    Bool(bool),
+    // Faders, scaled into the configured range.
+    Float(f32),
+    // Encoder steps.
+    Relative(i32),
 }
 
 #[macro_export]
 macro_rules! midi {
     ($status: expr, $kind: expr) => {
-        MidiEvent {
+        MidiMessage::Raw {
             status: $status,
-            kind: $kind,
-            value: 0,
+            data0: $kind,
+            data1: 0,
         }
     };
     ($status: expr, $kind: expr, $value: expr) => {
-        MidiEvent {
+        MidiMessage::Raw {
             status: $status,
-            kind: $kind,
-            value: $value,
+            data0: $kind,
+            data1: $value,
         }
     };
 }
@@ -123,8 +209,54 @@ macro_rules! midi {
 pub use midi;
 
 impl MidiEvent {
+    //
+    // 7-bit view on the event, as it would appear in a 3-byte short message.
+    // 14-bit values are reduced to their MSB.
+    //
+
     pub fn tup(&self) -> (u8, u8) {
-        (self.status, self.kind)
+        match self.message {
+            MidiMessage::Raw { status, data0, .. } => (status, data0),
+            MidiMessage::NoteOff { channel, note, .. } => (0x80 | channel, note),
+            MidiMessage::NoteOn { channel, note, .. } => (0x90 | channel, note),
+            MidiMessage::PolyPressure { channel, note, .. } => (0xA0 | channel, note),
+            MidiMessage::ControlChange {
+                channel, control, ..
+            }
+            | MidiMessage::ControlChange14 {
+                channel, control, ..
+            } => (0xB0 | channel, control),
+            MidiMessage::ProgramChange { channel, program } => (0xC0 | channel, program),
+            MidiMessage::ChannelPressure { channel, pressure } => (0xD0 | channel, pressure),
+            MidiMessage::PitchBend { channel, .. } => (0xE0 | channel, 0),
+            MidiMessage::Nrpn { channel, .. } => (0xB0 | channel, 99),
+            MidiMessage::SysEx(_) => (0xF0, 0),
+            MidiMessage::Clock => (0xF8, 0),
+            MidiMessage::Start => (0xFA, 0),
+            MidiMessage::Continue => (0xFB, 0),
+            MidiMessage::Stop => (0xFC, 0),
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match self.message {
+            MidiMessage::Raw { data1, .. } => data1,
+            MidiMessage::NoteOff { velocity, .. } | MidiMessage::NoteOn { velocity, .. } => {
+                velocity
+            }
+            MidiMessage::PolyPressure { pressure, .. } => pressure,
+            MidiMessage::ControlChange { value, .. } => value,
+            MidiMessage::PitchBend { value, .. }
+            | MidiMessage::ControlChange14 { value, .. }
+            | MidiMessage::Nrpn { value, .. } => (value >> 7) as u8,
+            MidiMessage::ProgramChange { .. }
+            | MidiMessage::ChannelPressure { .. }
+            | MidiMessage::SysEx(_)
+            | MidiMessage::Clock
+            | MidiMessage::Start
+            | MidiMessage::Continue
+            | MidiMessage::Stop => 0,
+        }
     }
 }
 
@@ -166,6 +298,35 @@ pub mod prelude {
         }
     pub use printc;
 
+    // Leveled logging, e.g. `crate::log_warn!("...")`. `println!` logs as info.
+    #[macro_export]
+    macro_rules! log_debug {
+        ($($arg:tt)*) => {{
+            $crate::blaulicht::bl_log_level($crate::blaulicht::LogLevel::Debug, &format!($($arg)*));
+        }};
+    }
+
+    #[macro_export]
+    macro_rules! log_info {
+        ($($arg:tt)*) => {{
+            $crate::blaulicht::bl_log_level($crate::blaulicht::LogLevel::Info, &format!($($arg)*));
+        }};
+    }
+
+    #[macro_export]
+    macro_rules! log_warn {
+        ($($arg:tt)*) => {{
+            $crate::blaulicht::bl_log_level($crate::blaulicht::LogLevel::Warn, &format!($($arg)*));
+        }};
+    }
+
+    #[macro_export]
+    macro_rules! log_error {
+        ($($arg:tt)*) => {{
+            $crate::blaulicht::bl_log_level($crate::blaulicht::LogLevel::Error, &format!($($arg)*));
+        }};
+    }
+
     #[macro_export]
     macro_rules! print {
         ($($arg:tt)*) => {
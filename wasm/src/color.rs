@@ -1,6 +1,6 @@
 use serde::Deserialize;
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -33,40 +33,175 @@ impl From<(u8, u8, u8)> for Color {
     }
 }
 
-/// Uses input ranging from 0 to 360.
-pub fn hsv_to_rgb(h: isize) -> Color {
-    // Catch white.
-    if h == -1 {
-        return (255, 255, 255).into();
-    }
-
-    // Normalize hue to [0, 360)
-    // let mut h = h % 360;
-    // if h < 0 {
-    //     h += 360;
-    // }
-
-    let s = 1.0f32;
-    let v = 1.0f32;
-    let c = v * s;
-    let h_prime = h as f32 / 60.0;
-    let x = c * (1.0 - ((h_prime % 2.0) - 1.0).abs());
-    let m = v - c;
-
-    let (rf, gf, bf) = match h {
-        0..=59 => (c, x, 0.0),
-        60..=119 => (x, c, 0.0),
-        120..=179 => (0.0, c, x),
-        180..=239 => (0.0, x, c),
-        240..=299 => (x, 0.0, c),
-        _ => (c, 0.0, x),
-    };
-
-    let r = ((rf + m) * 255.0).round() as u8;
-    let g = ((gf + m) * 255.0).round() as u8;
-    let b = ((bf + m) * 255.0).round() as u8;
-
-    (r, g, b).into()
+//
+// Color spaces.
+// Hues are in degrees, all other components range from 0.0 to 1.0.
+//
+
+impl Color {
+    fn from_unit(r: f32, g: f32, b: f32) -> Self {
+        let to_u8 = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        (to_u8(r), to_u8(g), to_u8(b)).into()
+    }
+
+    fn unit(&self) -> (f32, f32, f32) {
+        (
+            self.r as f32 / 255.0,
+            self.g as f32 / 255.0,
+            self.b as f32 / 255.0,
+        )
+    }
+
+    // Shared by HSV and HSL, `c` is the chroma and `m` the offset of all channels.
+    fn from_hue(h: f32, c: f32, m: f32) -> Self {
+        let h = h.rem_euclid(360.0) / 60.0;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+
+        let (r, g, b) = match h as u8 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+
+        Self::from_unit(r + m, g + m, b + m)
+    }
+
+    pub fn from_hsv(h: f32, s: f32, v: f32) -> Self {
+        let c = v * s;
+        Self::from_hue(h, c, v - c)
+    }
+
+    pub fn from_hsl(h: f32, s: f32, l: f32) -> Self {
+        let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        Self::from_hue(h, c, l - c / 2.0)
+    }
+
+    /// Hue, saturation and value.
+    pub fn to_hsv(&self) -> (f32, f32, f32) {
+        let (r, g, b) = self.unit();
+        let max = r.max(g).max(b);
+        let delta = max - r.min(g).min(b);
+
+        let h = match delta {
+            d if d == 0.0 => 0.0,
+            _ if max == r => 60.0 * ((g - b) / delta).rem_euclid(6.0),
+            _ if max == g => 60.0 * ((b - r) / delta + 2.0),
+            _ => 60.0 * ((r - g) / delta + 4.0),
+        };
+        let s = if max == 0.0 { 0.0 } else { delta / max };
+
+        (h, s, max)
+    }
+
+    /// Color of a black body, approximated for 1000K to 40000K.
+    pub fn from_kelvin(kelvin: f32) -> Self {
+        let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+
+        let r = match t {
+            t if t <= 66.0 => 255.0,
+            t => 329.698_73 * (t - 60.0).powf(-0.133_204_76),
+        };
+        let g = match t {
+            t if t <= 66.0 => 99.470_8 * t.ln() - 161.119_57,
+            t => 288.122_16 * (t - 60.0).powf(-0.075_514_85),
+        };
+        let b = match t {
+            t if t >= 66.0 => 255.0,
+            t if t <= 19.0 => 0.0,
+            t => 138.517_73 * (t - 10.0).ln() - 305.044_8,
+        };
+
+        Self::from_unit(r / 255.0, g / 255.0, b / 255.0)
+    }
+
+    /// Straight RGB fade, `t` ranges from 0.0 (`self`) to 1.0 (`other`).
+    pub fn lerp(&self, other: Color, t: f32) -> Self {
+        let (r0, g0, b0) = self.unit();
+        let (r1, g1, b1) = other.unit();
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        Self::from_unit(lerp(r0, r1), lerp(g0, g1), lerp(b0, b1))
+    }
+}
+
+//
+// Emitters.
+// Maps RGB onto the LEDs of a fixture, the white and amber parts are taken out of the RGB mix.
+//
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Emitters {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub white: u8,
+    pub amber: u8,
+    pub uv: u8,
+}
+
+/// Per fixture correction, so that the same color looks alike on different fixtures.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct ColorCalibration {
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+    pub white: f32,
+    pub amber: f32,
+    /// Share of the blue part driving the UV emitter, off by default.
+    pub uv: f32,
+    pub gamma: f32,
+}
+
+impl Default for ColorCalibration {
+    fn default() -> Self {
+        Self {
+            red: 1.0,
+            green: 1.0,
+            blue: 1.0,
+            white: 1.0,
+            amber: 1.0,
+            uv: 0.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl ColorCalibration {
+    pub fn emitters(&self, color: Color, white: bool, amber: bool) -> Emitters {
+        let (mut r, mut g, mut b) = color.unit();
+
+        let mut w = 0.0;
+        if white {
+            w = r.min(g).min(b);
+            r -= w;
+            g -= w;
+            b -= w;
+        }
+
+        // Amber is roughly (255, 128, 0).
+        let mut a = 0.0;
+        if amber {
+            a = r.min(g * 2.0);
+            r -= a;
+            g -= a / 2.0;
+        }
+
+        let output = |value: f32, gain: f32| {
+            ((value * gain).clamp(0.0, 1.0).powf(self.gamma) * 255.0).round() as u8
+        };
+
+        Emitters {
+            r: output(r, self.red),
+            g: output(g, self.green),
+            b: output(b, self.blue),
+            white: output(w, self.white),
+            amber: output(a, self.amber),
+            uv: output(b, self.uv),
+        }
+    }
 }
 
 // #[macro_export]
@@ -79,3 +214,64 @@ pub fn hsv_to_rgb(h: isize) -> Color {
 // }
 
 // pub use colorize;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hsv_and_hsl() {
+        assert_eq!(Color::from_hsv(0.0, 1.0, 1.0), (255, 0, 0).into());
+        assert_eq!(Color::from_hsv(480.0, 1.0, 1.0), (0, 255, 0).into());
+        assert_eq!(Color::from_hsv(240.0, 0.5, 1.0), (128, 128, 255).into());
+        assert_eq!(Color::from_hsl(240.0, 1.0, 0.5), (0, 0, 255).into());
+        assert_eq!(Color::from_hsl(0.0, 0.0, 1.0), Color::white());
+
+        let (h, s, v) = Color::from((255, 128, 0)).to_hsv();
+        assert!((h - 30.1).abs() < 0.1);
+        assert_eq!((s, v), (1.0, 1.0));
+    }
+
+    #[test]
+    fn kelvin_is_warm_to_cold() {
+        let warm = Color::from_kelvin(2700.0);
+        let cold = Color::from_kelvin(10000.0);
+
+        assert_eq!(warm.r, 255);
+        assert!(warm.b < warm.g);
+        assert_eq!(cold.b, 255);
+        assert!(cold.r < 255);
+    }
+
+    #[test]
+    fn emitters_take_white_and_amber_out_of_the_mix() {
+        let calibration = ColorCalibration::default();
+        let color = (255, 200, 100).into();
+
+        let rgb = calibration.emitters(color, false, false);
+        assert_eq!((rgb.r, rgb.g, rgb.b, rgb.white), (255, 200, 100, 0));
+
+        let rgbw = calibration.emitters(color, true, false);
+        assert_eq!((rgbw.r, rgbw.g, rgbw.b, rgbw.white), (155, 100, 0, 100));
+
+        let rgbaw = calibration.emitters(color, true, true);
+        assert_eq!(
+            (rgbaw.r, rgbaw.g, rgbaw.amber, rgbaw.white),
+            (0, 22, 155, 100)
+        );
+    }
+
+    #[test]
+    fn calibration_scales_and_corrects_the_emitters() {
+        let calibration = ColorCalibration {
+            green: 0.5,
+            uv: 1.0,
+            gamma: 2.0,
+            ..ColorCalibration::default()
+        };
+
+        let emitters = calibration.emitters((255, 255, 128).into(), false, false);
+        assert_eq!((emitters.r, emitters.g, emitters.b), (255, 64, 64));
+        assert_eq!(emitters.uv, 64);
+    }
+}
@@ -0,0 +1,258 @@
+use serde::Deserialize;
+
+//
+// Waveform effects, layered onto the attributes of the fixtures of a group.
+// Effects only compute a modulation per fixture, the base state of the fixture is not changed.
+//
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Sine,
+    Square,
+    /// Rises over the cycle, then drops.
+    Saw,
+    /// Rises over the first half of the cycle and falls over the second.
+    Triangle,
+    /// A new random value every cycle.
+    Random,
+    /// On for 1/n of the cycle in a group of n fixtures, use with a spread of 1.0.
+    Chase,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+    Intensity,
+    /// Hue shift in degrees.
+    Hue,
+    /// Pan offset in degrees.
+    Pan,
+    /// Tilt offset in degrees.
+    Tilt,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Rate {
+    /// Cycles per second.
+    Hz(f32),
+    /// Beats per cycle, e.g. 0.5 for two cycles per beat or 16.0 for one cycle every four bars.
+    Beats(f32),
+}
+
+fn size_default() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Effect {
+    pub attribute: Attribute,
+    pub waveform: Waveform,
+    pub rate: Rate,
+    /// Phase difference from the first to the last fixture of the group in cycles,
+    /// 1.0 spreads the fixtures evenly across one cycle.
+    #[serde(default)]
+    pub spread: f32,
+    /// Intensity effects scale between `offset` and `offset + size`,
+    /// all other effects swing `size` degrees around `offset`.
+    #[serde(default = "size_default")]
+    pub size: f32,
+    #[serde(default)]
+    pub offset: f32,
+}
+
+/// Timing of the current tick.
+#[derive(Debug, Clone, Copy)]
+pub struct EffectClock {
+    pub millis: i32,
+    /// Beats since the start, including the position inside the current beat.
+    pub beats: f32,
+}
+
+/// All effects of a group on one fixture, combined.
+#[derive(Debug, Clone, Copy)]
+pub struct Modulation {
+    /// Multiplies the alpha.
+    pub intensity: f32,
+    pub hue: f32,
+    pub pan: f32,
+    pub tilt: f32,
+}
+
+impl Default for Modulation {
+    fn default() -> Self {
+        Self {
+            intensity: 1.0,
+            hue: 0.0,
+            pan: 0.0,
+            tilt: 0.0,
+        }
+    }
+}
+
+impl Waveform {
+    /// Output from 0.0 to 1.0, `position` counts cycles.
+    pub fn sample(&self, position: f32, index: usize, count: usize) -> f32 {
+        let phase = position.rem_euclid(1.0);
+
+        match self {
+            Waveform::Sine => 0.5 - 0.5 * (phase * std::f32::consts::TAU).cos(),
+            Waveform::Square => (phase < 0.5) as u8 as f32,
+            Waveform::Saw => phase,
+            Waveform::Triangle => 1.0 - (2.0 * phase - 1.0).abs(),
+            Waveform::Random => random(position.floor() as i64, index),
+            Waveform::Chase => (phase < 1.0 / count.max(1) as f32) as u8 as f32,
+        }
+    }
+}
+
+/// From 0.0 to 1.0, the same for the same cycle and index.
+pub fn random(cycle: i64, index: usize) -> f32 {
+    // SplitMix64.
+    let mut x = (cycle as u64) ^ (index as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^= x >> 31;
+
+    (x >> 40) as f32 / (1u64 << 24) as f32
+}
+
+impl Effect {
+    fn position(&self, clock: EffectClock, index: usize, count: usize) -> f32 {
+        let cycles = match self.rate {
+            Rate::Hz(hz) => clock.millis as f32 / 1000.0 * hz,
+            Rate::Beats(beats) if beats > 0.0 => clock.beats / beats,
+            Rate::Beats(_) => 0.0,
+        };
+
+        // Later fixtures lag behind.
+        cycles - self.spread * index as f32 / count.max(1) as f32
+    }
+
+    /// Layers the effect onto the modulation of the fixture at `index` of `count`.
+    pub fn apply(
+        &self,
+        clock: EffectClock,
+        index: usize,
+        count: usize,
+        modulation: &mut Modulation,
+    ) {
+        let sample = self
+            .waveform
+            .sample(self.position(clock, index, count), index, count);
+
+        match self.attribute {
+            Attribute::Intensity => {
+                modulation.intensity *= (self.offset + self.size * sample).clamp(0.0, 1.0);
+            }
+            Attribute::Hue => modulation.hue += self.offset + self.size * (sample - 0.5),
+            Attribute::Pan => modulation.pan += self.offset + self.size * (sample - 0.5),
+            Attribute::Tilt => modulation.tilt += self.offset + self.size * (sample - 0.5),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-4, "{actual:?} != {expected:?}");
+        }
+    }
+
+    fn samples(waveform: Waveform) -> Vec<f32> {
+        [0.0, 0.25, 0.5, 1.0]
+            .into_iter()
+            .map(|position| waveform.sample(position, 0, 4))
+            .collect()
+    }
+
+    fn effect(attribute: Attribute, waveform: Waveform, rate: Rate, spread: f32) -> Effect {
+        Effect {
+            attribute,
+            waveform,
+            rate,
+            spread,
+            size: 1.0,
+            offset: 0.0,
+        }
+    }
+
+    fn clock(millis: i32, beats: f32) -> EffectClock {
+        EffectClock { millis, beats }
+    }
+
+    #[test]
+    fn waveforms() {
+        assert_near(&samples(Waveform::Sine), &[0.0, 0.5, 1.0, 0.0]);
+        assert_near(&samples(Waveform::Square), &[1.0, 1.0, 0.0, 1.0]);
+        assert_near(&samples(Waveform::Saw), &[0.0, 0.25, 0.5, 0.0]);
+        assert_near(&samples(Waveform::Triangle), &[0.0, 0.5, 1.0, 0.0]);
+        // On for a quarter of the cycle in a group of four.
+        assert_near(&samples(Waveform::Chase), &[1.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn random_changes_per_cycle_and_fixture() {
+        let sample = |position, index| Waveform::Random.sample(position, index, 4);
+
+        assert_eq!(sample(3.1, 0), sample(3.9, 0));
+        assert_ne!(sample(3.1, 0), sample(4.1, 0));
+        assert_ne!(sample(3.1, 0), sample(3.1, 1));
+        assert!((0..100).all(|cycle| (0.0..1.0).contains(&sample(cycle as f32, 0))));
+    }
+
+    #[test]
+    fn spread_delays_later_fixtures() {
+        let saw = effect(Attribute::Intensity, Waveform::Saw, Rate::Beats(2.0), 1.0);
+
+        let positions: Vec<_> = (0..4)
+            .map(|index| saw.position(clock(0, 3.0), index, 4))
+            .collect();
+        assert_near(&positions, &[1.5, 1.25, 1.0, 0.75]);
+
+        let intensities: Vec<_> = (0..4)
+            .map(|index| {
+                let mut modulation = Modulation::default();
+                saw.apply(clock(0, 3.0), index, 4, &mut modulation);
+                modulation.intensity
+            })
+            .collect();
+        assert_near(&intensities, &[0.5, 0.25, 0.0, 0.75]);
+    }
+
+    #[test]
+    fn rates() {
+        let hz = effect(Attribute::Pan, Waveform::Saw, Rate::Hz(2.0), 0.0);
+        assert_near(&[hz.position(clock(1250, 0.0), 0, 1)], &[2.5]);
+
+        let stopped = effect(Attribute::Pan, Waveform::Saw, Rate::Beats(0.0), 0.0);
+        assert_near(&[stopped.position(clock(1250, 8.0), 0, 1)], &[0.0]);
+    }
+
+    #[test]
+    fn effects_layer() {
+        let mut modulation = Modulation::default();
+
+        let pan = Effect {
+            size: 90.0,
+            offset: 10.0,
+            ..effect(Attribute::Pan, Waveform::Square, Rate::Beats(1.0), 0.0)
+        };
+        let dim = Effect {
+            size: 0.5,
+            offset: 0.25,
+            ..effect(Attribute::Intensity, Waveform::Saw, Rate::Beats(1.0), 0.0)
+        };
+        pan.apply(clock(0, 0.5), 0, 1, &mut modulation);
+        pan.apply(clock(0, 0.0), 0, 1, &mut modulation);
+        dim.apply(clock(0, 0.5), 0, 1, &mut modulation);
+        dim.apply(clock(0, 0.5), 0, 1, &mut modulation);
+
+        // Pan swings 45 degrees around the offset, intensities multiply.
+        assert_near(&[modulation.pan, modulation.intensity], &[20.0, 0.25]);
+        assert_near(&[modulation.hue, modulation.tilt], &[0.0, 0.0]);
+    }
+}
@@ -2,30 +2,147 @@ mod blaulicht;
 mod ui;
 mod user;
 mod color;
+mod effect;
 
-fn decode_midi(midi: &[u32]) -> Vec<blaulicht::MidiEvent> {
-    let res: Vec<blaulicht::MidiEvent> = midi
-        .iter()
-        .map(|word| {
-            let data1 = (word & 0x000000FF) as u8;
-            let data0 = ((word & 0x0000FF00) >> 8) as u8;
-            let status = ((word & 0x00FF0000) >> 16) as u8;
-            let device = ((word & 0xFF000000) >> 24) as u8;
-
-            blaulicht::MidiEvent {
-                device: device.into(),
-                status,
-                kind: data0,
-                value: data1,
+//
+// MIDI events are a stream of records:
+// [device: u8, kind: u8, payload length: u16 (LE), payload...]
+// The kinds must be kept in sync with `MidiEvent::serialize` on the host.
+// Control events from the host MIDI mapping use the kind 15.
+//
+
+const CONTROL_EVENT_KIND: u8 = 15;
+
+fn decode_midi(midi: &[u8]) -> (Vec<blaulicht::MidiEvent>, Vec<blaulicht::ControlEvent>) {
+    use blaulicht::MidiMessage;
+
+    let mut res = vec![];
+    let mut controls = vec![];
+    let mut rest = midi;
+
+    while rest.len() >= 4 {
+        let device = rest[0];
+        let kind = rest[1];
+        let len = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+
+        if rest.len() < 4 + len {
+            blaulicht::bl_log_level(
+                blaulicht::LogLevel::Warn,
+                &format!("[MIDI] truncated event of kind {kind}"),
+            );
+            break;
+        }
+
+        let p = &rest[4..4 + len];
+        rest = &rest[4 + len..];
+
+        let u16_at = |i: usize| u16::from_le_bytes([p[i], p[i + 1]]);
+
+        if kind == CONTROL_EVENT_KIND {
+            match decode_control(p) {
+                Some(control) => controls.push(control),
+                None => blaulicht::bl_log_level(
+                    blaulicht::LogLevel::Warn,
+                    &format!("[MIDI] malformed control event (len {len})"),
+                ),
             }
-        })
-        .collect();
+            continue;
+        }
+
+        let message = match (kind, len) {
+            (0, 3) => MidiMessage::Raw {
+                status: p[0],
+                data0: p[1],
+                data1: p[2],
+            },
+            (1, 3) => MidiMessage::NoteOff {
+                channel: p[0],
+                note: p[1],
+                velocity: p[2],
+            },
+            (2, 3) => MidiMessage::NoteOn {
+                channel: p[0],
+                note: p[1],
+                velocity: p[2],
+            },
+            (3, 3) => MidiMessage::PolyPressure {
+                channel: p[0],
+                note: p[1],
+                pressure: p[2],
+            },
+            (4, 3) => MidiMessage::ControlChange {
+                channel: p[0],
+                control: p[1],
+                value: p[2],
+            },
+            (5, 2) => MidiMessage::ProgramChange {
+                channel: p[0],
+                program: p[1],
+            },
+            (6, 2) => MidiMessage::ChannelPressure {
+                channel: p[0],
+                pressure: p[1],
+            },
+            (7, 3) => MidiMessage::PitchBend {
+                channel: p[0],
+                value: u16_at(1),
+            },
+            (8, 4) => MidiMessage::ControlChange14 {
+                channel: p[0],
+                control: p[1],
+                value: u16_at(2),
+            },
+            (9, 5) => MidiMessage::Nrpn {
+                channel: p[0],
+                parameter: u16_at(1),
+                value: u16_at(3),
+            },
+            (10, _) => MidiMessage::SysEx(p.to_vec()),
+            (11, 0) => MidiMessage::Clock,
+            (12, 0) => MidiMessage::Start,
+            (13, 0) => MidiMessage::Continue,
+            (14, 0) => MidiMessage::Stop,
+            (kind, len) => {
+                blaulicht::bl_log_level(
+                    blaulicht::LogLevel::Warn,
+                    &format!("[MIDI] unknown event kind {kind} (len {len})"),
+                );
+                continue;
+            }
+        };
+
+        res.push(blaulicht::MidiEvent {
+            device: device.into(),
+            message,
+        });
+    }
+
+    (res, controls)
+}
+
+fn decode_control(p: &[u8]) -> Option<blaulicht::ControlEvent> {
+    use blaulicht::ControlValue;
+
+    if p.len() < 5 {
+        return None;
+    }
+
+    let raw = [p[1], p[2], p[3], p[4]];
+    let value = match p[0] {
+        0 => ControlValue::Bool(u32::from_le_bytes(raw) != 0),
+        1 => ControlValue::Float(f32::from_le_bytes(raw)),
+        2 => ControlValue::Relative(i32::from_le_bytes(raw)),
+        _ => return None,
+    };
 
-    res
+    Some(blaulicht::ControlEvent {
+        name: String::from_utf8_lossy(&p[5..]).to_string(),
+        value,
+    })
 }
 
 fn tickinput_from_array(arr: &[u32]) -> blaulicht::TickInput {
-    const ARRAY_LEN: usize = 9;
+    const ARRAY_LEN: usize = 10;
     if arr.len() != ARRAY_LEN {
         panic!(
             "tick array len in 'tickinput_from_array' is not expected length: {}",
@@ -42,7 +159,11 @@ fn tickinput_from_array(arr: &[u32]) -> blaulicht::TickInput {
         bass_avg: arr[5] as u8,
         bpm: arr[6] as u8,
         time_between_beats_millis: arr[7] as u16,
-        initial: arr[8] != 0,
+        beat_phase: match arr[8] as i32 {
+            -1 => None,
+            phase => Some(phase as u8),
+        },
+        initial: arr[9] != 0,
     }
 }
 
@@ -58,25 +179,28 @@ pub extern "C" fn internal_tick(
     data_array: *mut u8,
     _data_length: usize,
     // Midi array.
-    midi_array: *const u32,
+    midi_array: *const u8,
     midi_len: usize,
 ) {
     let tick_array = unsafe { blaulicht::_get_array_u32(tick_input_array, tick_input_length) };
     let dmx_array = unsafe { blaulicht::_get_array(dmx_array, dmx_array_length) };
-    let midi_array = unsafe { blaulicht::_get_array_u32(midi_array, midi_len) };
+    let midi_array = unsafe { blaulicht::_get_bytes(midi_array, midi_len) };
 
     // Run user code
     let tick_input = tickinput_from_array(tick_array);
-    let midi_inputs = decode_midi(midi_array);
+    let (midi_inputs, controls) = decode_midi(midi_array);
 
     match tick_input.initial {
         true => {
             std::panic::set_hook(Box::new(|info| {
-                blaulicht::bl_log(&format!("***PANIC***: {}", info.to_string()));
+                blaulicht::bl_log_level(
+                    blaulicht::LogLevel::Error,
+                    &format!("***PANIC***: {}", info.to_string()),
+                );
             }));
 
             user::initialize(tick_input, dmx_array, data_array)
         }
-        false => user::run(tick_input, dmx_array, data_array, &midi_inputs),
+        false => user::run(tick_input, dmx_array, data_array, &midi_inputs, &controls),
     };
 }
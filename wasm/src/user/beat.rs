@@ -1,14 +1,262 @@
+use std::collections::VecDeque;
+
+use serde::Deserialize;
+
 use crate::blaulicht::TickInput;
 
 use super::{clock::Time, println, state::State};
 
-#[derive(Debug)]
+//
+// Beat and drop filter settings, from the `beat` section of the patch.
+// All of them can be changed at runtime with the `beat.*` controls.
+//
+
+// Thresholds below this open the filter on noise.
+const MIN_THRESHOLD: u8 = 10;
+const MAX_COOLDOWN: u32 = 10_000;
+const MAX_DROP_BEGIN: u32 = 10_000;
+const MAX_CALIBRATION_MINUTES: u32 = 30;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FilterSensitivity {
     High,
     Mid,
     Low,
 }
 
+impl Default for FilterSensitivity {
+    fn default() -> Self {
+        Self::Mid
+    }
+}
+
+impl FilterSensitivity {
+    fn thresholds(&self) -> FilterThresholds {
+        match self {
+            FilterSensitivity::High => FilterThresholds {
+                bass_avg: 70,
+                bass_peak: 200,
+                bass: 70,
+                quiet_avg: 50,
+            },
+            FilterSensitivity::Mid => FilterThresholds {
+                bass_avg: 80,
+                bass_peak: 255,
+                bass: 80,
+                quiet_avg: 50,
+            },
+            FilterSensitivity::Low => FilterThresholds {
+                bass_avg: 90,
+                bass_peak: 255,
+                bass: 90,
+                quiet_avg: 60,
+            },
+        }
+    }
+
+    // Shifts the percentiles of the calibration, higher sensitivities open the filter more often.
+    fn percentile_offset(&self) -> f32 {
+        match self {
+            FilterSensitivity::High => -0.1,
+            FilterSensitivity::Mid => 0.0,
+            FilterSensitivity::Low => 0.1,
+        }
+    }
+
+    /// Positive steps are more sensitive.
+    pub fn step(&self, steps: i32) -> Self {
+        const ORDER: [FilterSensitivity; 3] = [
+            FilterSensitivity::Low,
+            FilterSensitivity::Mid,
+            FilterSensitivity::High,
+        ];
+
+        let index = ORDER.iter().position(|s| s == self).unwrap_or(1) as i32;
+        ORDER[(index + steps).clamp(0, ORDER.len() as i32 - 1) as usize]
+    }
+
+    /// From 0.0 (low) to 1.0 (high).
+    pub fn from_fader(value: f32) -> Self {
+        FilterSensitivity::Low.step((value.clamp(0.0, 1.0) * 2.0).round() as i32)
+    }
+}
+
+/// The filter opens if any of the thresholds is reached.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilterThresholds {
+    pub bass_avg: u8,
+    /// Short bass average, e.g. a single loud kick. Reaching it is enough, the High sensitivity
+    /// used to require exactly 200.
+    pub bass_peak: u8,
+    /// Bass while the average is at most `quiet_avg`, kicks after a break.
+    pub bass: u8,
+    pub quiet_avg: u8,
+}
+
+impl FilterThresholds {
+    // Quiet means below the average threshold, anything above it opens the filter anyway.
+    fn clamped(self) -> Self {
+        let bass_avg = self.bass_avg.max(MIN_THRESHOLD);
+
+        Self {
+            bass_avg,
+            bass_peak: self.bass_peak.max(MIN_THRESHOLD),
+            bass: self.bass.max(MIN_THRESHOLD),
+            quiet_avg: self.quiet_avg.clamp(MIN_THRESHOLD, bass_avg),
+        }
+    }
+
+    fn is_open(&self, input: TickInput) -> bool {
+        input.bass_avg >= self.bass_avg
+            || input.bass_avg_short >= self.bass_peak
+            || (input.bass >= self.bass && input.bass_avg <= self.quiet_avg)
+    }
+}
+
+fn cooldown_default() -> u32 {
+    1000
+}
+
+fn drop_begin_default() -> u32 {
+    2000
+}
+
+fn calibration_minutes_default() -> u32 {
+    5
+}
+
+/// Times are in milliseconds.
+#[derive(Deserialize, Debug, Clone)]
+pub struct BeatSettings {
+    #[serde(default)]
+    pub sensitivity: FilterSensitivity,
+    /// Replaces the thresholds of the sensitivity.
+    #[serde(default)]
+    pub thresholds: Option<FilterThresholds>,
+    /// Minimum time without beats before the filter counts as opened again, up to 10 s.
+    #[serde(default = "cooldown_default")]
+    pub cooldown: u32,
+    /// Build up of a drop before the main part, up to 10 s.
+    #[serde(default = "drop_begin_default")]
+    pub drop_begin: u32,
+    /// Learns the thresholds from the bass of the last `calibration_minutes`.
+    #[serde(default)]
+    pub calibrate: bool,
+    /// From 1 to 30.
+    #[serde(default = "calibration_minutes_default")]
+    pub calibration_minutes: u32,
+}
+
+impl Default for BeatSettings {
+    fn default() -> Self {
+        Self {
+            sensitivity: FilterSensitivity::default(),
+            thresholds: None,
+            cooldown: cooldown_default(),
+            drop_begin: drop_begin_default(),
+            calibrate: false,
+            calibration_minutes: calibration_minutes_default(),
+        }
+    }
+}
+
+//
+// Calibration, collects one summary of the bass per second.
+//
+
+const SAMPLE_MILLIS: i32 = 1000;
+// Seconds of samples before the thresholds are learned.
+const MIN_SAMPLES: usize = 60;
+const CALIBRATION_INTERVAL: Time = Time::new(10_000);
+
+#[derive(Debug, Clone, Copy, Default)]
+struct BassSample {
+    bass: u8,
+    bass_avg: u8,
+    bass_peak: u8,
+}
+
+#[derive(Debug)]
+struct Calibration {
+    samples: VecDeque<BassSample>,
+    capacity: usize,
+    current: BassSample,
+    bass_avg_sum: u32,
+    ticks: u32,
+    sample_start: Time,
+}
+
+fn percentile(values: &mut [u8], percentile: f32) -> u8 {
+    values.sort_unstable();
+    let index = ((values.len() - 1) as f32 * percentile.clamp(0.0, 1.0)).round() as usize;
+    values[index]
+}
+
+impl Calibration {
+    fn new(minutes: u32) -> Self {
+        let capacity = minutes.clamp(1, MAX_CALIBRATION_MINUTES) as usize * 60;
+
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            current: BassSample::default(),
+            bass_avg_sum: 0,
+            ticks: 0,
+            sample_start: Time::now(),
+        }
+    }
+
+    fn tick(&mut self, input: TickInput) {
+        // Silence would pull all thresholds down.
+        if input.volume > 0 {
+            self.current.bass = self.current.bass.max(input.bass);
+            self.current.bass_peak = self.current.bass_peak.max(input.bass_avg_short);
+            self.bass_avg_sum += input.bass_avg as u32;
+            self.ticks += 1;
+        }
+
+        if self.sample_start.elapsed() < SAMPLE_MILLIS {
+            return;
+        }
+
+        if let Some(bass_avg) = self.bass_avg_sum.checked_div(self.ticks) {
+            if self.samples.len() >= self.capacity {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(BassSample {
+                bass_avg: bass_avg as u8,
+                ..self.current
+            });
+        }
+
+        self.current = BassSample::default();
+        self.bass_avg_sum = 0;
+        self.ticks = 0;
+        self.sample_start = Time::now();
+    }
+
+    fn thresholds(&self, sensitivity: FilterSensitivity) -> Option<FilterThresholds> {
+        if self.samples.len() < MIN_SAMPLES {
+            return None;
+        }
+
+        let offset = sensitivity.percentile_offset();
+        let learn = |select: fn(&BassSample) -> u8, at: f32| {
+            let mut values: Vec<u8> = self.samples.iter().map(select).collect();
+            percentile(&mut values, at + offset)
+        };
+
+        let thresholds = FilterThresholds {
+            bass_avg: learn(|s| s.bass_avg, 0.7),
+            bass_peak: learn(|s| s.bass_peak, 0.9),
+            bass: learn(|s| s.bass, 0.8),
+            quiet_avg: learn(|s| s.bass_avg, 0.3),
+        };
+
+        Some(thresholds.clamped())
+    }
+}
+
 //
 // Beat filter.
 //
@@ -16,45 +264,104 @@ pub enum FilterSensitivity {
 #[derive(Debug)]
 pub struct BeatFilter {
     pub sensitivity: FilterSensitivity,
+    thresholds: FilterThresholds,
+    cooldown: Time,
+    calibrating: bool,
+    calibration: Calibration,
+    last_calibration: Time,
     open: bool,
     first_time_open: (bool, Time), // State and when it was changed.
 }
 
 impl BeatFilter {
-    pub fn new(sensitivity: FilterSensitivity) -> Self {
+    pub fn new(settings: &BeatSettings) -> Self {
+        let thresholds = settings
+            .thresholds
+            .unwrap_or_else(|| settings.sensitivity.thresholds());
+
         Self {
-            sensitivity,
+            sensitivity: settings.sensitivity,
+            thresholds: thresholds.clamped(),
+            cooldown: Time::new(settings.cooldown.min(MAX_COOLDOWN) as i32),
+            calibrating: settings.calibrate,
+            calibration: Calibration::new(settings.calibration_minutes),
+            last_calibration: Time::now(),
             open: false,
             first_time_open: (false, Time::now()),
         }
     }
 
-    pub fn tick(&mut self, input: TickInput) {
-        let open = match self.sensitivity {
-            FilterSensitivity::High => {
-                (input.bass_avg >= 70)
-                    || (input.bass_avg_short == 200)
-                    || (input.bass >= 70 && input.bass_avg <= 50)
-                    || (input.bass_avg >= 70)
-            }
-            FilterSensitivity::Mid => {
-                (input.bass_avg >= 80)
-                    || (input.bass_avg_short == 255)
-                    || (input.bass >= 80 && input.bass_avg <= 50)
-                    || (input.bass_avg >= 80)
-            }
-            FilterSensitivity::Low => {
-                (input.bass_avg >= 90)
-                    || (input.bass_avg_short == 255)
-                    || (input.bass >= 90 && input.bass_avg <= 60)
-                    || (input.bass_avg >= 90)
+    pub fn thresholds(&self) -> FilterThresholds {
+        self.thresholds
+    }
+
+    /// Stops the calibration, the thresholds are set by hand.
+    pub fn set_thresholds(&mut self, thresholds: FilterThresholds) {
+        if self.calibrating {
+            println!("[BEAT] Calibration stopped");
+            self.calibrating = false;
+        }
+
+        self.thresholds = thresholds.clamped();
+        println!("[BEAT] Thresholds: {:?}", self.thresholds);
+    }
+
+    pub fn set_sensitivity(&mut self, sensitivity: FilterSensitivity) {
+        self.sensitivity = sensitivity;
+        println!("[BEAT] Sensitivity: {sensitivity:?}");
+
+        match self.calibrating {
+            true => self.calibrate(),
+            false => self.thresholds = sensitivity.thresholds(),
+        }
+    }
+
+    pub fn cooldown(&self) -> Time {
+        self.cooldown
+    }
+
+    pub fn set_cooldown(&mut self, millis: f32) {
+        self.cooldown = Time::new(millis.clamp(0.0, MAX_COOLDOWN as f32) as i32);
+        println!("[BEAT] Cooldown: {} ms", self.cooldown);
+    }
+
+    pub fn set_calibrating(&mut self, calibrating: bool) {
+        self.calibrating = calibrating;
+        println!("[BEAT] Calibration: {calibrating}");
+
+        if calibrating {
+            self.calibrate();
+        }
+    }
+
+    fn calibrate(&mut self) {
+        self.last_calibration = Time::now();
+
+        match self.calibration.thresholds(self.sensitivity) {
+            Some(thresholds) if thresholds != self.thresholds => {
+                self.thresholds = thresholds;
+                println!("[BEAT] Calibrated thresholds: {thresholds:?}");
             }
-        };
+            Some(_) => {}
+            None => println!("[BEAT] Not enough bass statistics to calibrate yet"),
+        }
+    }
+
+    pub fn tick(&mut self, input: TickInput) {
+        self.calibration.tick(input);
+        if self.calibrating
+            && self.last_calibration.elapsed() >= CALIBRATION_INTERVAL
+            && self.calibration.samples.len() >= MIN_SAMPLES
+        {
+            self.calibrate();
+        }
+
+        let open = self.thresholds.is_open(input);
 
         if open {
             if !self.open {
                 // Cooldown for drop activations.
-                if self.first_time_open.1.elapsed() > 1000 {
+                if self.first_time_open.1.elapsed() > self.cooldown {
                     self.first_time_open.0 = true
                 } else {
                     println!("COOLDOWN");
@@ -93,32 +400,33 @@ pub enum DropState {
     Main,
 }
 
-impl DropState {
-    // TODO: make this configurable.
-    pub fn max_duration(&self) -> Time {
-        match self {
-            DropState::None => Time::new(0),
-            DropState::Begin => Time::new(2000),
-            DropState::Main => Time::new(0),
-        }
-    }
-}
-
 #[derive(Debug)]
 pub struct DropFilter {
     // pub sensitivity: FilterSensitivity,
     pub drop_start_time: Option<Time>,
     pub state: DropState,
+    // How long the drop stays in `Begin`.
+    begin_duration: Time,
 }
 
 impl DropFilter {
-    pub fn new() -> Self {
+    pub fn new(settings: &BeatSettings) -> Self {
         Self {
             drop_start_time: None,
             state: DropState::None,
+            begin_duration: Time::new(settings.drop_begin.min(MAX_DROP_BEGIN) as i32),
         }
     }
 
+    pub fn begin_duration(&self) -> Time {
+        self.begin_duration
+    }
+
+    pub fn set_begin_duration(&mut self, millis: f32) {
+        self.begin_duration = Time::new(millis.clamp(0.0, MAX_DROP_BEGIN as f32) as i32);
+        println!("[BEAT] Drop build up: {} ms", self.begin_duration);
+    }
+
     pub fn beat_filter_in(&mut self, on_beat: bool) {
         match on_beat {
             true => self.tick_beat_true(),
@@ -134,7 +442,7 @@ impl DropFilter {
             }
             (Some(_), DropState::None) => unreachable!(),
             (Some(t), DropState::Begin) => {
-                if t.elapsed() > self.state.max_duration() {
+                if t.elapsed() > self.begin_duration {
                     self.state = DropState::Main
                 }
             }
@@ -149,3 +457,79 @@ impl DropFilter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(bass: u8, bass_avg_short: u8, bass_avg: u8) -> TickInput {
+        TickInput {
+            time: 0,
+            volume: 255,
+            beat_volume: 0,
+            bass,
+            bass_avg_short,
+            bass_avg,
+            bpm: 0,
+            time_between_beats_millis: 0,
+            beat_phase: None,
+            initial: false,
+        }
+    }
+
+    #[test]
+    fn clamped_keeps_quiet_avg_below_bass_avg() {
+        let thresholds = FilterThresholds {
+            bass_avg: 40,
+            bass_peak: 0,
+            bass: 5,
+            quiet_avg: 90,
+        }
+        .clamped();
+
+        assert_eq!(
+            thresholds,
+            FilterThresholds {
+                bass_avg: 40,
+                bass_peak: MIN_THRESHOLD,
+                bass: MIN_THRESHOLD,
+                quiet_avg: 40,
+            }
+        );
+    }
+
+    #[test]
+    fn high_opens_at_and_above_the_peak() {
+        let thresholds = FilterSensitivity::High.thresholds();
+
+        assert!(!thresholds.is_open(input(0, 199, 60)));
+        assert!(thresholds.is_open(input(0, 200, 60)));
+        assert!(thresholds.is_open(input(0, 230, 60)));
+    }
+
+    #[test]
+    fn quiet_bass_opens_after_a_break() {
+        let thresholds = FilterSensitivity::Mid.thresholds();
+
+        assert!(thresholds.is_open(input(80, 0, 50)));
+        assert!(!thresholds.is_open(input(80, 0, 60)));
+    }
+
+    #[test]
+    fn sensitivity_steps_stop_at_the_ends() {
+        assert_eq!(FilterSensitivity::Mid.step(1), FilterSensitivity::High);
+        assert_eq!(FilterSensitivity::Mid.step(5), FilterSensitivity::High);
+        assert_eq!(FilterSensitivity::Mid.step(-5), FilterSensitivity::Low);
+        assert_eq!(FilterSensitivity::from_fader(0.5), FilterSensitivity::Mid);
+        assert_eq!(FilterSensitivity::from_fader(1.0), FilterSensitivity::High);
+    }
+
+    #[test]
+    fn percentile_picks_the_nearest_value() {
+        let mut values = [50, 10, 40, 20, 30];
+
+        assert_eq!(percentile(&mut values, 0.0), 10);
+        assert_eq!(percentile(&mut values, 0.7), 40);
+        assert_eq!(percentile(&mut values, 2.0), 50);
+    }
+}
@@ -6,8 +6,9 @@ use std::{
     ops::{Add, Div, Sub},
 };
 
+use serde::Deserialize;
+
 use crate::blaulicht::TickInput;
-use crate::println;
 
 use super::GLOBAL_TIME;
 
@@ -107,6 +108,19 @@ pub struct BeatClock {
     // Is set to true
     current_activation: Option<BeatClockActivation>,
     avg_drift: VecDeque<Time>,
+    last_beat_phase: Option<u8>,
+    // Beats of the tempo since the clock was created, see `beat_position`.
+    tempo_beats: f64,
+    last_input_time: Option<TIME_INNER>,
+    // Whether the last tick followed the beat phase.
+    followed_phase: bool,
+    // Activations per beat of the tempo.
+    multiplier: f32,
+    // Share of a beat the activations are shifted by, `applied_phase` is the shift so far.
+    phase: f32,
+    applied_phase: f32,
+    // Replaces the analyzed tempo if set.
+    bpm: Option<f32>,
 }
 
 #[derive(Debug)]
@@ -117,6 +131,9 @@ pub struct BeatClockActivation {
 
 const CLOCK_DRIFT_HISTORY_LEN: usize = 10;
 
+const MIN_MULTIPLIER: f32 = 0.25;
+const MAX_MULTIPLIER: f32 = 4.0;
+
 impl BeatClock {
     pub fn new() -> Self {
         Self {
@@ -124,24 +141,149 @@ impl BeatClock {
             target_time_between_ticks: Time::default(),
             current_activation: None,
             avg_drift: VecDeque::with_capacity(CLOCK_DRIFT_HISTORY_LEN),
+            last_beat_phase: None,
+            tempo_beats: 0.0,
+            last_input_time: None,
+            followed_phase: false,
+            multiplier: 1.0,
+            phase: 0.0,
+            applied_phase: 0.0,
+            bpm: None,
+        }
+    }
+
+    pub fn from_settings(settings: &ClockSettings) -> Self {
+        let mut clock = Self::new();
+        clock.set_multiplier(settings.multiplier);
+        clock.set_phase(settings.phase);
+        clock.set_bpm(settings.bpm);
+        clock
+    }
+
+    pub fn multiplier(&self) -> f32 {
+        self.multiplier
+    }
+
+    /// From 1/4 to 4 activations per beat.
+    pub fn set_multiplier(&mut self, multiplier: f32) {
+        self.multiplier = multiplier.clamp(MIN_MULTIPLIER, MAX_MULTIPLIER);
+    }
+
+    /// Shifts the activations by a share of a beat, positive values activate earlier.
+    pub fn set_phase(&mut self, phase: f32) {
+        self.phase = phase;
+    }
+
+    /// A fixed tempo instead of the analyzed one, `None` follows the audio again.
+    /// Taps go through the tempo of the host (`tempo.tap`), which all clocks follow.
+    pub fn set_bpm(&mut self, bpm: Option<f32>) {
+        self.bpm = bpm.filter(|bpm| *bpm > 0.0);
+    }
+
+    // The external MIDI clock is only followed at its own tempo.
+    fn follows_external(&self) -> bool {
+        self.bpm.is_none() && self.multiplier == 1.0
+    }
+
+    /// Beats of the clock since the start, including the position inside the current beat.
+    /// The beats of the tempo are shifted by the phase and scaled by the multiplier,
+    /// a clock at 2x advances by two for every beat of the tempo.
+    pub fn beat_position(&self) -> f32 {
+        ((self.tempo_beats + self.phase as f64) * self.multiplier as f64) as f32
+    }
+
+    // Follows the beats of the tempo, with the beat phase if there is one and the time otherwise.
+    fn advance(&mut self, input: TickInput) {
+        let elapsed = self
+            .last_input_time
+            .map_or(0, |last| input.time - last)
+            .max(0);
+        self.last_input_time = Some(input.time);
+
+        let phase = input.beat_phase.filter(|_| self.bpm.is_none());
+        let followed_phase = std::mem::replace(&mut self.followed_phase, phase.is_some());
+
+        if let Some(phase) = phase {
+            let delta = (phase as f64 / 256.0 - self.tempo_beats.rem_euclid(1.0)).rem_euclid(1.0);
+            // Once following, the phase takes the shortest way, nudges move it backwards.
+            self.tempo_beats += match followed_phase && delta > 0.5 {
+                true => delta - 1.0,
+                false => delta,
+            };
+            return;
+        }
+
+        let beat_millis = match self.bpm {
+            Some(bpm) => 60_000.0 / bpm as f64,
+            None if input.bpm > 0 => input.time_between_beats_millis as f64,
+            None => return,
+        };
+        if beat_millis > 0.0 {
+            self.tempo_beats += elapsed as f64 / beat_millis;
         }
     }
 
     fn internal_speed_update(&mut self, input: TickInput) {
+        if let Some(bpm) = self.bpm {
+            let millis = 60_000.0 / bpm / self.multiplier;
+            self.target_time_between_ticks = Time::new(millis.round() as TIME_INNER);
+            return;
+        }
+
         if input.bpm == 0 {
             self.avg_drift.clear();
             return;
         }
 
-        let target_time_between_ticks_avg = (input.time_between_beats_millis as TIME_INNER
+        let time_between_ticks = input.time_between_beats_millis as f32 / self.multiplier;
+        let target_time_between_ticks_avg = (time_between_ticks.round() as TIME_INNER
             + self.target_time_between_ticks.inner())
             / 2;
         self.target_time_between_ticks = Time::new(target_time_between_ticks_avg);
     }
 
+    // Moves the last beat by the change of the phase, once the tempo is known.
+    fn internal_phase_update(&mut self) {
+        let delta = self.phase - self.applied_phase;
+        if delta == 0.0 || self.target_time_between_ticks == 0 {
+            return;
+        }
+
+        // The target is the time between activations, not between beats.
+        let shift = delta * self.target_time_between_ticks.inner() as f32 * self.multiplier;
+        self.last_beat_tick_time = self.last_beat_tick_time - Time::new(shift.round() as TIME_INNER);
+        self.applied_phase = self.phase;
+    }
+
     pub fn tick(&mut self, input: TickInput) {
+        self.advance(input);
         self.internal_speed_update(input);
 
+        //
+        // External MIDI clock: the beat starts whenever the phase wraps around.
+        //
+
+        let external = input.beat_phase.filter(|_| self.follows_external());
+        if let Some(phase) = external {
+            let phase = phase.wrapping_add((self.phase.rem_euclid(1.0) * 256.0) as u8);
+            let wrapped = self.last_beat_phase.is_some_and(|last| phase < last);
+            self.last_beat_phase = Some(phase);
+
+            if wrapped && self.current_activation.is_none() {
+                self.last_beat_tick_time = Time::now();
+                self.avg_drift.clear();
+                self.current_activation = Some(BeatClockActivation {
+                    overdue_delta: Time::new(0),
+                    avg_drift: Time::new(0),
+                });
+            }
+
+            return;
+        }
+
+        self.last_beat_phase = None;
+        self.internal_phase_update();
+
         //
         // Activation logic.
         //
@@ -197,7 +339,7 @@ impl BeatClock {
     //
     // Also returns a bool whether the callback was activated.
     //
-    pub fn activate(&mut self, callback: fn(activation: BeatClockActivation)) -> bool {
+    pub fn activate(&mut self, callback: impl FnOnce(BeatClockActivation)) -> bool {
         let activation = self.current_activation.take();
         if let Some(a) = activation {
             callback(a);
@@ -207,3 +349,220 @@ impl BeatClock {
         }
     }
 }
+
+//
+// Named clocks, all driven by the same audio tempo.
+// Features use their own clock if the patch defines it, otherwise the main clock.
+//
+
+pub const MAIN_CLOCK: &str = "main";
+pub const STROBE_CLOCK: &str = "strobe";
+pub const MOOD_CLOCK: &str = "mood";
+
+fn multiplier_default() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ClockSettings {
+    pub name: String,
+    /// From 0.25 to 4.0 activations per beat.
+    #[serde(default = "multiplier_default")]
+    pub multiplier: f32,
+    /// Share of a beat the activations are shifted by.
+    #[serde(default)]
+    pub phase: f32,
+    /// Fixed tempo instead of the analyzed one.
+    #[serde(default)]
+    pub bpm: Option<f32>,
+}
+
+#[derive(Debug)]
+struct NamedClock {
+    name: String,
+    clock: BeatClock,
+    // Whether the clock activated on the current tick.
+    activated: bool,
+}
+
+#[derive(Debug)]
+pub struct Clocks {
+    // The main clock comes first.
+    clocks: Vec<NamedClock>,
+}
+
+impl Clocks {
+    pub fn new(settings: &[ClockSettings]) -> Self {
+        let mut clocks = vec![NamedClock {
+            name: MAIN_CLOCK.to_string(),
+            clock: BeatClock::new(),
+            activated: false,
+        }];
+
+        for settings in settings {
+            let clock = BeatClock::from_settings(settings);
+            match clocks.iter_mut().find(|c| c.name == settings.name) {
+                Some(existing) => existing.clock = clock,
+                None => clocks.push(NamedClock {
+                    name: settings.name.clone(),
+                    clock,
+                    activated: false,
+                }),
+            }
+        }
+
+        Self { clocks }
+    }
+
+    pub fn tick(&mut self, input: TickInput) {
+        for named in self.clocks.iter_mut() {
+            named.clock.tick(input);
+            named.activated = named.clock.activate(|_| {});
+        }
+    }
+
+    fn find(&self, name: &str) -> &NamedClock {
+        self.clocks
+            .iter()
+            .find(|c| c.name == name)
+            .unwrap_or(&self.clocks[0])
+    }
+
+    /// Falls back to the main clock.
+    pub fn get(&self, name: &str) -> &BeatClock {
+        &self.find(name).clock
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut BeatClock> {
+        self.clocks
+            .iter_mut()
+            .find(|c| c.name == name)
+            .map(|c| &mut c.clock)
+    }
+
+    /// Whether the clock, or the main clock if there is none by that name, activated on this tick.
+    pub fn activated(&self, name: &str) -> bool {
+        self.find(name).activated
+    }
+}
+
+impl Default for Clocks {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(time: i32, beat_phase: Option<u8>) -> TickInput {
+        TickInput {
+            time,
+            volume: 0,
+            beat_volume: 0,
+            bass: 0,
+            bass_avg_short: 0,
+            bass_avg: 0,
+            bpm: 120,
+            time_between_beats_millis: 500,
+            beat_phase,
+            initial: false,
+        }
+    }
+
+    fn settings(name: &str, multiplier: f32, phase: f32) -> ClockSettings {
+        ClockSettings {
+            name: name.to_string(),
+            multiplier,
+            phase,
+            bpm: None,
+        }
+    }
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-3, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn multiplier_scales_the_beats() {
+        let mut clocks = Clocks::new(&[settings("double", 2.0, 0.0), settings("half", 0.5, 0.0)]);
+
+        for time in (0..=1250).step_by(50) {
+            clocks.tick(input(time, None));
+        }
+
+        // 1250 ms at 120 BPM.
+        assert_near(clocks.get(MAIN_CLOCK).beat_position(), 2.5);
+        assert_near(clocks.get("double").beat_position(), 5.0);
+        assert_near(clocks.get("half").beat_position(), 1.25);
+        // Unknown clocks fall back to the main clock.
+        assert_near(clocks.get("unknown").beat_position(), 2.5);
+    }
+
+    #[test]
+    fn phase_shifts_the_beats() {
+        let mut clocks = Clocks::new(&[settings("late", 2.0, -0.25)]);
+        clocks.tick(input(0, None));
+        clocks.tick(input(500, None));
+
+        assert_near(clocks.get("late").beat_position(), 1.5);
+    }
+
+    #[test]
+    fn follows_the_beat_phase_across_beats() {
+        let mut clock = BeatClock::from_settings(&settings("double", 2.0, 0.0));
+
+        // The phase wraps around at every beat of the tempo.
+        let steps = [(64, 0.5), (128, 1.0), (240, 1.875), (16, 2.125), (128, 3.0)];
+
+        clock.tick(input(0, Some(0)));
+        for (time, (phase, expected)) in (100..).step_by(100).zip(steps) {
+            clock.tick(input(time, Some(phase)));
+            assert_near(clock.beat_position(), expected);
+        }
+    }
+
+    #[test]
+    fn nudges_move_the_beats_backwards() {
+        let mut clock = BeatClock::new();
+        clock.tick(input(0, Some(128)));
+        clock.tick(input(10, Some(120)));
+
+        assert_near(clock.beat_position(), 120.0 / 256.0);
+    }
+
+    // Taps set the beat phase of the tick input, see the tempo of the host.
+    #[test]
+    fn follows_the_tapped_grid() {
+        let mut clocks = Clocks::new(&[settings("double", 2.0, 0.0)]);
+
+        // The analyzed tempo is unknown, only the phase of the tapped grid arrives.
+        let mut tapped = |time, phase| {
+            let mut input = input(time, Some(phase));
+            input.bpm = 100;
+            input.time_between_beats_millis = 600;
+            clocks.tick(input);
+        };
+        tapped(0, 0);
+        tapped(300, 128);
+        tapped(600, 0);
+
+        assert_near(clocks.get(MAIN_CLOCK).beat_position(), 1.0);
+        assert_near(clocks.get("double").beat_position(), 2.0);
+    }
+
+    #[test]
+    fn fixed_tempo_ignores_the_beat_phase() {
+        let mut clock = BeatClock::from_settings(&ClockSettings {
+            bpm: Some(60.0),
+            ..settings("slow", 1.0, 0.0)
+        });
+
+        clock.tick(input(0, Some(0)));
+        clock.tick(input(250, Some(128)));
+        clock.tick(input(500, Some(0)));
+
+        assert_near(clock.beat_position(), 0.5);
+    }
+}
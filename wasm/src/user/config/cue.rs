@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Fixture, Look, PaletteColor, Rotation};
+
+//
+// Scenes store looks of fixture groups, cue lists play them back with fades.
+// Both are kept in the cue file of the host, recording a scene writes them back.
+//
+
+/// Attributes of one group, unset attributes are left to the live state.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SceneGroup {
+    /// Label of the group.
+    pub group: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alpha: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<PaletteColor>,
+    /// Degrees from the center.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tilt: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pan: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Scene {
+    pub name: String,
+    pub groups: Vec<SceneGroup>,
+}
+
+/// Times are in milliseconds.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Cue {
+    /// Name of the scene.
+    pub scene: String,
+    #[serde(default)]
+    pub fade_in: u32,
+    /// Fade once the next cue starts or the cue is released.
+    #[serde(default)]
+    pub fade_out: u32,
+    /// Wait before the fades start.
+    #[serde(default)]
+    pub delay: u32,
+    /// Continues with the next cue this long after the fade in, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub follow: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CueList {
+    pub name: String,
+    pub cues: Vec<Cue>,
+    /// Starts over after the last cue.
+    #[serde(default)]
+    pub looped: bool,
+}
+
+impl Scene {
+    pub fn group(&self, label: &str) -> Option<&SceneGroup> {
+        self.groups.iter().find(|group| group.group == label)
+    }
+}
+
+impl SceneGroup {
+    /// The current state of a group, taken from its first fixture.
+    pub fn capture(label: &str, fixtures: &[Fixture]) -> Option<Self> {
+        let fixture = fixtures.first()?;
+        let Look { alpha, color, .. } = fixture.look();
+        let (tilt, pan) = match fixture.is_movable() {
+            true => {
+                let (tilt, pan) = fixture.position();
+                (Some(tilt), Some(pan))
+            }
+            false => (None, None),
+        };
+
+        Some(Self {
+            group: label.to_string(),
+            alpha: Some(alpha),
+            color: Some(PaletteColor::Rgb(color.r, color.g, color.b)),
+            tilt,
+            pan,
+        })
+    }
+
+    /// Fades a look of the fixture towards the scene, `weight` ranges from 0.0 to 1.0.
+    pub fn blend(&self, fixture: &Fixture, look: Look, weight: f32) -> Look {
+        if weight <= 0.0 {
+            return look;
+        }
+
+        let lerp = |from: f32, to: f32| from + (to - from) * weight;
+        let target = fixture.position_to_raw(self.tilt.unwrap_or(0.0), self.pan.unwrap_or(0.0));
+        let axis = |from: u16, to: Option<u16>| match to {
+            Some(to) => lerp(from as f32, to as f32).round() as u16,
+            None => from,
+        };
+
+        let rotation = Rotation {
+            tilt: axis(look.rotation.tilt, self.tilt.map(|_| target.tilt)),
+            pan: axis(look.rotation.pan, self.pan.map(|_| target.pan)),
+        };
+
+        Look {
+            alpha: self
+                .alpha
+                .map_or(look.alpha, |alpha| lerp(look.alpha as f32, alpha as f32).round() as u8),
+            color: self
+                .color
+                .map_or(look.color, |color| look.color.lerp(color.color(), weight)),
+            rotation,
+        }
+    }
+}
@@ -4,17 +4,164 @@ use crate::user::clock::Time;
 
 use super::Fixture;
 
+//
+// Conventional dimmer packs, driven by the alpha of the fixture.
+// Multi-channel dimmers drive consecutive channels with the same level.
+//
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DimmerCurve {
+    Linear,
+    Square,
+    /// Soft at both ends.
+    SCurve,
+    /// Perceptually linear (CIE 1931 lightness), for LED dimmers which are too bright at low levels.
+    Led,
+}
+
+impl Default for DimmerCurve {
+    fn default() -> Self {
+        Self::Linear
+    }
+}
+
+impl DimmerCurve {
+    /// Maps a level from 0.0 to 1.0 onto an output from 0.0 to 1.0.
+    pub fn apply(&self, level: f32) -> f32 {
+        let level = level.clamp(0.0, 1.0);
+        match self {
+            DimmerCurve::Linear => level,
+            DimmerCurve::Square => level * level,
+            DimmerCurve::SCurve => level * level * (3.0 - 2.0 * level),
+            DimmerCurve::Led => {
+                let lightness = level * 100.0;
+                if lightness <= 8.0 {
+                    lightness / 903.3
+                } else {
+                    ((lightness + 16.0) / 116.0).powi(3)
+                }
+            }
+        }
+    }
+}
+
+fn channels_default() -> usize {
+    1
+}
+
+fn max_default() -> u8 {
+    255
+}
 
 #[derive(Deserialize, Debug)]
-pub enum Dimmer {}
+pub struct Dimmer {
+    #[serde(default = "channels_default")]
+    pub channels: usize,
+    #[serde(default)]
+    pub curve: DimmerCurve,
+    /// Output of the lowest level above zero.
+    #[serde(default)]
+    pub min: u8,
+    /// Output of the highest level.
+    #[serde(default = "max_default")]
+    pub max: u8,
+    /// Output at level zero and in blackout, keeps filaments warm.
+    #[serde(default)]
+    pub preheat: u8,
+}
 
 impl Dimmer {
-    pub fn write(&self, this: &Fixture, dmx: &mut [u8]) {}
-    pub fn blackout(&self, this: &Fixture, dmx: &mut [u8]) {}
+    pub fn output(&self, level: u8) -> u8 {
+        if level == 0 {
+            return self.preheat;
+        }
+
+        let range = self.max.saturating_sub(self.min) as f32;
+        let curved = self.curve.apply(level as f32 / 255.0);
+        let output = (self.min as f32 + curved * range).round() as u8;
+
+        output.max(self.preheat)
+    }
+
+    fn fill(&self, this: &Fixture, value: u8, dmx: &mut [u8]) {
+        for offset in 0..self.channels {
+            dmx[this.start_channel + offset] = value;
+        }
+    }
+
+    pub fn write(&self, this: &Fixture, dmx: &mut [u8]) {
+        self.fill(this, self.output(this.alpha), dmx);
+    }
+
+    pub fn blackout(&self, this: &Fixture, dmx: &mut [u8]) {
+        self.fill(this, self.preheat, dmx);
+    }
+
     pub fn setup(&self, this: &Fixture, time: Time, dmx: &mut [u8]) {
-        // match self {
-        //     MovingHead::Generic3ChanNoAlpha => todo!(),
-        //     MovingHead::Generic4ChanWithAlpha => todo!(),
-        // }
+        self.write(this, dmx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dimmer(preheat: u8) -> Fixture {
+        Fixture::new(
+            10,
+            Dimmer {
+                channels: 2,
+                curve: DimmerCurve::Linear,
+                min: 0,
+                max: 255,
+                preheat,
+            }
+            .into(),
+        )
+    }
+
+    #[test]
+    fn blackout_keeps_the_preheat() {
+        let mut dmx = [0; 513];
+        let mut fixture = dimmer(5);
+
+        fixture.set_alpha(255, &mut dmx);
+        assert_eq!(dmx[9..13], [0, 255, 255, 0]);
+
+        fixture.blackout(&mut dmx);
+        assert_eq!(dmx[9..13], [0, 5, 5, 0]);
+        assert_eq!(fixture.alpha, 255);
+
+        fixture.write(&mut dmx);
+        assert_eq!(dmx[9..13], [0, 255, 255, 0]);
+    }
+
+    #[test]
+    fn home_is_dark() {
+        let mut dmx = [0; 513];
+        let mut fixture = dimmer(0);
+
+        fixture.set_alpha(128, &mut dmx);
+        fixture.home(&mut dmx);
+        assert_eq!(dmx[9..13], [0, 0, 0, 0]);
+
+        fixture.write(&mut dmx);
+        assert_eq!(dmx[9..13], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn output_follows_the_curve_between_min_and_max() {
+        let dimmer = Dimmer {
+            channels: 1,
+            curve: DimmerCurve::Square,
+            min: 20,
+            max: 220,
+            preheat: 10,
+        };
+
+        assert_eq!(dimmer.output(0), 10);
+        assert_eq!(dimmer.output(1), 20);
+        assert_eq!(dimmer.output(128), 70);
+        assert_eq!(dimmer.output(255), 220);
     }
-}
\ No newline at end of file
+}
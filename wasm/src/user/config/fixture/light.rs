@@ -27,28 +27,83 @@ pub enum Light {
     // 5: Strobe
     //
     LEDPartyTCLSpot,
+    //
+    // 0: Alpha
+    // 1: Red
+    // 2: Green
+    // 3: Blue
+    // 4: White
+    //
+    Generic5ChanRGBW,
+    //
+    // 0: Alpha
+    // 1: Red
+    // 2: Green
+    // 3: Blue
+    // 4: Amber
+    //
+    Generic5ChanRGBA,
+    //
+    // 0: Alpha
+    // 1: Red
+    // 2: Green
+    // 3: Blue
+    // 4: Amber
+    // 5: White
+    // 6: UV
+    //
+    Generic7ChanRGBAWUV,
 }
 
 impl Light {
     pub fn write(&self, this: &Fixture, dmx: &mut [u8]) {
         match self {
             Light::Generic3ChanNoAlpha => {
-                dmx[this.start_channel + 0] = this.color.r;
-                dmx[this.start_channel + 1] = this.color.g;
-                dmx[this.start_channel + 2] = this.color.b;
+                let color = this.emitters(false, false);
+                dmx[this.start_channel + 0] = color.r;
+                dmx[this.start_channel + 1] = color.g;
+                dmx[this.start_channel + 2] = color.b;
             }
             Light::Generic4ChanWithAlpha => {
+                let color = this.emitters(false, false);
                 dmx[this.start_channel + 0] = this.alpha;
-                dmx[this.start_channel + 1] = this.color.r;
-                dmx[this.start_channel + 2] = this.color.g;
-                dmx[this.start_channel + 3] = this.color.b;
+                dmx[this.start_channel + 1] = color.r;
+                dmx[this.start_channel + 2] = color.g;
+                dmx[this.start_channel + 3] = color.b;
             }
             Light::LEDPartyTCLSpot => {
-                dmx[this.start_channel + 0] = this.color.r;
-                dmx[this.start_channel + 1] = this.color.g;
-                dmx[this.start_channel + 2] = this.color.b;
+                let color = this.emitters(false, false);
+                dmx[this.start_channel + 0] = color.r;
+                dmx[this.start_channel + 1] = color.g;
+                dmx[this.start_channel + 2] = color.b;
                 dmx[this.start_channel + 3] = this.alpha;
             }
+            Light::Generic5ChanRGBW => {
+                let color = this.emitters(true, false);
+                dmx[this.start_channel + 0] = this.alpha;
+                dmx[this.start_channel + 1] = color.r;
+                dmx[this.start_channel + 2] = color.g;
+                dmx[this.start_channel + 3] = color.b;
+                dmx[this.start_channel + 4] = color.white;
+            }
+            Light::Generic5ChanRGBA => {
+                let color = this.emitters(false, true);
+                dmx[this.start_channel + 0] = this.alpha;
+                dmx[this.start_channel + 1] = color.r;
+                dmx[this.start_channel + 2] = color.g;
+                dmx[this.start_channel + 3] = color.b;
+                dmx[this.start_channel + 4] = color.amber;
+            }
+            Light::Generic7ChanRGBAWUV => {
+                let color = this.emitters(true, true);
+                dmx[this.start_channel + 0] = this.alpha;
+                dmx[this.start_channel + 1] = color.r;
+                dmx[this.start_channel + 2] = color.g;
+                dmx[this.start_channel + 3] = color.b;
+                dmx[this.start_channel + 4] = color.amber;
+                dmx[this.start_channel + 5] = color.white;
+                dmx[this.start_channel + 6] = color.uv;
+            }
         }
     }
 
@@ -59,18 +114,23 @@ impl Light {
                 dmx[this.start_channel + 1] = 0;
                 dmx[this.start_channel + 2] = 0;
             }
-            Light::Generic4ChanWithAlpha => {
+            // Some fixtures ignore the alpha channel in certain modes, so the colors are cleared too.
+            Light::Generic4ChanWithAlpha | Light::LEDPartyTCLSpot => {
                 dmx[this.start_channel + 0] = 0;
-                dmx[this.start_channel + 1] = this.color.r;
-                dmx[this.start_channel + 2] = this.color.g;
-                dmx[this.start_channel + 3] = this.color.b;
-            }
-            Light::LEDPartyTCLSpot => {
-                dmx[this.start_channel + 0] = this.color.r;
-                dmx[this.start_channel + 1] = this.color.g;
-                dmx[this.start_channel + 2] = this.color.b;
+                dmx[this.start_channel + 1] = 0;
+                dmx[this.start_channel + 2] = 0;
                 dmx[this.start_channel + 3] = 0;
             }
+            Light::Generic5ChanRGBW | Light::Generic5ChanRGBA => {
+                for offset in 0..5 {
+                    dmx[this.start_channel + offset] = 0;
+                }
+            }
+            Light::Generic7ChanRGBAWUV => {
+                for offset in 0..7 {
+                    dmx[this.start_channel + offset] = 0;
+                }
+            }
         }
     }
 
@@ -81,3 +141,82 @@ impl Light {
         // }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::color::Color;
+
+    use super::*;
+
+    fn lit(light: Light, dmx: &mut [u8]) -> Fixture {
+        let mut fixture = Fixture::new(1, light.into());
+        fixture.set_alpha(200, dmx);
+        fixture.set_color((255, 128, 0).into(), dmx);
+        fixture
+    }
+
+    #[test]
+    fn rgb_without_alpha() {
+        let mut dmx = [0; 513];
+        let mut fixture = lit(Light::Generic3ChanNoAlpha, &mut dmx);
+        assert_eq!(dmx[0..5], [0, 255, 128, 0, 0]);
+
+        fixture.blackout(&mut dmx);
+        assert_eq!(dmx[0..5], [0, 0, 0, 0, 0]);
+
+        fixture.write(&mut dmx);
+        assert_eq!(dmx[0..5], [0, 255, 128, 0, 0]);
+    }
+
+    #[test]
+    fn rgb_with_alpha_clears_the_colors_in_blackout() {
+        let mut dmx = [0; 513];
+        let mut fixture = lit(Light::Generic4ChanWithAlpha, &mut dmx);
+        assert_eq!(dmx[0..6], [0, 200, 255, 128, 0, 0]);
+
+        fixture.blackout(&mut dmx);
+        assert_eq!(dmx[0..6], [0, 0, 0, 0, 0, 0]);
+
+        let mut fixture = lit(Light::LEDPartyTCLSpot, &mut dmx);
+        assert_eq!(dmx[0..6], [0, 255, 128, 0, 200, 0]);
+
+        fixture.blackout(&mut dmx);
+        assert_eq!(dmx[0..6], [0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn white_and_amber_emitters() {
+        let mut dmx = [0; 513];
+        let mut fixture = lit(Light::Generic5ChanRGBW, &mut dmx);
+        fixture.set_color(Color::white(), &mut dmx);
+        assert_eq!(dmx[0..7], [0, 200, 0, 0, 0, 255, 0]);
+
+        fixture.blackout(&mut dmx);
+        assert_eq!(dmx[0..7], [0, 0, 0, 0, 0, 0, 0]);
+
+        // Amber takes twice as much red as green.
+        let mut fixture = lit(Light::Generic5ChanRGBA, &mut dmx);
+        fixture.set_color((255, 102, 0).into(), &mut dmx);
+        assert_eq!(dmx[0..7], [0, 200, 51, 0, 0, 204, 0]);
+
+        fixture.blackout(&mut dmx);
+        assert_eq!(dmx[0..7], [0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn seven_channels_go_dark_and_home() {
+        let mut dmx = [0; 513];
+        let mut fixture = lit(Light::Generic7ChanRGBAWUV, &mut dmx);
+        fixture.set_color(Color::white(), &mut dmx);
+        assert_eq!(dmx[0..9], [0, 200, 0, 0, 0, 0, 255, 0, 0]);
+
+        fixture.blackout(&mut dmx);
+        assert_eq!(dmx[0..9], [0; 9]);
+
+        fixture.home(&mut dmx);
+        assert_eq!(dmx[0..9], [0; 9]);
+
+        fixture.write(&mut dmx);
+        assert_eq!(dmx[0..9], [0; 9]);
+    }
+}
@@ -1,33 +1,118 @@
 mod dimmer;
 mod light;
 mod moving_head;
+mod profile;
 
 pub use dimmer::*;
 pub use light::*;
 pub use moving_head::*;
+pub use profile::Profiles;
 use serde::Deserialize;
 
-use crate::{color::Color, user::clock::Time};
+use crate::{
+    color::{Color, ColorCalibration, Emitters},
+    effect::Modulation,
+    user::clock::Time,
+};
 
-#[derive(Deserialize, Debug)]
+/// Raw 16-bit position of the center of a range, 0x80 on the coarse channel.
+const CENTER: u16 = u16::MAX / 2 + 1;
+
+/// Raw 16-bit positions across the full range of the fixture.
+#[derive(Deserialize, Debug, Clone, Copy)]
 pub struct Rotation {
-    pub tilt: u8,
-    pub pan: u8,
+    pub tilt: u16,
+    pub pan: u16,
+}
+
+impl Rotation {
+    /// Center of both ranges.
+    pub fn home() -> Self {
+        Self {
+            tilt: CENTER,
+            pan: CENTER,
+        }
+    }
 }
 
 impl Default for Rotation {
     fn default() -> Self {
-        Self { tilt: 0, pan: 0 }
+        Self::home()
     }
 }
 
+/// Per fixture pan / tilt calibration, the ranges default to the ones of the fixture type.
+#[derive(Deserialize, Debug, Default)]
+pub struct Movement {
+    /// Full pan range in degrees.
+    pub pan_range: Option<f32>,
+    /// Full tilt range in degrees.
+    pub tilt_range: Option<f32>,
+    #[serde(default)]
+    pub invert_pan: bool,
+    #[serde(default)]
+    pub invert_tilt: bool,
+}
+
+// Used for fixture types which do not know their ranges.
+const DEFAULT_PAN_RANGE: f32 = 540.0;
+const DEFAULT_TILT_RANGE: f32 = 270.0;
+
+// Degrees are relative to the center of the range.
+fn degrees_to_raw(degrees: f32, range: f32) -> u16 {
+    if range <= 0.0 {
+        return CENTER;
+    }
+
+    ((degrees / range + 0.5).clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
+}
+
+// Moves a raw position by some degrees.
+fn shift_raw(raw: u16, degrees: f32, range: f32) -> u16 {
+    if degrees == 0.0 {
+        return raw;
+    }
+
+    degrees_to_raw(raw_to_degrees(raw, range) + degrees, range)
+}
+
+// Inverse of `degrees_to_raw`.
+fn raw_to_degrees(raw: u16, range: f32) -> f32 {
+    (raw as f32 / u16::MAX as f32 - 0.5) * range
+}
+
+// Coarse channel first, followed by the fine channel.
+fn write_16bit(dmx: &mut [u8], channel: usize, value: u16) {
+    let [coarse, fine] = value.to_be_bytes();
+    dmx[channel] = coarse;
+    dmx[channel + 1] = fine;
+}
+
+/// The attributes a fixture outputs, cues and effects change a copy of them.
+#[derive(Debug, Clone, Copy)]
+pub struct Look {
+    pub alpha: u8,
+    pub color: Color,
+    pub rotation: Rotation,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Fixture {
+    /// DMX address, channel 0 is the start code.
     start_channel: usize,
+    #[serde(rename = "type")]
     type_: FixtureType,
+    #[serde(default)]
     color: Color,
+    #[serde(default)]
     alpha: u8,
+    #[serde(default)]
     rotation: Rotation,
+    #[serde(default)]
+    movement: Movement,
+    #[serde(default)]
+    calibration: ColorCalibration,
+    #[serde(default)]
     strobe_state: bool,
 }
 
@@ -39,6 +124,8 @@ impl Fixture {
             color: Color::default(),
             alpha: 0,
             rotation: Rotation::default(),
+            movement: Movement::default(),
+            calibration: ColorCalibration::default(),
             strobe_state: false,
         }
     }
@@ -57,13 +144,122 @@ impl Fixture {
         self.type_.write(self, dmx)
     }
 
-    pub fn set_tilt_pan(&mut self, tilt: u8, pan: u8, dmx: &mut [u8]) {
+    pub fn set_tilt_pan(&mut self, tilt: u16, pan: u16, dmx: &mut [u8]) {
         self.rotation = Rotation { tilt, pan };
         self.type_.write(self, dmx)
     }
 
+    /// Degrees from the center of the pan / tilt ranges, clamped to the ranges.
+    pub fn set_position(&mut self, tilt: f32, pan: f32, dmx: &mut [u8]) {
+        let Rotation { tilt, pan } = self.position_to_raw(tilt, pan);
+        self.set_tilt_pan(tilt, pan, dmx)
+    }
+
+    pub fn position_to_raw(&self, tilt: f32, pan: f32) -> Rotation {
+        let (tilt_range, pan_range) = self.tilt_pan_range();
+        Rotation {
+            tilt: degrees_to_raw(tilt, tilt_range),
+            pan: degrees_to_raw(pan, pan_range),
+        }
+    }
+
+    /// Tilt and pan in degrees from the center.
+    pub fn position(&self) -> (f32, f32) {
+        let (tilt_range, pan_range) = self.tilt_pan_range();
+        (
+            raw_to_degrees(self.rotation.tilt, tilt_range),
+            raw_to_degrees(self.rotation.pan, pan_range),
+        )
+    }
+
+    /// Whether the fixture type can move, other fixtures ignore the rotation.
+    pub fn is_movable(&self) -> bool {
+        self.type_.tilt_pan_range() != (None, None)
+    }
+
+    pub fn tilt_pan_range(&self) -> (f32, f32) {
+        let (tilt, pan) = self.type_.tilt_pan_range();
+        (
+            self.movement.tilt_range.or(tilt).unwrap_or(DEFAULT_TILT_RANGE),
+            self.movement.pan_range.or(pan).unwrap_or(DEFAULT_PAN_RANGE),
+        )
+    }
+
+    /// The calibrated color, split onto the emitters the fixture has.
+    fn emitters(&self, white: bool, amber: bool) -> Emitters {
+        self.calibration.emitters(self.color, white, amber)
+    }
+
+    /// The rotation as sent to the fixture, inverted if configured.
+    fn output_rotation(&self) -> Rotation {
+        let invert = |value: u16, invert: bool| match invert {
+            true => u16::MAX - value,
+            false => value,
+        };
+
+        Rotation {
+            tilt: invert(self.rotation.tilt, self.movement.invert_tilt),
+            pan: invert(self.rotation.pan, self.movement.invert_pan),
+        }
+    }
+
+    /// Turns off all light output, other channels such as the position are kept.
+    /// The state of the fixture is not changed, `write` restores the output.
     pub fn blackout(&mut self, dmx: &mut [u8]) {
+        self.type_.blackout(self, dmx);
+    }
+
+    pub fn look(&self) -> Look {
+        Look {
+            alpha: self.alpha,
+            color: self.color,
+            rotation: self.rotation,
+        }
+    }
+
+    /// Writes a look instead of the state of the fixture, the state is kept.
+    pub fn write_look(&mut self, look: Look, dmx: &mut [u8]) {
+        let state = self.look();
+
+        self.alpha = look.alpha;
+        self.color = look.color;
+        self.rotation = look.rotation;
         self.type_.write(self, dmx);
+
+        self.alpha = state.alpha;
+        self.color = state.color;
+        self.rotation = state.rotation;
+    }
+
+    /// Applies the combined effects of the group to a look.
+    pub fn modulate(&self, look: Look, modulation: &Modulation) -> Look {
+        let (tilt_range, pan_range) = self.tilt_pan_range();
+
+        let color = match modulation.hue {
+            hue if hue == 0.0 => look.color,
+            hue => {
+                let (h, s, v) = look.color.to_hsv();
+                Color::from_hsv(h + hue, s, v)
+            }
+        };
+
+        Look {
+            alpha: (look.alpha as f32 * modulation.intensity.clamp(0.0, 1.0)).round() as u8,
+            color,
+            rotation: Rotation {
+                tilt: shift_raw(look.rotation.tilt, modulation.tilt, tilt_range),
+                pan: shift_raw(look.rotation.pan, modulation.pan, pan_range),
+            },
+        }
+    }
+
+    /// Dark, centered and not strobing.
+    pub fn home(&mut self, dmx: &mut [u8]) {
+        self.color = Color::default();
+        self.alpha = 0;
+        self.rotation = Rotation::home();
+        self.strobe_state = false;
+        self.type_.blackout(self, dmx);
     }
 
     pub fn set_burst(&mut self, state: bool, dmx: &mut [u8]) {
@@ -74,6 +270,10 @@ impl Fixture {
     pub fn setup(&mut self, time: Time, dmx: &mut [u8]) {
         self.type_.setup(self, time, dmx);
     }
+
+    pub fn resolve_profile(&mut self, profiles: &Profiles) -> Result<(), String> {
+        self.type_.resolve_profile(profiles, self.start_channel)
+    }
 }
 
 // Convert type and address tuple into Fixture.
@@ -92,6 +292,15 @@ pub enum FixtureType {
     MovingHead(MovingHead),
     Light(Light),
     Dimmer(Dimmer),
+    /// A fixture described by a profile of the patch, see `Profile`.
+    Profile {
+        profile: String,
+        /// The first mode of the profile if not set.
+        #[serde(default)]
+        mode: Option<String>,
+        #[serde(skip)]
+        layout: profile::Layout,
+    },
 }
 
 impl FixtureType {
@@ -100,6 +309,7 @@ impl FixtureType {
             FixtureType::MovingHead(moving_head) => moving_head.write(this, dmx),
             FixtureType::Light(light) => light.write(this, dmx),
             FixtureType::Dimmer(dimmer) => dimmer.write(this, dmx),
+            FixtureType::Profile { layout, .. } => profile::write(layout, this, dmx),
         }
     }
 
@@ -108,6 +318,7 @@ impl FixtureType {
             FixtureType::MovingHead(moving_head) => moving_head.blackout(this, dmx),
             FixtureType::Light(light) => light.blackout(this, dmx),
             FixtureType::Dimmer(dimmer) => dimmer.blackout(this, dmx),
+            FixtureType::Profile { layout, .. } => profile::blackout(layout, this, dmx),
         }
     }
 
@@ -116,7 +327,51 @@ impl FixtureType {
             FixtureType::MovingHead(moving_head) => moving_head.setup(this, time, dmx),
             FixtureType::Light(light) => light.setup(this, time, dmx),
             FixtureType::Dimmer(dimmer) => dimmer.setup(this, time, dmx),
+            FixtureType::Profile { layout, .. } => profile::write(layout, this, dmx),
+        }
+    }
+
+    /// Tilt and pan ranges in degrees, if known.
+    pub fn tilt_pan_range(&self) -> (Option<f32>, Option<f32>) {
+        match self {
+            FixtureType::MovingHead(moving_head) => moving_head.tilt_pan_range(),
+            FixtureType::Light(_) | FixtureType::Dimmer(_) => (None, None),
+            FixtureType::Profile { layout, .. } => profile::tilt_pan_range(layout),
+        }
+    }
+
+    /// Looks up the channel layout of profile fixtures, a no-op for builtin types.
+    /// Fixtures which do not fit into the universe at their address keep an empty layout.
+    pub fn resolve_profile(
+        &mut self,
+        profiles: &Profiles,
+        start_channel: usize,
+    ) -> Result<(), String> {
+        let FixtureType::Profile {
+            profile,
+            mode,
+            layout,
+        } = self
+        else {
+            return Ok(());
+        };
+
+        let Some(found) = profiles.get(profile) else {
+            return Err(format!("unknown profile <{profile}>"));
+        };
+
+        let resolved = found.layout(mode.as_deref())?;
+
+        // Channel 0 is the start code, the universe ends at channel 512.
+        if start_channel == 0 || start_channel + resolved.len() > 513 {
+            return Err(format!(
+                "profile <{profile}> with {} channels does not fit at address {start_channel}",
+                resolved.len()
+            ));
         }
+
+        *layout = resolved;
+        Ok(())
     }
 }
 
@@ -137,3 +392,77 @@ impl From<Dimmer> for FixtureType {
         Self::Dimmer(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::user::config::FixtureGroup;
+
+    use super::*;
+
+    fn group(dmx: &mut [u8]) -> FixtureGroup {
+        let mut fixtures = vec![
+            Fixture::new(1, Light::Generic4ChanWithAlpha.into()),
+            Fixture::new(5, MovingHead::MartinMacAura.into()),
+        ];
+        for fixture in fixtures.iter_mut() {
+            fixture.set_alpha(255, dmx);
+            fixture.set_color(Color::white(), dmx);
+            fixture.set_tilt_pan(0x1000, 0x2000, dmx);
+        }
+
+        FixtureGroup::new("all".to_string(), fixtures)
+    }
+
+    const LIT: [u8; 17] = [
+        0, 255, 255, 255, 255, 0, 255, 0, 0x20, 0x00, 0x10, 0x00, 0, 0, 255, 255, 255,
+    ];
+    const DARK: [u8; 17] = [
+        0, 0, 0, 0, 0, 0, 0, 0, 0x20, 0x00, 0x10, 0x00, 0, 0, 0, 0, 0,
+    ];
+
+    #[test]
+    fn degrees_are_relative_to_the_center() {
+        let home = Rotation::home();
+        assert_eq!((home.tilt, home.pan), (CENTER, CENTER));
+
+        assert_eq!(degrees_to_raw(0.0, DEFAULT_PAN_RANGE), CENTER);
+        assert_eq!(degrees_to_raw(45.0, 0.0), CENTER);
+        assert_eq!(degrees_to_raw(-270.0, DEFAULT_PAN_RANGE), 0);
+        assert_eq!(degrees_to_raw(270.0, DEFAULT_PAN_RANGE), u16::MAX);
+    }
+
+    // Same as the grand master on every tick, releasing it writes the groups again.
+    #[test]
+    fn grand_master_blackout_and_release() {
+        let mut dmx = [0; 513];
+        let mut group = group(&mut dmx);
+        assert_eq!(dmx[0..17], LIT);
+
+        group.blackout(&mut dmx);
+        assert_eq!(dmx[0..17], DARK);
+        assert!(dmx[17..].iter().all(|value| *value == 0));
+
+        group.write(&mut dmx);
+        assert_eq!(dmx[0..17], LIT);
+    }
+
+    #[test]
+    fn disabled_groups_stay_dark() {
+        let mut dmx = [0; 513];
+        let mut group = group(&mut dmx);
+
+        group.set_enabled(&mut dmx, false);
+        assert_eq!(dmx[0..17], DARK);
+
+        group.set_enabled(&mut dmx, true);
+        assert_eq!(dmx[0..17], LIT);
+    }
+
+    #[test]
+    fn degrees_map_onto_the_raw_range() {
+        assert_eq!(degrees_to_raw(0.0, 540.0), 32768);
+        assert_eq!(degrees_to_raw(-270.0, 540.0), 0);
+        assert_eq!(degrees_to_raw(400.0, 540.0), u16::MAX);
+        assert!((raw_to_degrees(u16::MAX, 540.0) - 270.0).abs() < 0.01);
+    }
+}
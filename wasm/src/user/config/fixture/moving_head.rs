@@ -2,14 +2,29 @@ use serde::Deserialize;
 
 use crate::user::clock::Time;
 
-use super::Fixture;
+use super::{write_16bit, Fixture};
 
 #[derive(Deserialize, Debug)]
 pub enum MovingHead {
+    //
+    // 0: Strobe
+    // 1: Alpha
+    // 3, 4: Pan, Pan fine
+    // 5, 6: Tilt, Tilt fine
+    // 9: Red
+    // 10: Green
+    // 11: Blue
+    //
     MartinMacAura,
 }
 
 impl MovingHead {
+    pub fn tilt_pan_range(&self) -> (Option<f32>, Option<f32>) {
+        match self {
+            MovingHead::MartinMacAura => (Some(232.0), Some(540.0)),
+        }
+    }
+
     pub fn write(&self, this: &Fixture, dmx: &mut [u8]) {
         match self {
             MovingHead::MartinMacAura => {
@@ -19,10 +34,16 @@ impl MovingHead {
                 // Alpha.
                 dmx[this.start_channel + 1] = this.alpha;
 
+                // Position.
+                let rotation = this.output_rotation();
+                write_16bit(dmx, this.start_channel + 3, rotation.pan);
+                write_16bit(dmx, this.start_channel + 5, rotation.tilt);
+
                 // Color.
-                dmx[this.start_channel + 9] = this.color.r;
-                dmx[this.start_channel + 10] = this.color.g;
-                dmx[this.start_channel + 11] = this.color.b;
+                let color = this.emitters(false, false);
+                dmx[this.start_channel + 9] = color.r;
+                dmx[this.start_channel + 10] = color.g;
+                dmx[this.start_channel + 11] = color.b;
             }
         }
         // match self {
@@ -32,10 +53,21 @@ impl MovingHead {
     }
 
     pub fn blackout(&self, this: &Fixture, dmx: &mut [u8]) {
-        // match self {
-        //     MovingHead::Generic3ChanNoAlpha => todo!(),
-        //     MovingHead::Generic4ChanWithAlpha => todo!(),
-        // }
+        match self {
+            MovingHead::MartinMacAura => {
+                dmx[this.start_channel + 0] = 0;
+                dmx[this.start_channel + 1] = 0;
+
+                // Keeps moving while dark, so that it is in place once the output is restored.
+                let rotation = this.output_rotation();
+                write_16bit(dmx, this.start_channel + 3, rotation.pan);
+                write_16bit(dmx, this.start_channel + 5, rotation.tilt);
+
+                dmx[this.start_channel + 9] = 0;
+                dmx[this.start_channel + 10] = 0;
+                dmx[this.start_channel + 11] = 0;
+            }
+        }
     }
 
     pub fn setup(&self, this: &Fixture, time: Time, dmx: &mut [u8]) {
@@ -47,3 +79,67 @@ impl MovingHead {
         // }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(dmx: &mut [u8]) -> Fixture {
+        let mut fixture = Fixture::new(1, MovingHead::MartinMacAura.into());
+        fixture.set_alpha(200, dmx);
+        fixture.set_color((255, 0, 64).into(), dmx);
+        fixture.set_tilt_pan(0x1234, 0xabcd, dmx);
+        fixture.set_burst(true, dmx);
+        fixture
+    }
+
+    #[test]
+    fn blackout_keeps_the_position() {
+        let mut dmx = [0; 513];
+        let mut fixture = lit(&mut dmx);
+        assert_eq!(
+            dmx[0..14],
+            [0, 255, 200, 0, 0xab, 0xcd, 0x12, 0x34, 0, 0, 255, 0, 64, 0]
+        );
+
+        fixture.blackout(&mut dmx);
+        assert_eq!(
+            dmx[0..14],
+            [0, 0, 0, 0, 0xab, 0xcd, 0x12, 0x34, 0, 0, 0, 0, 0, 0]
+        );
+
+        fixture.write(&mut dmx);
+        assert_eq!(
+            dmx[0..14],
+            [0, 255, 200, 0, 0xab, 0xcd, 0x12, 0x34, 0, 0, 255, 0, 64, 0]
+        );
+    }
+
+    #[test]
+    fn home_is_dark_and_centered() {
+        let mut dmx = [0; 513];
+        let mut fixture = lit(&mut dmx);
+
+        fixture.home(&mut dmx);
+        assert_eq!(
+            dmx[0..14],
+            [0, 0, 0, 0, 0x80, 0x00, 0x80, 0x00, 0, 0, 0, 0, 0, 0]
+        );
+
+        fixture.write(&mut dmx);
+        assert_eq!(
+            dmx[0..14],
+            [0, 0, 0, 0, 0x80, 0x00, 0x80, 0x00, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn inverted_axes() {
+        let mut dmx = [0; 513];
+        let mut fixture = lit(&mut dmx);
+        fixture.movement.invert_pan = true;
+
+        fixture.blackout(&mut dmx);
+        assert_eq!(dmx[4..8], [0x54, 0x32, 0x12, 0x34]);
+    }
+}
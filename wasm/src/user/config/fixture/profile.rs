@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::color::{Color, Emitters};
+
+use super::Fixture;
+
+//
+// Data-driven fixture profiles, usually generated by the host from the Open Fixture Library.
+// The channel layout of a mode is resolved once after the patch is loaded.
+//
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelKind {
+    Intensity,
+    Red,
+    Green,
+    Blue,
+    White,
+    Amber,
+    Uv,
+    Pan,
+    PanFine,
+    Tilt,
+    TiltFine,
+    Strobe,
+    ColorWheel,
+    Generic,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapabilityKind {
+    Open,
+    Closed,
+    Strobe,
+    Color { r: u8, g: u8, b: u8 },
+    Other,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Capability {
+    pub start: u8,
+    pub end: u8,
+    pub kind: CapabilityKind,
+    #[serde(default)]
+    pub label: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Channel {
+    pub name: String,
+    pub kind: ChannelKind,
+    #[serde(default)]
+    pub default: u8,
+    /// Full range of pan and tilt channels in degrees.
+    #[serde(default)]
+    pub degrees: Option<f32>,
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Mode {
+    pub name: String,
+    pub channels: Vec<Option<String>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Profile {
+    pub name: String,
+    pub channels: Vec<Channel>,
+    /// If empty, all channels in order form the only mode.
+    #[serde(default)]
+    pub modes: Vec<Mode>,
+}
+
+/// Channels by offset from the start channel of the fixture.
+pub type Layout = Vec<Option<Channel>>;
+
+impl Profile {
+    /// Uses the first mode if none is given.
+    pub fn layout(&self, mode: Option<&str>) -> Result<Layout, String> {
+        if self.modes.is_empty() {
+            return match mode {
+                None => Ok(self.channels.iter().cloned().map(Some).collect()),
+                Some(mode) => Err(format!("profile <{}> has no mode <{mode}>", self.name)),
+            };
+        }
+
+        let found = match mode {
+            None => self.modes.first(),
+            Some(mode) => self.modes.iter().find(|m| m.name == mode),
+        };
+        let Some(found) = found else {
+            return Err(format!(
+                "profile <{}> has no mode <{}>",
+                self.name,
+                mode.unwrap_or_default()
+            ));
+        };
+
+        found
+            .channels
+            .iter()
+            .map(|name| {
+                let Some(name) = name else {
+                    return Ok(None);
+                };
+
+                self.channels
+                    .iter()
+                    .find(|channel| &channel.name == name)
+                    .cloned()
+                    .map(Some)
+                    .ok_or_else(|| format!("profile <{}> has no channel <{name}>", self.name))
+            })
+            .collect()
+    }
+}
+
+pub type Profiles = HashMap<String, Profile>;
+
+impl Channel {
+    fn capability(&self, kind: CapabilityKind) -> Option<&Capability> {
+        self.capabilities
+            .iter()
+            .find(|capability| capability.kind == kind)
+    }
+
+    fn value(&self, this: &Fixture, color: &Emitters) -> u8 {
+        match self.kind {
+            ChannelKind::Intensity => this.alpha,
+            ChannelKind::Red => color.r,
+            ChannelKind::Green => color.g,
+            ChannelKind::Blue => color.b,
+            ChannelKind::White => color.white,
+            ChannelKind::Amber => color.amber,
+            ChannelKind::Uv => color.uv,
+            ChannelKind::Pan => this.output_rotation().pan.to_be_bytes()[0],
+            ChannelKind::PanFine => this.output_rotation().pan.to_be_bytes()[1],
+            ChannelKind::Tilt => this.output_rotation().tilt.to_be_bytes()[0],
+            ChannelKind::TiltFine => this.output_rotation().tilt.to_be_bytes()[1],
+            ChannelKind::Strobe => self.strobe(this.strobe_state),
+            ChannelKind::ColorWheel => self.wheel_slot(this.color).unwrap_or(self.default),
+            ChannelKind::Generic => self.default,
+        }
+    }
+
+    fn strobe(&self, on: bool) -> u8 {
+        if on {
+            // The fastest strobe speed.
+            return self
+                .capability(CapabilityKind::Strobe)
+                .map_or(255, |capability| capability.end);
+        }
+
+        self.capability(CapabilityKind::Open)
+            .map_or(self.default, |capability| capability.start)
+    }
+
+    // The slot closest to the requested color, the wheel does not dim.
+    fn wheel_slot(&self, color: Color) -> Option<u8> {
+        let full = |color: Color| {
+            let (h, s, _) = color.to_hsv();
+            Color::from_hsv(h, s, 1.0)
+        };
+        let color = full(color);
+
+        self.capabilities
+            .iter()
+            .filter_map(|capability| match capability.kind {
+                CapabilityKind::Color { r, g, b } => {
+                    let Color { r, g, b } = full((r, g, b).into());
+                    let distance = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+                    let d = distance(r, color.r) + distance(g, color.g) + distance(b, color.b);
+                    Some((d, capability.start))
+                }
+                _ => None,
+            })
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, value)| value)
+    }
+}
+
+pub fn tilt_pan_range(layout: &Layout) -> (Option<f32>, Option<f32>) {
+    let degrees = |kind: ChannelKind| {
+        layout
+            .iter()
+            .flatten()
+            .find(|channel| channel.kind == kind)
+            .and_then(|channel| channel.degrees)
+    };
+
+    (degrees(ChannelKind::Tilt), degrees(ChannelKind::Pan))
+}
+
+fn has(layout: &Layout, kind: ChannelKind) -> bool {
+    layout.iter().flatten().any(|channel| channel.kind == kind)
+}
+
+fn emitters(layout: &Layout, this: &Fixture) -> Emitters {
+    this.emitters(
+        has(layout, ChannelKind::White),
+        has(layout, ChannelKind::Amber),
+    )
+}
+
+pub fn write(layout: &Layout, this: &Fixture, dmx: &mut [u8]) {
+    let color = emitters(layout, this);
+
+    for (offset, channel) in layout.iter().enumerate() {
+        if let Some(channel) = channel {
+            dmx[this.start_channel + offset] = channel.value(this, &color);
+        }
+    }
+}
+
+pub fn blackout(layout: &Layout, this: &Fixture, dmx: &mut [u8]) {
+    let has_intensity = has(layout, ChannelKind::Intensity);
+    let color = emitters(layout, this);
+
+    for (offset, channel) in layout.iter().enumerate() {
+        let Some(channel) = channel else {
+            continue;
+        };
+
+        dmx[this.start_channel + offset] = match channel.kind {
+            ChannelKind::Intensity => 0,
+            // Without a master dimmer, the color channels are the intensity.
+            ChannelKind::Red
+            | ChannelKind::Green
+            | ChannelKind::Blue
+            | ChannelKind::White
+            | ChannelKind::Amber
+            | ChannelKind::Uv
+                if !has_intensity =>
+            {
+                0
+            }
+            ChannelKind::Strobe => channel
+                .capability(CapabilityKind::Closed)
+                .map_or(0, |capability| capability.start),
+            _ => channel.value(this, &color),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::FixtureType;
+    use super::*;
+
+    fn channel(kind: ChannelKind, default: u8, capabilities: Vec<Capability>) -> Option<Channel> {
+        Some(Channel {
+            name: format!("{kind:?}"),
+            kind,
+            default,
+            degrees: None,
+            capabilities,
+        })
+    }
+
+    fn capability(start: u8, end: u8, kind: CapabilityKind) -> Capability {
+        Capability {
+            start,
+            end,
+            kind,
+            label: String::new(),
+        }
+    }
+
+    fn profile(intensity: bool) -> Fixture {
+        let mut layout = vec![
+            channel(ChannelKind::Red, 0, vec![]),
+            channel(ChannelKind::Green, 0, vec![]),
+            channel(ChannelKind::Blue, 0, vec![]),
+            channel(
+                ChannelKind::Strobe,
+                0,
+                vec![
+                    capability(0, 9, CapabilityKind::Closed),
+                    capability(10, 19, CapabilityKind::Open),
+                    capability(20, 250, CapabilityKind::Strobe),
+                ],
+            ),
+            channel(ChannelKind::Pan, 0, vec![]),
+            channel(ChannelKind::PanFine, 0, vec![]),
+            None,
+            channel(ChannelKind::Generic, 7, vec![]),
+        ];
+        if intensity {
+            layout.insert(0, channel(ChannelKind::Intensity, 0, vec![]));
+        }
+
+        let mut fixture = Fixture::new(
+            1,
+            FixtureType::Profile {
+                profile: "test".to_string(),
+                mode: None,
+                layout,
+            },
+        );
+        fixture.rotation.pan = 0x1234;
+        fixture
+    }
+
+    #[test]
+    fn blackout_without_intensity_clears_the_colors() {
+        let mut dmx = [0; 513];
+        let mut fixture = profile(false);
+        fixture.set_color((255, 128, 0).into(), &mut dmx);
+        assert_eq!(dmx[0..10], [0, 255, 128, 0, 10, 0x12, 0x34, 0, 7, 0]);
+
+        fixture.blackout(&mut dmx);
+        assert_eq!(dmx[0..10], [0, 0, 0, 0, 0, 0x12, 0x34, 0, 7, 0]);
+
+        fixture.write(&mut dmx);
+        assert_eq!(dmx[0..10], [0, 255, 128, 0, 10, 0x12, 0x34, 0, 7, 0]);
+    }
+
+    #[test]
+    fn blackout_with_intensity_keeps_the_colors() {
+        let mut dmx = [0; 513];
+        let mut fixture = profile(true);
+        fixture.set_color((255, 128, 0).into(), &mut dmx);
+        fixture.set_alpha(200, &mut dmx);
+        fixture.set_burst(true, &mut dmx);
+        assert_eq!(dmx[0..11], [0, 200, 255, 128, 0, 250, 0x12, 0x34, 0, 7, 0]);
+
+        fixture.blackout(&mut dmx);
+        assert_eq!(dmx[0..11], [0, 0, 255, 128, 0, 0, 0x12, 0x34, 0, 7, 0]);
+    }
+
+    #[test]
+    fn home_is_dark_and_centered() {
+        let mut dmx = [0; 513];
+        let mut fixture = profile(true);
+        fixture.set_color((255, 128, 0).into(), &mut dmx);
+        fixture.set_alpha(200, &mut dmx);
+
+        fixture.home(&mut dmx);
+        assert_eq!(dmx[0..11], [0, 0, 0, 0, 0, 0, 0x80, 0x00, 0, 7, 0]);
+
+        fixture.write(&mut dmx);
+        assert_eq!(dmx[0..11], [0, 0, 0, 0, 0, 10, 0x80, 0x00, 0, 7, 0]);
+    }
+
+    fn patched(start_channel: usize) -> (Fixture, Result<(), String>) {
+        let profiles = Profiles::from([(
+            "rgb".to_string(),
+            Profile {
+                name: "RGB".to_string(),
+                channels: [ChannelKind::Red, ChannelKind::Green, ChannelKind::Blue]
+                    .into_iter()
+                    .flat_map(|kind| channel(kind, 0, vec![]))
+                    .collect(),
+                modes: vec![],
+            },
+        )]);
+
+        let mut fixture = Fixture::new(
+            start_channel,
+            FixtureType::Profile {
+                profile: "rgb".to_string(),
+                mode: None,
+                layout: vec![],
+            },
+        );
+        let resolved = fixture.resolve_profile(&profiles);
+        (fixture, resolved)
+    }
+
+    #[test]
+    fn fixtures_must_fit_into_the_universe() {
+        let mut dmx = [0; 513];
+
+        let (mut fixture, resolved) = patched(510);
+        assert_eq!(resolved, Ok(()));
+        fixture.set_color((255, 128, 1).into(), &mut dmx);
+        assert_eq!(dmx[510..], [255, 128, 1]);
+
+        for start_channel in [0, 511] {
+            let (mut fixture, resolved) = patched(start_channel);
+            assert!(resolved.is_err());
+
+            // Without a layout the fixture writes nothing.
+            let mut dmx = [0; 513];
+            fixture.set_color((255, 128, 1).into(), &mut dmx);
+            assert!(dmx.iter().all(|value| *value == 0));
+        }
+    }
+}
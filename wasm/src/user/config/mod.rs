@@ -1,22 +1,49 @@
+mod cue;
 mod fixture;
+mod palette;
+mod sequence;
 
+pub use cue::*;
 pub use fixture::*;
+pub use palette::*;
+pub use sequence::*;
 
 use serde::Deserialize;
 
-use crate::color::Color;
+use crate::{
+    color::Color,
+    effect::{Effect, EffectClock, Modulation},
+    log_error,
+};
 
 use super::{
-    clock::Time,
+    beat::BeatSettings,
+    clock::{ClockSettings, Time},
     println,
     state::strobe::{StrobeGroupState, StrobeState},
 };
 
+fn enabled_default() -> bool {
+    true
+}
+
 #[derive(Deserialize, Debug)]
 pub struct FixtureGroup {
+    #[serde(default = "enabled_default")]
     pub enabled: bool,
     pub label: String,
     pub fixtures: Vec<Fixture>,
+    /// Mood groups use this palette instead of the selected one.
+    #[serde(default)]
+    pub palette: Option<String>,
+    /// Layered in order on every tick.
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+    #[serde(default)]
+    pub sequence: Option<Sequence>,
+    /// Clock of the sequence and the effects, the main clock if not set.
+    #[serde(default)]
+    pub clock: Option<String>,
 }
 
 impl FixtureGroup {
@@ -25,12 +52,26 @@ impl FixtureGroup {
             enabled: true,
             label,
             fixtures,
+            palette: None,
+            effects: vec![],
+            sequence: None,
+            clock: None,
         }
     }
 
+    /// Disabled groups are dark, enabling restores their last state.
     pub fn set_enabled(&mut self, dmx: &mut [u8], enabled: bool) {
         self.enabled = enabled;
-        // TODO: should call blackout?
+        match enabled {
+            true => self.write(dmx),
+            false => self.blackout(dmx),
+        }
+    }
+
+    pub fn write(&self, dmx: &mut [u8]) {
+        for fixture in self.fixtures.iter() {
+            fixture.write(dmx);
+        }
     }
 
     pub fn blackout(&mut self, dmx: &mut [u8]) {
@@ -39,6 +80,51 @@ impl FixtureGroup {
         }
     }
 
+    pub fn home(&mut self, dmx: &mut [u8]) {
+        for fixture in self.fixtures.iter_mut() {
+            fixture.home(dmx);
+        }
+    }
+
+    /// Writes all fixtures with the scenes of the active cues, the sequence and the effects
+    /// on top of their state. Scenes are layered in order, each faded in by its weight.
+    /// `sequence` and `effects` are `None` while disabled.
+    pub fn write_output(
+        &mut self,
+        scenes: &[(&SceneGroup, f32)],
+        sequence: Option<EffectClock>,
+        effects: Option<EffectClock>,
+        dmx: &mut [u8],
+    ) {
+        let sequence = self.sequence.as_ref().zip(sequence);
+        let effects = effects.filter(|_| !self.effects.is_empty());
+        if scenes.is_empty() && sequence.is_none() && effects.is_none() {
+            return;
+        }
+
+        let count = self.fixtures.len();
+        for (index, fixture) in self.fixtures.iter_mut().enumerate() {
+            let mut look = fixture.look();
+            for (scene, weight) in scenes {
+                look = scene.blend(fixture, look, *weight);
+            }
+
+            if let Some((sequence, clock)) = sequence {
+                look = sequence.apply(clock.beats, index, count, look);
+            }
+
+            if let Some(clock) = effects {
+                let mut modulation = Modulation::default();
+                for effect in self.effects.iter() {
+                    effect.apply(clock, index, count, &mut modulation);
+                }
+                look = fixture.modulate(look, &modulation);
+            }
+
+            fixture.write_look(look, dmx);
+        }
+    }
+
     pub fn set_color(&mut self, color: Color, dmx: &mut [u8]) {
         for fixture in self.fixtures.iter_mut() {
             fixture.set_color(color, dmx);
@@ -51,12 +137,18 @@ impl FixtureGroup {
         }
     }
 
-    pub fn set_tilt_pan(&mut self, tilt: u8, pan: u8, dmx: &mut [u8]) {
+    pub fn set_tilt_pan(&mut self, tilt: u16, pan: u16, dmx: &mut [u8]) {
         for fixture in self.fixtures.iter_mut() {
             fixture.set_tilt_pan(tilt, pan, dmx);
         }
     }
 
+    pub fn set_position(&mut self, tilt: f32, pan: f32, dmx: &mut [u8]) {
+        for fixture in self.fixtures.iter_mut() {
+            fixture.set_position(tilt, pan, dmx);
+        }
+    }
+
     pub fn setup(&mut self, time: Time, dmx: &mut [u8]) {
         for fixture in self.fixtures.iter_mut() {
             fixture.setup(time, dmx);
@@ -66,7 +158,9 @@ impl FixtureGroup {
 
 #[derive(Deserialize, Debug)]
 pub struct StrobeGroup {
+    #[serde(default)]
     pub supports_burst: bool,
+    #[serde(flatten)]
     pub group: FixtureGroup,
     #[serde(skip)]
     pub state: StrobeGroupState,
@@ -113,8 +207,12 @@ impl StrobeGroup {
         self.group.set_alpha(alpha, dmx);
     }
 
-    pub fn set_tilt_pan(&mut self, tilt: u8, pan: u8, dmx: &mut [u8]) {
-        self.group.blackout(dmx);
+    pub fn set_tilt_pan(&mut self, tilt: u16, pan: u16, dmx: &mut [u8]) {
+        self.group.set_tilt_pan(tilt, pan, dmx);
+    }
+
+    pub fn set_position(&mut self, tilt: f32, pan: f32, dmx: &mut [u8]) {
+        self.group.set_position(tilt, pan, dmx);
     }
 
     pub fn setup(&mut self, time: Time, dmx: &mut [u8]) {
@@ -127,11 +225,80 @@ impl StrobeGroup {
 pub type MoodGroup = FixtureGroup;
 pub type DimmerGroup = FixtureGroup;
 
+/// The fixture patch, deserialized from the JSON passed by the host.
 #[derive(Deserialize, Debug)]
+#[serde(default)]
 pub struct Config {
     pub strobe_groups: Vec<StrobeGroup>,
     pub mood_groups: Vec<MoodGroup>,
     pub dimmer_groups: Vec<DimmerGroup>,
+    /// Fixture profiles by name, including the ones imported by the host.
+    pub profiles: Profiles,
+    #[serde(default = "Palette::builtin")]
+    pub palettes: Vec<Palette>,
+    pub scenes: Vec<Scene>,
+    pub cue_lists: Vec<CueList>,
+    /// Clocks besides the main clock.
+    pub clocks: Vec<ClockSettings>,
+    /// Beat and drop filter.
+    pub beat: BeatSettings,
+}
+
+impl Config {
+    /// Used if the host has no patch.
+    pub fn builtin() -> Self {
+        Self {
+            strobe_groups: vec![StrobeGroup::new(
+                true,
+                FixtureGroup::new(
+                    "Primary Strobe".into(),
+                    vec![(MovingHead::MartinMacAura.into(), 40).into()],
+                ),
+            )],
+            mood_groups: vec![FixtureGroup::new(
+                "Primary Mood".into(),
+                vec![(Light::LEDPartyTCLSpot.into(), 20).into()],
+            )],
+            dimmer_groups: vec![FixtureGroup::new(
+                "Primary Dimmer".into(),
+                vec![(Light::Generic4ChanWithAlpha.into(), 30).into()],
+            )],
+            profiles: Profiles::default(),
+            palettes: Palette::builtin(),
+            scenes: vec![],
+            cue_lists: vec![],
+            clocks: vec![],
+            beat: BeatSettings::default(),
+        }
+    }
+
+    /// All groups, including the ones of the strobe groups.
+    pub fn groups_mut(&mut self) -> impl Iterator<Item = &mut FixtureGroup> {
+        self.strobe_groups
+            .iter_mut()
+            .map(|strobe| &mut strobe.group)
+            .chain(self.mood_groups.iter_mut())
+            .chain(self.dimmer_groups.iter_mut())
+    }
+
+    /// Resolves the channel layout of all profile fixtures.
+    /// Fixtures with an unknown profile or mode have no channels.
+    pub fn resolve_profiles(&mut self) {
+        let groups = self
+            .strobe_groups
+            .iter_mut()
+            .map(|strobe| &mut strobe.group)
+            .chain(self.mood_groups.iter_mut())
+            .chain(self.dimmer_groups.iter_mut());
+
+        for group in groups {
+            for fixture in group.fixtures.iter_mut() {
+                if let Err(err) = fixture.resolve_profile(&self.profiles) {
+                    log_error!("[SETUP] Fixture in group <{}>: {err}", group.label);
+                }
+            }
+        }
+    }
 }
 
 impl Default for Config {
@@ -140,6 +307,12 @@ impl Default for Config {
             strobe_groups: Default::default(),
             mood_groups: Default::default(),
             dimmer_groups: Default::default(),
+            profiles: Default::default(),
+            palettes: Default::default(),
+            scenes: Default::default(),
+            cue_lists: Default::default(),
+            clocks: Default::default(),
+            beat: Default::default(),
         }
     }
 }
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+
+use crate::color::Color;
+
+//
+// Color palettes of the mood groups.
+// A palette steps through its colors, or fades between them if it is a gradient.
+//
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum PaletteColor {
+    Rgb(u8, u8, u8),
+    /// Fully saturated hue in degrees.
+    Hue(f32),
+    Hsv(f32, f32, f32),
+    Hsl(f32, f32, f32),
+    Kelvin(f32),
+}
+
+impl PaletteColor {
+    pub fn color(&self) -> Color {
+        match *self {
+            PaletteColor::Rgb(r, g, b) => (r, g, b).into(),
+            PaletteColor::Hue(h) => Color::from_hsv(h, 1.0, 1.0),
+            PaletteColor::Hsv(h, s, v) => Color::from_hsv(h, s, v),
+            PaletteColor::Hsl(h, s, l) => Color::from_hsl(h, s, l),
+            PaletteColor::Kelvin(kelvin) => Color::from_kelvin(kelvin),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum PaletteCycle {
+    Off,
+    /// Advances every n beats of the beat clock.
+    Beats(u32),
+    /// Advances every n milliseconds.
+    Millis(u32),
+}
+
+impl Default for PaletteCycle {
+    fn default() -> Self {
+        Self::Beats(1)
+    }
+}
+
+fn steps_default() -> u32 {
+    1
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Palette {
+    pub name: String,
+    pub colors: Vec<PaletteColor>,
+    /// Steps from one color to the next, colors are faded if greater than one.
+    #[serde(default = "steps_default")]
+    pub steps: u32,
+    #[serde(default)]
+    pub cycle: PaletteCycle,
+}
+
+impl Palette {
+    fn new(name: &str, colors: Vec<PaletteColor>, steps: u32, cycle: PaletteCycle) -> Self {
+        Self {
+            name: name.to_string(),
+            colors,
+            steps,
+            cycle,
+        }
+    }
+
+    /// Used if the patch has no palettes.
+    pub fn builtin() -> Vec<Self> {
+        use PaletteColor::*;
+
+        vec![
+            Self::new(
+                "All",
+                vec![Hue(0.0), Hue(120.0), Hue(240.0)],
+                120,
+                PaletteCycle::Millis(100),
+            ),
+            Self::new(
+                "Cyan Magenta",
+                vec![Hue(180.0), Hue(345.0)],
+                1,
+                PaletteCycle::Beats(1),
+            ),
+            Self::new(
+                "Orange Blue",
+                vec![Hue(240.0), Hue(40.0)],
+                1,
+                PaletteCycle::Beats(1),
+            ),
+            Self::new("White", vec![Rgb(255, 255, 255)], 1, PaletteCycle::Off),
+        ]
+    }
+
+    /// Color at a step counter, wraps around.
+    pub fn color_at(&self, step: u64) -> Color {
+        if self.colors.is_empty() {
+            return Color::white();
+        }
+
+        let steps = self.steps.max(1) as u64;
+        let len = self.colors.len() as u64;
+        let index = (step / steps) % len;
+
+        let from = self.colors[index as usize].color();
+        let to = self.colors[((index + 1) % len) as usize].color();
+
+        fade(from, to, (step % steps) as f32 / steps as f32)
+    }
+}
+
+// Fades through the hue circle along the shorter way.
+fn fade(from: Color, to: Color, t: f32) -> Color {
+    if t == 0.0 {
+        return from;
+    }
+
+    let (h0, s0, v0) = from.to_hsv();
+    let (h1, s1, v1) = to.to_hsv();
+
+    let mut dh = h1 - h0;
+    if dh > 180.0 {
+        dh -= 360.0;
+    } else if dh < -180.0 {
+        dh += 360.0;
+    }
+
+    let lerp = |a: f32, b: f32| a + (b - a) * t;
+    Color::from_hsv(h0 + dh * t, lerp(s0, s1), lerp(v0, v1))
+}
@@ -0,0 +1,267 @@
+use serde::Deserialize;
+
+use crate::effect::random;
+
+use super::{Look, PaletteColor};
+
+//
+// Step sequencer, steps through patterns of fixture states in time with the beat clock.
+// A step only scales the alpha of a fixture and may replace its color, so that it follows the mood.
+//
+
+// A quarter bar to four bars, in beats of a 4/4 bar.
+const MIN_LENGTH: f32 = 1.0;
+const MAX_LENGTH: f32 = 16.0;
+const MAX_SWING: f32 = 0.75;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    /// Forward, then backward, without repeating the first and last step.
+    Bounce,
+    Random,
+}
+
+impl Default for Direction {
+    fn default() -> Self {
+        Self::Forward
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Step {
+    /// Alpha per fixture, repeated across larger groups. Fixtures without a value are at full.
+    pub alpha: Vec<u8>,
+    #[serde(default)]
+    pub color: Option<PaletteColor>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum Pattern {
+    /// Even and odd fixtures take turns.
+    Alternating,
+    /// One fixture after the other, from the first to the last of the group.
+    Snake,
+    Steps(Vec<Step>),
+}
+
+fn length_default() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Sequence {
+    pub pattern: Pattern,
+    /// Beats per step, from 1 (a quarter bar) to 16 (four bars).
+    #[serde(default = "length_default")]
+    pub length: f32,
+    #[serde(default)]
+    pub direction: Direction,
+    /// Delays every second step by this share of a step, from 0.0 to 0.75.
+    #[serde(default)]
+    pub swing: f32,
+}
+
+impl Sequence {
+    fn step_count(&self, fixtures: usize) -> usize {
+        match &self.pattern {
+            Pattern::Alternating => 2,
+            Pattern::Snake => fixtures,
+            Pattern::Steps(steps) => steps.len(),
+        }
+    }
+
+    // Steps since the start, steps are played in pairs so that the second one can swing.
+    fn counter(&self, beats: f32) -> u64 {
+        let length = self.length.clamp(MIN_LENGTH, MAX_LENGTH);
+        let swing = self.swing.clamp(0.0, MAX_SWING);
+
+        let pairs = (beats.max(0.0) / (2.0 * length)).floor();
+        let within = beats - pairs * 2.0 * length;
+
+        pairs as u64 * 2 + (within >= length * (1.0 + swing)) as u64
+    }
+
+    fn index(&self, counter: u64, count: usize) -> usize {
+        let count = count as u64;
+
+        let index = match self.direction {
+            Direction::Forward => counter % count,
+            Direction::Bounce if count > 1 => {
+                let period = 2 * (count - 1);
+                match counter % period {
+                    step if step < count => step,
+                    step => period - step,
+                }
+            }
+            Direction::Bounce => 0,
+            Direction::Random => (random(counter as i64, 0) * count as f32) as u64 % count,
+        };
+
+        index as usize
+    }
+
+    /// Applies the current step to the look of the fixture at `index` of `count`.
+    pub fn apply(&self, beats: f32, index: usize, count: usize, look: Look) -> Look {
+        let steps = self.step_count(count);
+        if steps == 0 {
+            return look;
+        }
+
+        let step = self.index(self.counter(beats), steps);
+        let (alpha, color) = match &self.pattern {
+            Pattern::Alternating => (((index + step) % 2 == 0) as u8 * u8::MAX, None),
+            Pattern::Snake => ((index == step) as u8 * u8::MAX, None),
+            Pattern::Steps(steps) => {
+                let step = &steps[step];
+                let alpha = match step.alpha.len() {
+                    0 => u8::MAX,
+                    len => step.alpha[index % len],
+                };
+                (alpha, step.color)
+            }
+        };
+
+        Look {
+            alpha: (look.alpha as u16 * alpha as u16 / u8::MAX as u16) as u8,
+            color: color.map_or(look.color, |color| color.color()),
+            ..look
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{color::Color, user::config::Rotation};
+
+    use super::*;
+
+    fn sequence(pattern: Pattern, length: f32, direction: Direction, swing: f32) -> Sequence {
+        Sequence {
+            pattern,
+            length,
+            direction,
+            swing,
+        }
+    }
+
+    fn indices(direction: Direction, count: usize) -> Vec<usize> {
+        let sequence = sequence(Pattern::Snake, 1.0, direction, 0.0);
+        (0..10)
+            .map(|counter| sequence.index(counter, count))
+            .collect()
+    }
+
+    fn look(alpha: u8) -> Look {
+        Look {
+            alpha,
+            color: Color::white(),
+            rotation: Rotation::home(),
+        }
+    }
+
+    #[test]
+    fn forward_and_bounce() {
+        assert_eq!(
+            indices(Direction::Forward, 3),
+            [0, 1, 2, 0, 1, 2, 0, 1, 2, 0]
+        );
+        assert_eq!(
+            indices(Direction::Bounce, 4),
+            [0, 1, 2, 3, 2, 1, 0, 1, 2, 3]
+        );
+        assert_eq!(
+            indices(Direction::Bounce, 2),
+            [0, 1, 0, 1, 0, 1, 0, 1, 0, 1]
+        );
+        assert_eq!(indices(Direction::Bounce, 1), [0; 10]);
+    }
+
+    #[test]
+    fn random_is_stable_and_in_range() {
+        let first = indices(Direction::Random, 5);
+        assert_eq!(first, indices(Direction::Random, 5));
+        assert!(first.iter().all(|index| *index < 5));
+        // Not stuck on one step.
+        assert!(first.iter().any(|index| *index != first[0]));
+    }
+
+    #[test]
+    fn steps_last_length_beats() {
+        let sequence = sequence(Pattern::Alternating, 2.0, Direction::Forward, 0.0);
+        let counters: Vec<_> = [0.0, 1.9, 2.0, 3.9, 4.0, 8.5]
+            .into_iter()
+            .map(|beats| sequence.counter(beats))
+            .collect();
+        assert_eq!(counters, [0, 0, 1, 1, 2, 4]);
+    }
+
+    #[test]
+    fn length_is_clamped_to_bars() {
+        // A quarter bar at least.
+        let short = sequence(Pattern::Alternating, 0.25, Direction::Forward, 0.0);
+        assert_eq!(short.counter(0.9), 0);
+        assert_eq!(short.counter(1.0), 1);
+
+        // Four bars at most.
+        let long = sequence(Pattern::Alternating, 64.0, Direction::Forward, 0.0);
+        assert_eq!(long.counter(15.9), 0);
+        assert_eq!(long.counter(16.0), 1);
+    }
+
+    #[test]
+    fn swing_delays_every_second_step() {
+        let swung = sequence(Pattern::Alternating, 1.0, Direction::Forward, 0.5);
+        let counters: Vec<_> = [0.0, 1.0, 1.4, 1.5, 2.0, 3.5]
+            .into_iter()
+            .map(|beats| swung.counter(beats))
+            .collect();
+        assert_eq!(counters, [0, 0, 0, 1, 2, 3]);
+
+        // At most three quarters of a step.
+        let clamped = sequence(Pattern::Alternating, 1.0, Direction::Forward, 2.0);
+        assert_eq!(clamped.counter(1.7), 0);
+        assert_eq!(clamped.counter(1.75), 1);
+    }
+
+    #[test]
+    fn patterns_scale_the_alpha() {
+        let alternating = sequence(Pattern::Alternating, 1.0, Direction::Forward, 0.0);
+        let alphas: Vec<_> = (0..4)
+            .map(|index| alternating.apply(1.0, index, 4, look(200)).alpha)
+            .collect();
+        assert_eq!(alphas, [0, 200, 0, 200]);
+
+        let snake = sequence(Pattern::Snake, 1.0, Direction::Forward, 0.0);
+        let alphas: Vec<_> = (0..4)
+            .map(|index| snake.apply(2.0, index, 4, look(255)).alpha)
+            .collect();
+        assert_eq!(alphas, [0, 0, 255, 0]);
+    }
+
+    #[test]
+    fn steps_repeat_across_the_group() {
+        let steps = Pattern::Steps(vec![
+            Step {
+                alpha: vec![255, 0],
+                color: Some(PaletteColor::Rgb(255, 0, 0)),
+            },
+            Step {
+                alpha: vec![],
+                color: None,
+            },
+        ]);
+        let sequence = sequence(steps, 1.0, Direction::Forward, 0.0);
+
+        let first: Vec<_> = (0..3)
+            .map(|index| sequence.apply(0.0, index, 3, look(100)))
+            .map(|look| (look.alpha, look.color))
+            .collect();
+        let red = Color::from((255, 0, 0));
+        assert_eq!(first, [(100, red), (0, red), (100, red)]);
+
+        // Without alpha values all fixtures are at full, the color is kept.
+        let second = sequence.apply(1.0, 1, 3, look(100));
+        assert_eq!((second.alpha, second.color), (100, Color::white()));
+    }
+}
@@ -0,0 +1,232 @@
+use crate::{
+    blaulicht::{ControlEvent, ControlValue},
+    println,
+};
+
+use super::{beat::FilterSensitivity, cues, palette, state::State};
+
+//
+// Dispatches the named controls of the host MIDI mapping.
+// Group toggles use the group label: `group.<label>.enabled`.
+// Clock controls use the clock name: `clock.<name>.multiplier`, `.bpm` and `.phase`.
+// Taps are handled by the host (`tempo.tap`) and reach every clock through the tempo.
+// Beat filter controls take raw values from faders and steps from encoders, see `beat_control`.
+//
+
+pub fn tick(state: &mut State, dmx: &mut [u8], controls: &[ControlEvent]) {
+    for control in controls {
+        match (control.name.as_str(), control.value) {
+            ("strobe.brightness", ControlValue::Float(value)) => {
+                state.controls.strobe_brightness = fader_to_u8(value);
+            }
+            ("mood.brightness", ControlValue::Float(value)) => {
+                state.controls.mood_brightness = fader_to_u8(value);
+            }
+            ("mood.audio_brightness", ControlValue::Bool(value)) => {
+                state.controls.mood_audio_brightness = value;
+            }
+            ("dimmer.brightness", ControlValue::Float(value)) => {
+                state.controls.dimmer_brightness = fader_to_u8(value);
+            }
+            ("dimmer.duck_level", ControlValue::Float(value)) => {
+                state.controls.dimmer_duck_level = fader_to_u8(value);
+            }
+            ("dimmer.strobe_duck", ControlValue::Bool(value)) => {
+                state.controls.dimmer_strobe_duck = value;
+            }
+            ("palette.next", ControlValue::Bool(true)) => palette::select_relative(state, 1),
+            ("palette.previous", ControlValue::Bool(true)) => palette::select_relative(state, -1),
+            ("palette.next" | "palette.previous", ControlValue::Bool(false)) => {}
+            ("palette.select", ControlValue::Relative(steps)) => {
+                palette::select_relative(state, steps);
+            }
+            // Faders select across all palettes.
+            ("palette.select", ControlValue::Float(value)) => {
+                let count = state.config.palettes.len();
+                let index = (value.clamp(0.0, 1.0) * count.saturating_sub(1) as f32).round();
+                palette::select(state, index as usize);
+            }
+            (name, ControlValue::Bool(value)) if name.starts_with("palette.") => {
+                let name = name.trim_start_matches("palette.");
+                if value && !palette::select_name(state, name) {
+                    println!("[CONTROLS] Unknown palette: {name}");
+                }
+            }
+            ("cue.go", ControlValue::Bool(true)) => cues::go(state),
+            ("cue.back", ControlValue::Bool(true)) => cues::back(state),
+            ("cue.release", ControlValue::Bool(true)) => cues::release(state),
+            ("cue.record", ControlValue::Bool(true)) => cues::record(state),
+            ("cue.go" | "cue.back" | "cue.release" | "cue.record", ControlValue::Bool(false)) => {}
+            ("cue.list", ControlValue::Relative(steps)) => cues::select_relative(state, steps),
+            ("effects.enabled", ControlValue::Bool(value)) => {
+                state.controls.effects_enabled = value;
+                if !value {
+                    restore_groups(state, dmx);
+                }
+            }
+            ("sequence.enabled", ControlValue::Bool(value)) => {
+                state.controls.sequences_enabled = value;
+                if !value {
+                    restore_groups(state, dmx);
+                }
+            }
+            ("master.blackout", ControlValue::Bool(value)) => {
+                set_blackout(state, dmx, value);
+            }
+            ("master.home", ControlValue::Bool(true)) => {
+                for group in state.config.groups_mut() {
+                    group.home(dmx);
+                }
+            }
+            ("master.home", ControlValue::Bool(false)) => {}
+            // Degrees from the center, the fader range is set in the mapping.
+            ("movement.tilt", ControlValue::Float(value)) => {
+                state.controls.tilt = value;
+                set_position(state, dmx);
+            }
+            ("movement.pan", ControlValue::Float(value)) => {
+                state.controls.pan = value;
+                set_position(state, dmx);
+            }
+            (name, ControlValue::Bool(enabled)) if is_group_toggle(name) => {
+                set_group_enabled(state, dmx, group_label(name), enabled);
+            }
+            (name, value) if name.starts_with("clock.") => clock_control(state, name, value),
+            (name, value) if name.starts_with("beat.") => beat_control(state, name, value),
+            _ => println!("[CONTROLS] Unknown control: {control:?}"),
+        }
+    }
+}
+
+fn fader_to_u8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0) as u8
+}
+
+fn is_group_toggle(name: &str) -> bool {
+    name.starts_with("group.") && name.ends_with(".enabled")
+}
+
+fn group_label(name: &str) -> &str {
+    name.trim_start_matches("group.").trim_end_matches(".enabled")
+}
+
+// The base state of the fixtures has to be written once effects or sequences stop.
+fn restore_groups(state: &mut State, dmx: &mut [u8]) {
+    for group in state.config.groups_mut().filter(|g| g.enabled) {
+        group.write(dmx);
+    }
+}
+
+// Multipliers a fader selects from.
+const CLOCK_MULTIPLIERS: [f32; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
+
+fn clock_control(state: &mut State, name: &str, value: ControlValue) {
+    let Some((clock, attribute)) = name.trim_start_matches("clock.").rsplit_once('.') else {
+        println!("[CONTROLS] Unknown control: {name}");
+        return;
+    };
+    let Some(clock) = state.clocks.get_mut(clock) else {
+        println!("[CONTROLS] Unknown clock: {clock}");
+        return;
+    };
+
+    match (attribute, value) {
+        // Every step doubles or halves the speed.
+        ("multiplier", ControlValue::Relative(steps)) => {
+            clock.set_multiplier(clock.multiplier() * 2f32.powi(steps));
+        }
+        ("multiplier", ControlValue::Float(value)) => {
+            let index = value.clamp(0.0, 1.0) * (CLOCK_MULTIPLIERS.len() - 1) as f32;
+            clock.set_multiplier(CLOCK_MULTIPLIERS[index.round() as usize]);
+        }
+        // Zero follows the audio again, the fader range is set in the mapping.
+        ("bpm", ControlValue::Float(bpm)) => clock.set_bpm(Some(bpm)),
+        ("phase", ControlValue::Float(phase)) => clock.set_phase(phase),
+        _ => println!("[CONTROLS] Unknown clock control: {name} {value:?}"),
+    }
+}
+
+// Faders set the value, encoders step it.
+fn adjust(current: f32, value: ControlValue, step: f32) -> f32 {
+    match value {
+        ControlValue::Float(value) => value,
+        ControlValue::Relative(steps) => current + steps as f32 * step,
+        ControlValue::Bool(_) => current,
+    }
+}
+
+// Thresholds are bass levels (10-255) in steps of 5, times are in ms (0-10000).
+fn beat_control(state: &mut State, name: &str, value: ControlValue) {
+    let filter = &mut state.beat_filter;
+
+    match (name.trim_start_matches("beat."), value) {
+        // Positive steps are more sensitive.
+        ("sensitivity", ControlValue::Relative(steps)) => {
+            filter.set_sensitivity(filter.sensitivity.step(steps));
+        }
+        ("sensitivity", ControlValue::Float(value)) => {
+            filter.set_sensitivity(FilterSensitivity::from_fader(value));
+        }
+        ("calibrate", ControlValue::Bool(calibrate)) => filter.set_calibrating(calibrate),
+        (_, ControlValue::Bool(_)) => println!("[CONTROLS] Unknown beat control: {name} {value:?}"),
+        ("cooldown", _) => {
+            let current = filter.cooldown().inner() as f32;
+            filter.set_cooldown(adjust(current, value, 100.0));
+        }
+        ("drop_begin", _) => {
+            let drop = &mut state.drop_filter;
+            let current = drop.begin_duration().inner() as f32;
+            drop.set_begin_duration(adjust(current, value, 250.0));
+        }
+        (threshold, _) => {
+            let mut thresholds = filter.thresholds();
+            let field = match threshold {
+                "bass_avg" => &mut thresholds.bass_avg,
+                "bass_peak" => &mut thresholds.bass_peak,
+                "bass" => &mut thresholds.bass,
+                "quiet_avg" => &mut thresholds.quiet_avg,
+                _ => {
+                    println!("[CONTROLS] Unknown beat control: {name} {value:?}");
+                    return;
+                }
+            };
+
+            *field = adjust(*field as f32, value, 5.0).clamp(0.0, 255.0) as u8;
+            filter.set_thresholds(thresholds);
+        }
+    }
+}
+
+fn set_position(state: &mut State, dmx: &mut [u8]) {
+    let (tilt, pan) = (state.controls.tilt, state.controls.pan);
+
+    for group in state.config.groups_mut() {
+        group.set_position(tilt, pan, dmx);
+    }
+}
+
+fn set_blackout(state: &mut State, dmx: &mut [u8], blackout: bool) {
+    state.controls.blackout = blackout;
+    println!("[CONTROLS] Blackout: {blackout}");
+
+    // Blackout is applied on every tick, releasing it restores the enabled groups.
+    if !blackout {
+        for group in state.config.groups_mut().filter(|g| g.enabled) {
+            group.write(dmx);
+        }
+    }
+}
+
+fn set_group_enabled(state: &mut State, dmx: &mut [u8], label: &str, enabled: bool) {
+    let groups = state.config.groups_mut().filter(|g| g.label == label);
+
+    let mut found = false;
+    for group in groups {
+        group.set_enabled(dmx, enabled);
+        found = true;
+    }
+
+    if !found {
+        println!("[CONTROLS] Unknown group: {label}");
+    }
+}
@@ -0,0 +1,317 @@
+use serde::Serialize;
+
+use crate::{blaulicht::bl_cues_store, log_error, println};
+
+use super::{
+    clock::Time,
+    config::{Cue, CueList, Scene, SceneGroup},
+    state::{cues::ActiveCue, State},
+};
+
+//
+// Cue list playback.
+// GO starts the next cue of the selected list and fades out the previous one,
+// BACK steps to the cue before, release fades out the current cue and rewinds the list.
+//
+
+// Progress of a fade, 0.0 before it starts and 1.0 once it is done.
+fn progress(elapsed: Time, duration: u32) -> f32 {
+    match duration {
+        0 => (elapsed >= 0) as u8 as f32,
+        duration => (elapsed.inner() as f32 / duration as f32).clamp(0.0, 1.0),
+    }
+}
+
+fn cue<'c>(lists: &'c [CueList], active: &ActiveCue) -> Option<&'c Cue> {
+    lists.get(active.list)?.cues.get(active.index)
+}
+
+fn weight(cue: &Cue, active: &ActiveCue, now: Time) -> f32 {
+    let fade_in = progress(now - active.started - Time::new(cue.delay as i32), cue.fade_in);
+    let fade_out = active
+        .released
+        .map_or(0.0, |released| progress(now - released, cue.fade_out));
+
+    fade_in * (1.0 - fade_out)
+}
+
+fn is_faded_out(cue: &Cue, active: &ActiveCue, now: Time) -> bool {
+    active
+        .released
+        .is_some_and(|released| progress(now - released, cue.fade_out) >= 1.0)
+}
+
+/// Follows cues and drops the ones which have faded out.
+pub fn tick(state: &mut State) {
+    let now = Time::now();
+    let lists = &state.config.cue_lists;
+    let cues = &mut state.cues;
+
+    let done = |active: &Option<ActiveCue>| {
+        active.is_some_and(|active| {
+            cue(lists, &active).map_or(true, |cue| is_faded_out(cue, &active, now))
+        })
+    };
+    if done(&cues.previous) {
+        cues.previous = None;
+    }
+    if done(&cues.current) {
+        cues.current = None;
+    }
+
+    let follow = cues.current.is_some_and(|active| {
+        let Some(cue) = cue(lists, &active) else {
+            return false;
+        };
+        let Some(follow) = cue.follow else {
+            return false;
+        };
+
+        active.released.is_none()
+            && now - active.started >= Time::new((cue.delay + cue.fade_in + follow) as i32)
+    });
+    if follow {
+        go(state);
+    }
+}
+
+/// Scenes of the active cues with their weights, in the order they are layered.
+pub fn scenes(state: &State) -> Vec<(Scene, f32)> {
+    let now = Time::now();
+    let lists = &state.config.cue_lists;
+
+    [state.cues.previous, state.cues.current]
+        .into_iter()
+        .flatten()
+        .filter_map(|active| {
+            let cue = cue(lists, &active)?;
+            let scene = state.config.scenes.iter().find(|s| s.name == cue.scene)?;
+            Some((scene.clone(), weight(cue, &active, now)))
+        })
+        .collect()
+}
+
+fn start(state: &mut State, list: usize, index: usize) {
+    let now = Time::now();
+    let cues = &mut state.cues;
+
+    if let Some(mut current) = cues.current.take() {
+        current.released = current.released.or(Some(now));
+        cues.previous = Some(current);
+    }
+    cues.current = Some(ActiveCue {
+        list,
+        index,
+        started: now,
+        released: None,
+    });
+
+    let list = &state.config.cue_lists[list];
+    let scene = &list.cues[index].scene;
+    println!(
+        "[CUES] <{}>: cue {}/{} <{scene}>",
+        list.name,
+        index + 1,
+        list.cues.len()
+    );
+
+    if !state.config.scenes.iter().any(|s| &s.name == scene) {
+        log_error!("[CUES] Unknown scene <{scene}>");
+    }
+}
+
+// The current cue if it belongs to the selected list and has not been released.
+fn playing(state: &State) -> Option<usize> {
+    state
+        .cues
+        .current
+        .filter(|active| active.list == state.cues.selected && active.released.is_none())
+        .map(|active| active.index)
+}
+
+pub fn go(state: &mut State) {
+    let selected = state.cues.selected;
+    let Some(list) = state.config.cue_lists.get(selected) else {
+        println!("[CUES] No cue list");
+        return;
+    };
+
+    let next = playing(state).map_or(0, |index| index + 1);
+    let next = match next < list.cues.len() {
+        true => next,
+        false if list.looped && !list.cues.is_empty() => 0,
+        false => {
+            println!("[CUES] <{}>: end of the list", list.name);
+            return;
+        }
+    };
+
+    start(state, selected, next);
+}
+
+pub fn back(state: &mut State) {
+    if let Some(index) = playing(state).filter(|index| *index > 0) {
+        start(state, state.cues.selected, index - 1);
+    }
+}
+
+pub fn release(state: &mut State) {
+    if let Some(current) = state.cues.current.as_mut() {
+        current.released = current.released.or(Some(Time::now()));
+    }
+}
+
+/// Steps through the cue lists, the cues of the previous list keep playing.
+pub fn select_relative(state: &mut State, steps: i32) {
+    let count = state.config.cue_lists.len() as i32;
+    if count == 0 {
+        return;
+    }
+
+    state.cues.selected = (state.cues.selected as i32 + steps).rem_euclid(count) as usize;
+    println!(
+        "[CUES] Selected <{}>",
+        state.config.cue_lists[state.cues.selected].name
+    );
+}
+
+/// Stores the state of all groups as a new scene and appends it to the selected list.
+pub fn record(state: &mut State) {
+    let groups: Vec<SceneGroup> = state
+        .config
+        .groups_mut()
+        .filter_map(|group| SceneGroup::capture(&group.label, &group.fixtures))
+        .collect();
+
+    let scenes = &mut state.config.scenes;
+    let name = (scenes.len() + 1..)
+        .map(|n| format!("Scene {n}"))
+        .find(|name| scenes.iter().all(|s| &s.name != name))
+        .expect("unbounded range");
+    scenes.push(Scene {
+        name: name.clone(),
+        groups,
+    });
+
+    let lists = &mut state.config.cue_lists;
+    if lists.is_empty() {
+        lists.push(CueList {
+            name: "Main".to_string(),
+            cues: vec![],
+            looped: false,
+        });
+    }
+    let selected = state.cues.selected.min(lists.len() - 1);
+    lists[selected].cues.push(Cue {
+        scene: name.clone(),
+        fade_in: 0,
+        fade_out: 0,
+        delay: 0,
+        follow: None,
+    });
+
+    println!("[CUES] Recorded <{name}> into <{}>", lists[selected].name);
+    store(state);
+}
+
+#[derive(Serialize)]
+struct CueFile<'c> {
+    scenes: &'c [Scene],
+    cue_lists: &'c [CueList],
+}
+
+// The host writes the cue file, it is read again with the patch.
+fn store(state: &State) {
+    let file = CueFile {
+        scenes: &state.config.scenes,
+        cue_lists: &state.config.cue_lists,
+    };
+
+    match serde_json::to_vec(&file) {
+        Ok(json) => bl_cues_store(&json),
+        Err(err) => log_error!("[CUES] Failed to serialize the cues: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(fade_in: u32, fade_out: u32, delay: u32) -> Cue {
+        Cue {
+            scene: "Scene 1".to_string(),
+            fade_in,
+            fade_out,
+            delay,
+            follow: None,
+        }
+    }
+
+    fn active(started: i32, released: Option<i32>) -> ActiveCue {
+        ActiveCue {
+            list: 0,
+            index: 0,
+            started: Time::new(started),
+            released: released.map(Time::new),
+        }
+    }
+
+    #[test]
+    fn progress_without_duration_snaps() {
+        assert_eq!(progress(Time::new(-1), 0), 0.0);
+        assert_eq!(progress(Time::new(0), 0), 1.0);
+    }
+
+    #[test]
+    fn progress_is_clamped() {
+        assert_eq!(progress(Time::new(-100), 1000), 0.0);
+        assert_eq!(progress(Time::new(250), 1000), 0.25);
+        assert_eq!(progress(Time::new(2000), 1000), 1.0);
+    }
+
+    #[test]
+    fn weight_waits_for_the_delay() {
+        let cue = cue(1000, 0, 500);
+        let active = active(1000, None);
+
+        assert_eq!(weight(&cue, &active, Time::new(1000)), 0.0);
+        assert_eq!(weight(&cue, &active, Time::new(1500)), 0.0);
+        assert_eq!(weight(&cue, &active, Time::new(2000)), 0.5);
+        assert_eq!(weight(&cue, &active, Time::new(3000)), 1.0);
+    }
+
+    #[test]
+    fn weight_fades_out_after_release() {
+        let cue = cue(0, 1000, 0);
+        let active = active(0, Some(2000));
+
+        assert_eq!(weight(&cue, &active, Time::new(2000)), 1.0);
+        assert_eq!(weight(&cue, &active, Time::new(2250)), 0.75);
+        assert_eq!(weight(&cue, &active, Time::new(3000)), 0.0);
+    }
+
+    #[test]
+    fn release_during_fade_in() {
+        let cue = cue(1000, 1000, 0);
+        let active = active(0, Some(500));
+
+        assert_eq!(weight(&cue, &active, Time::new(500)), 0.5);
+        assert_eq!(weight(&cue, &active, Time::new(1000)), 0.5);
+        assert_eq!(weight(&cue, &active, Time::new(1500)), 0.0);
+    }
+
+    #[test]
+    fn faded_out_once_released_and_done() {
+        let cue = cue(0, 1000, 0);
+
+        assert!(!is_faded_out(&cue, &active(0, None), Time::new(10_000)));
+        assert!(!is_faded_out(&cue, &active(0, Some(0)), Time::new(999)));
+        assert!(is_faded_out(&cue, &active(0, Some(0)), Time::new(1000)));
+    }
+
+    #[test]
+    fn faded_out_at_release_without_fade() {
+        let cue = cue(1000, 0, 0);
+        assert!(is_faded_out(&cue, &active(0, Some(0)), Time::new(0)));
+    }
+}
@@ -1,51 +1,35 @@
-// use crate::{blaulicht::TickInput, elapsed, printc, user::midi::DIM_BRIGHTNESS_STAGE};
-
-// use super::state::State;
-
-// pub const DIMMERS_STAGE: [usize; 12] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
-
-// pub const DIMMERS_NOT_STAGE: [usize; 2] = [
-//     // 13, // stage
-//     // 14, // stage
-//     // 15, // nothing
-//     // 16, // nothing
-//     // 17, // stage
-//     // 18, // stage
-//     // 19, // nothing.
-//     20, 21,
-// ];
-
-// pub fn tick(dmx: &mut [u8], input: TickInput, state: &mut State) {
-//     if elapsed!(input, state.last_beat_time) <= 500 && state.animation.strobe.controls.strobe_enabled {
-//         // Dim during strobe.
-//         state.animation.dim.stage_real_brightness = 40;
-//     } else {
-//         state.animation.dim.stage_real_brightness = state.animation.dim.controls.brightness_stage;
-//     }
-
-//     for addr in DIMMERS_NOT_STAGE {
-//         dmx[addr] = state.animation.dim.controls.brightness_other;
-//     }
-
-//     for addr in DIMMERS_STAGE {
-//         dmx[addr] = state.animation.dim.stage_real_brightness;
-//     }
-// }
-
-// pub fn set_brightness_stage(state: &mut State, value: u8) {
-//     state.animation.dim.controls.brightness_stage = value;
-//     printc!(
-//         DIM_BRIGHTNESS_STAGE.0,
-//         DIM_BRIGHTNESS_STAGE.1,
-//         "D.BRI {value:03}",
-//     );
-// }
-
-// pub fn set_brightness_other(state: &mut State, value: u8) {
-//     state.animation.dim.controls.brightness_other = value;
-//     printc!(
-//         DIM_BRIGHTNESS_STAGE.0,
-//         DIM_BRIGHTNESS_STAGE.1,
-//         "D.BRI {value:03}",
-//     );
-// }
+use super::{beat::DropState, clock::Time, state::State};
+
+//
+// Dimmer groups, ducked while the strobes fire so that the flashes stand out.
+//
+
+const DUCK_HOLD: Time = Time::new(500);
+
+pub fn tick(state: &mut State, dmx: &mut [u8]) {
+    let controls = state.controls;
+    let now = Time::now();
+
+    if controls.dimmer_strobe_duck && strobe_is_active(state) {
+        state.dimmer.ducked_until = now + DUCK_HOLD;
+    }
+
+    let brightness = match now < state.dimmer.ducked_until {
+        true => controls.dimmer_duck_level.min(controls.dimmer_brightness),
+        false => controls.dimmer_brightness,
+    };
+
+    for group in state.config.dimmer_groups.iter_mut() {
+        if !group.enabled {
+            group.blackout(dmx);
+            continue;
+        }
+
+        group.set_alpha(brightness, dmx);
+    }
+}
+
+fn strobe_is_active(state: &State) -> bool {
+    !matches!(state.drop_filter.state, DropState::None)
+        && state.config.strobe_groups.iter().any(|g| g.group.enabled)
+}
@@ -1,32 +1,34 @@
-// use crate::{
-//     blaulicht::{self, TickInput},
-//     printc, println,
-//     user::{
-//         config::{Config, Light, StrobeGroup},
-//         dim, logo,
-//         midi::{
-//             self, MOOD_ALTERNATING, MOOD_FORCE_TOGGLE, MOOD_ON_BEAT, MOOD_PALETTE_ALL,
-//             MOOD_PALETTE_CYAN_MAGENTA, MOOD_PALETTE_ORANGE_BLUE, MOOD_PALETTE_WHITE, MOOD_SNAKE,
-//             MOOD_SYNCED, STROBE_ALTERNATING, STROBE_AUTOMATION_TOGGLE, STROBE_ON_BEAT,
-//             STROBE_SYNCED, STROBE_TOGGLE,
-//         },
-//         mood::{self},
-//         strobe::{self},
-//         video,
-//     },
-// };
-
 use crate::{
-    blaulicht::TickInput,
-    println,
-    user::config::{Config, Light, MovingHead, StrobeGroup},
+    blaulicht::{self, TickInput},
+    log_error, println,
+    user::config::Config,
 };
 
 use super::{
-    config::FixtureGroup,
+    beat::{BeatFilter, DropFilter},
+    clock::Clocks,
     state::{strobe::StrobeState, State},
 };
 
+fn load_patch() -> Config {
+    let Some(patch) = blaulicht::bl_patch() else {
+        println!("[SETUP] No patch from the host, using the builtin one.");
+        return Config::builtin();
+    };
+
+    match serde_json::from_slice::<Config>(&patch) {
+        Ok(mut config) => {
+            config.resolve_profiles();
+            println!("[SETUP] Loaded patch.");
+            config
+        }
+        Err(err) => {
+            log_error!("[SETUP] Invalid patch, using the builtin one: {err}");
+            Config::builtin()
+        }
+    }
+}
+
 fn remove_old_state(state: &mut State, dmx: &mut [u8]) {
     state.reset();
 
@@ -47,23 +49,10 @@ pub fn initialize(state: &mut State, input: TickInput, dmx: &mut [u8]) {
     // 0-initialize.
     *state = State::default();
 
-    state.config = Config {
-        strobe_groups: vec![StrobeGroup::new(
-            true,
-            FixtureGroup::new(
-                "Primary Strobe".into(),
-                vec![(MovingHead::MartinMacAura.into(), 40).into()],
-            ),
-        )],
-        mood_groups: vec![FixtureGroup::new(
-            "Primary Mood".into(),
-            vec![(Light::LEDPartyTCLSpot.into(), 20).into()],
-        )],
-        dimmer_groups: vec![FixtureGroup::new(
-            "Primary Dimmer".into(),
-            vec![(Light::Generic4ChanWithAlpha.into(), 30).into()],
-        )],
-    };
+    state.config = load_patch();
+    state.clocks = Clocks::new(&state.config.clocks);
+    state.beat_filter = BeatFilter::new(&state.config.beat);
+    state.drop_filter = DropFilter::new(&state.config.beat);
 
     // Initialize the control surface.
     // blaulicht::bl_controls_config(8, 8);
@@ -143,9 +132,6 @@ pub fn initialize(state: &mut State, input: TickInput, dmx: &mut [u8]) {
 
     // // AAAA
 
-    // midi::set_left_fader_target_alt(state, false);
-    // midi::set_right_fader_target_alt(state, false);
-
     // //
     // // Video
     // //
@@ -180,10 +166,5 @@ pub fn initialize(state: &mut State, input: TickInput, dmx: &mut [u8]) {
 
     // logo::set_mode(state, LogoMode::Normal, true);
 
-    // midi::toggle_fogger(state, false);
-    // midi::set_fogger_intensity(state, 0);
-
-    // midi::toggle_crossfader_input(state, false);
-
     println!("[SETUP] Running...");
 }
@@ -1,17 +1,20 @@
 mod beat;
 mod clock;
 mod config;
+mod controls;
+mod cues;
 mod dim;
 mod fogger;
 mod init;
 mod logo;
-mod midi;
 mod mood;
+mod output;
+mod palette;
 mod state;
 mod strobe;
 mod video;
 
-use clock::Time;
+use clock::{Time, MOOD_CLOCK, STROBE_CLOCK};
 use state::State;
 
 #[deny(unsafe_op_in_unsafe_fn)]
@@ -19,7 +22,7 @@ use state::State;
 use crate::blaulicht::{
     self, elapsed, midi,
     prelude::{printc, println},
-    MidiEvent, TickInput,
+    ControlEvent, MidiEvent, TickInput,
 };
 
 static mut GLOBAL_TIME: Time = Time::new(0);
@@ -47,7 +50,13 @@ fn on_setup_complete(state: &mut State) {
     println!("filter: {:?}", state.beat_filter.sensitivity)
 }
 
-pub fn run(input: TickInput, dmx: &mut [u8], data: *mut u8, midi: &[MidiEvent]) {
+pub fn run(
+    input: TickInput,
+    dmx: &mut [u8],
+    data: *mut u8,
+    _midi: &[MidiEvent],
+    controls: &[ControlEvent],
+) {
     // STATE
     let state_ptr = data.cast::<State>();
     let state = unsafe { &mut *state_ptr };
@@ -64,8 +73,6 @@ pub fn run(input: TickInput, dmx: &mut [u8], data: *mut u8, midi: &[MidiEvent])
     //     input.time_between_beats_millis = 333;
     // }
 
-    // midi::tick(state, dmx, midi, input);
-
     //
     // Setup
     //
@@ -95,6 +102,12 @@ pub fn run(input: TickInput, dmx: &mut [u8], data: *mut u8, midi: &[MidiEvent])
     }
     state.was_initial = false;
 
+    //
+    // Controls.
+    //
+
+    controls::tick(state, dmx, controls);
+
     //
     // Mood.
     //
@@ -105,10 +118,7 @@ pub fn run(input: TickInput, dmx: &mut [u8], data: *mut u8, midi: &[MidiEvent])
     // Clocks.
     //
 
-    state.clock.tick(input);
-    let activated = state.clock.activate(|activation| {
-        // println!("Clock: activation: {:?}", activation)
-    });
+    state.clocks.tick(input);
 
     //
     // Beat filters.
@@ -116,12 +126,14 @@ pub fn run(input: TickInput, dmx: &mut [u8], data: *mut u8, midi: &[MidiEvent])
 
     state.beat_filter.tick(input);
 
-    let is_on_beat = activated && state.beat_filter.is_open();
+    let is_on_beat = state.clocks.activated(STROBE_CLOCK) && state.beat_filter.is_open();
 
     let beat_filter_out = state.beat_filter.is_open() || state.beat_filter.is_open_first_time();
 
     state.drop_filter.beat_filter_in(beat_filter_out);
 
+    palette::tick(state, state.clocks.activated(MOOD_CLOCK));
+
     // if state.beat_filter.is_open_first_time() {
     //     strobe::tick_on_beat(dmx, input, state);
     // }
@@ -134,9 +146,31 @@ pub fn run(input: TickInput, dmx: &mut [u8], data: *mut u8, midi: &[MidiEvent])
         strobe::tick_off_beat(dmx, input, state);
     }
 
+    //
+    // Dimmers, after the strobes so that they duck on the same tick.
+    //
+
+    dim::tick(state, dmx);
+
+    //
+    // Cues and effects, on top of the state written by the features above.
+    //
+
+    cues::tick(state);
+    output::tick(state, dmx);
+
+    //
+    // Grand master, overrides everything written above.
+    //
+
+    if state.controls.blackout {
+        for group in state.config.groups_mut() {
+            group.blackout(dmx);
+        }
+    }
+
     // fogger::tick(state, dmx);
     // strobe::tick(state, dmx, input);
-    // dim::tick(dmx, input, state);
     // video::tick(state, input, false);
     // logo::tick(state, input);
 
@@ -1,23 +1,10 @@
 // use crate::{
 //     blaulicht::{self, bl_controls_set, TickInput},
 //     color, elapsed, printc, smidi,
-//     user::{
-//         midi::{
-//             DDJ_RIGHT_BEAT_JMP_0_0, DDJ_RIGHT_BEAT_JMP_1_0, DDJ_RIGHT_BEAT_JMP_2_0,
-//             DDJ_RIGHT_BEAT_LOOP_0_0, DDJ_RIGHT_BEAT_LOOP_1_0, DDJ_RIGHT_BEAT_LOOP_2_0,
-//             DDJ_RIGHT_BEAT_LOOP_3_0, DDJ_RIGHT_BEAT_SYNC, DDJ_RIGHT_CUE, DDJ_RIGHT_RELOOP,
-//             MOOD_ALTERNATING, MOOD_BEAT_ANIM, MOOD_BRIGHTNESS, MOOD_PALETTE_ALL,
-//             MOOD_PALETTE_CYAN_MAGENTA, MOOD_PALETTE_ORANGE_BLUE, MOOD_PALETTE_WHITE, MOOD_SNAKE,
-//             MOOD_SPEED, MOOD_SPEED_BEAT, MOOD_SYNCED,
-//         },
-//         strobe::parse_speed_multiplier,
-//     },
+//     user::strobe::parse_speed_multiplier,
 // };
 
-// use super::{
-//     midi::{MOOD_FORCE_TOGGLE, MOOD_ON_BEAT},
-//     state::{MoodAnimation, MoodColorPalette, State},
-// };
+// use super::state::{MoodAnimation, MoodColorPalette, State};
 // use map_range::MapRange;
 
 // fn animation_step(state: &mut State, input: TickInput) {
@@ -147,9 +134,9 @@
 
 use map_range::MapRange;
 
-use crate::{blaulicht::TickInput, color};
+use crate::{blaulicht::TickInput, color::Color};
 
-use super::state::State;
+use super::{palette, state::State};
 
 pub fn tick_without_beat(state: &mut State, dmx: &mut [u8], input: TickInput) {
     // if !state.animation.mood.controls.animation_on_beat {
@@ -159,11 +146,11 @@ pub fn tick_without_beat(state: &mut State, dmx: &mut [u8], input: TickInput) {
     // let strobe_is_overriding = (state.animation.strobe.strobe_activate_time.is_some()
     //     && state.animation.strobe.controls.strobe_enabled);
 
-    let master_brightness = 255;
+    let master_brightness = state.controls.mood_brightness;
 
     let strobe_is_overriding = false;
     let force_light_on = false;
-    let audio_controls_brightness = true;
+    let audio_controls_brightness = state.controls.mood_audio_brightness;
 
     let brightness = match (
         strobe_is_overriding,
@@ -177,17 +164,18 @@ pub fn tick_without_beat(state: &mut State, dmx: &mut [u8], input: TickInput) {
         }
     };
 
-    // Get HSV based on counter and current color scheme.
-    let hsv = 0;
-    let color = color::hsv_to_rgb(hsv);
+    let config = &mut state.config;
 
     // Iterate over mood groups.
-    for group in state.config.mood_groups.iter_mut() {
+    for group in config.mood_groups.iter_mut() {
         if !group.enabled {
             group.blackout(dmx);
             continue;
         }
 
+        let color = palette::current(&config.palettes, &state.palette, group.palette.as_deref())
+            .map_or(Color::white(), |palette| palette.color_at(state.palette.step));
+
         group.set_alpha(brightness, dmx);
         group.set_color(color, dmx);
     }
@@ -0,0 +1,44 @@
+use crate::effect::EffectClock;
+
+use super::{
+    clock::{Time, MAIN_CLOCK},
+    cues,
+    state::State,
+};
+
+//
+// Layers the scenes of the active cues, the sequences and the effects onto all enabled groups.
+//
+
+pub fn tick(state: &mut State, dmx: &mut [u8]) {
+    let scenes = cues::scenes(state);
+
+    // Groups which are not written on every tick keep the last look of a cue otherwise.
+    let restore = scenes.is_empty() && state.cues.was_active;
+    state.cues.was_active = !scenes.is_empty();
+
+    let controls = state.controls;
+    let clocks = &state.clocks;
+
+    for group in state.config.groups_mut().filter(|g| g.enabled) {
+        let clock = EffectClock {
+            millis: Time::now().inner(),
+            beats: clocks
+                .get(group.clock.as_deref().unwrap_or(MAIN_CLOCK))
+                .beat_position(),
+        };
+        let sequence = controls.sequences_enabled.then_some(clock);
+        let effects = controls.effects_enabled.then_some(clock);
+
+        if restore {
+            group.write(dmx);
+        }
+
+        let layers: Vec<_> = scenes
+            .iter()
+            .filter_map(|(scene, weight)| Some((scene.group(&group.label)?, *weight)))
+            .collect();
+
+        group.write_output(&layers, sequence, effects, dmx);
+    }
+}
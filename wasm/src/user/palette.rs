@@ -0,0 +1,85 @@
+use crate::println;
+
+use super::{
+    clock::Time,
+    config::{Palette, PaletteCycle},
+    state::{palette::PaletteState, State},
+};
+
+//
+// Palette selection and cycling.
+//
+
+pub fn tick(state: &mut State, beat: bool) {
+    let Some(palette) = state.config.palettes.get(state.palette.selected) else {
+        return;
+    };
+
+    let now = Time::now();
+    let palette_state = &mut state.palette;
+
+    let advance = match palette.cycle {
+        PaletteCycle::Off => false,
+        PaletteCycle::Beats(beats) => {
+            if beat {
+                palette_state.beats += 1;
+            }
+            palette_state.beats >= beats.max(1)
+        }
+        PaletteCycle::Millis(millis) => now - palette_state.last_step >= Time::new(millis as i32),
+    };
+
+    if advance {
+        palette_state.step += 1;
+        palette_state.beats = 0;
+        palette_state.last_step = now;
+    }
+}
+
+/// The palette pinned to a group if it exists, otherwise the selected one.
+pub fn current<'p>(
+    palettes: &'p [Palette],
+    state: &PaletteState,
+    pinned: Option<&str>,
+) -> Option<&'p Palette> {
+    pinned
+        .and_then(|name| palettes.iter().find(|palette| palette.name == name))
+        .or_else(|| palettes.get(state.selected))
+}
+
+pub fn select(state: &mut State, index: usize) {
+    let count = state.config.palettes.len();
+    if count == 0 {
+        return;
+    }
+
+    state.palette = PaletteState {
+        selected: index % count,
+        ..PaletteState::default()
+    };
+    println!(
+        "[PALETTE] Selected <{}>",
+        state.config.palettes[state.palette.selected].name
+    );
+}
+
+/// Steps through the palettes, wrapping around in both directions.
+pub fn select_relative(state: &mut State, steps: i32) {
+    let count = state.config.palettes.len() as i32;
+    if count == 0 {
+        return;
+    }
+
+    let index = (state.palette.selected as i32 + steps).rem_euclid(count);
+    select(state, index as usize);
+}
+
+pub fn select_name(state: &mut State, name: &str) -> bool {
+    match state.config.palettes.iter().position(|p| p.name == name) {
+        Some(index) => {
+            select(state, index);
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,40 @@
+//
+// Values set through named controls of the host MIDI mapping.
+//
+
+#[derive(Debug, Clone, Copy)]
+pub struct Controls {
+    // Grand master, all groups are dark while set.
+    pub blackout: bool,
+    pub effects_enabled: bool,
+    pub sequences_enabled: bool,
+    pub strobe_brightness: u8,
+    pub mood_brightness: u8,
+    // Whether the volume drives the mood brightness.
+    pub mood_audio_brightness: bool,
+    pub dimmer_brightness: u8,
+    // Whether the dimmers are lowered to `dimmer_duck_level` while strobing.
+    pub dimmer_strobe_duck: bool,
+    pub dimmer_duck_level: u8,
+    // Moving head position in degrees from the center.
+    pub tilt: f32,
+    pub pan: f32,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Self {
+            blackout: false,
+            effects_enabled: true,
+            sequences_enabled: true,
+            strobe_brightness: 255,
+            mood_brightness: 255,
+            mood_audio_brightness: true,
+            dimmer_brightness: 255,
+            dimmer_strobe_duck: true,
+            dimmer_duck_level: 40,
+            tilt: 0.0,
+            pan: 0.0,
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use crate::user::clock::Time;
+
+/// A cue which is playing or fading out.
+#[derive(Debug, Clone, Copy)]
+pub struct ActiveCue {
+    // Index into the cue lists of the config.
+    pub list: usize,
+    pub index: usize,
+    pub started: Time,
+    // The cue fades out from then on.
+    pub released: Option<Time>,
+}
+
+#[derive(Debug, Default)]
+pub struct CueState {
+    // The list GO and BACK step through.
+    pub selected: usize,
+    pub current: Option<ActiveCue>,
+    // Fades out on top of which the current cue fades in.
+    pub previous: Option<ActiveCue>,
+    // Whether scenes were layered on the last tick, the groups are restored once they end.
+    pub was_active: bool,
+}
@@ -1,11 +1,7 @@
-#[derive(Debug, Clone, Copy)]
-pub struct Dimmer {
-    pub stage_real_brightness: u8,
-    pub controls: DimmerControls,
-}
+use crate::user::clock::Time;
 
-#[derive(Debug, Clone, Copy)]
-pub struct DimmerControls {
-    pub brightness_stage: u8,
-    pub brightness_other: u8,
-}
\ No newline at end of file
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DimmerState {
+    // Dimmers stay ducked until then, bridges the gaps between strobe flashes.
+    pub ducked_until: Time,
+}
@@ -1,15 +1,22 @@
+use controls::Controls;
+use cues::CueState;
+use dimmer::DimmerState;
+use palette::PaletteState;
 use logo::LogoMode;
 
 use super::{
-    beat::{BeatFilter, DropFilter, FilterSensitivity},
-    clock::BeatClock,
+    beat::{BeatFilter, BeatSettings, DropFilter},
+    clock::Clocks,
     config::Config,
 };
 
 pub mod animation;
+pub mod controls;
+pub mod cues;
 pub mod dimmer;
 pub mod logo;
 pub mod mood;
+pub mod palette;
 pub mod strobe;
 pub mod video;
 
@@ -30,10 +37,14 @@ pub struct State {
 
     // pub logo_mode: LogoMode,
 
-    // TODO: utilize multiple clocks instead.
-    pub clock: BeatClock,
+    pub clocks: Clocks,
     pub beat_filter: BeatFilter,
     pub drop_filter: DropFilter,
+
+    pub controls: Controls,
+    pub dimmer: DimmerState,
+    pub palette: PaletteState,
+    pub cues: CueState,
 }
 
 impl Default for State {
@@ -121,9 +132,13 @@ impl Default for State {
             //         },
             //     },
             // },
-            clock: BeatClock::new(),
-            beat_filter: BeatFilter::new(FilterSensitivity::Mid),
-            drop_filter: DropFilter::new(),
+            clocks: Clocks::default(),
+            beat_filter: BeatFilter::new(&BeatSettings::default()),
+            drop_filter: DropFilter::new(&BeatSettings::default()),
+            controls: Controls::default(),
+            dimmer: DimmerState::default(),
+            palette: PaletteState::default(),
+            cues: CueState::default(),
         }
     }
 }
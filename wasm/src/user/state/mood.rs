@@ -1,5 +1,3 @@
-use std::ops::Range;
-
 use super::animation::AnimationAlternating;
 
 #[derive(Debug)]
@@ -20,30 +18,9 @@ pub struct MoodControls {
     pub force: bool,
     pub brightness: u8,
     pub animation: MoodAnimation,
-    pub color_palette: MoodColorPalette,
     pub animation_on_beat: bool,
 }
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum MoodColorPalette {
-    All,
-    CyanMagenta,
-    OrangeBlue,
-    White,
-}
-
-impl MoodColorPalette {
-    pub fn to_hsv_ranges(&self) -> Vec<Range<isize>> {
-        match self {
-            Self::All => vec![0..361],
-            // TODO: fix this kind of animation.
-            Self::CyanMagenta => vec![180..180, 345..345],
-            Self::OrangeBlue => vec![240..241, 40..41],
-            Self::White => vec![-1..-1],
-        }
-    }
-}
-
 #[derive(Debug, PartialEq, Clone)]
 pub enum MoodAnimation {
     Synced,
@@ -0,0 +1,11 @@
+use crate::user::clock::Time;
+
+#[derive(Debug, Default)]
+pub struct PaletteState {
+    // Index into the palettes of the config.
+    pub selected: usize,
+    // Advanced by the cycle of the selected palette, shared by all palettes.
+    pub step: u64,
+    pub beats: u32,
+    pub last_step: Time,
+}
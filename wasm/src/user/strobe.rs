@@ -7,22 +7,12 @@ use crate::{color::Color, println};
 //     elapsed, printc, smidi,
 //     user::{
 //         logo,
-//         midi::{
-//             DDJ_LEFT_BEAT_JMP_0_0, DDJ_LEFT_BEAT_JMP_0_1, DDJ_LEFT_BEAT_JMP_1_0,
-//             DDJ_LEFT_BEAT_JMP_1_1, DDJ_LEFT_BEAT_JMP_2_1, DDJ_LEFT_BEAT_SYNC, DDJ_LEFT_CUE,
-//             DDJ_LEFT_CUE_ROUND, DDJ_LEFT_RELOOP, MOVING_HEAD_TILT_ANIM_SPEED, STROBE_ALTERNATING,
-//             STROBE_BRIGHTNESS, STROBE_MOVING_HEAD_GROUP, STROBE_ON_OFF_TIME, STROBE_PANEL_GROUP,
-//             STROBE_REMAINING_TIME, STROBE_SPEED, STROBE_STAGE_GROUP, STROBE_SYNCED,
-//         },
 //         state::{LogoMode, StrobeAnimation, StrobeAnimationAlternatingState, StrobeControls},
 //         video,
 //     },
 // };
 
-// use super::{
-//     midi::{STROBE_AUTOMATION_TOGGLE, STROBE_ON_BEAT, STROBE_TOGGLE},
-//     println, state, State,
-// };
+// use super::{println, state, State};
 
 // const STROBE_RESET_TIME_MILLIS: u32 = 1000;
 // const STROBE_ACTIVE_MILLIS: u32 = 5000;
@@ -279,14 +269,14 @@ use crate::blaulicht::TickInput;
 use super::{beat::DropState, config, state::State};
 
 pub fn tick_on_beat(dmx: &mut [u8], input: TickInput, state: &mut State) {
-    let master_brightness = 255;
+    let master_brightness = state.controls.strobe_brightness;
 
     match state.drop_filter.state {
         DropState::None => unreachable!(),
         DropState::Begin => {
             println!("drop begin: {}", input.time);
             for g in state.config.strobe_groups.iter_mut() {
-                if !g.supports_burst {
+                if !g.supports_burst || !g.group.enabled {
                     continue;
                 }
 
@@ -297,6 +287,10 @@ pub fn tick_on_beat(dmx: &mut [u8], input: TickInput, state: &mut State) {
         DropState::Main => {
             println!("drop main: {}", input.time);
             for g in state.config.strobe_groups.iter_mut() {
+                if !g.group.enabled {
+                    continue;
+                }
+
                 g.state.strobe_is_white_state = true;
                 g.set_alpha(master_brightness, dmx);
             }
@@ -5,14 +5,6 @@
 //     elapsed, printc, println, smidi,
 //     user::{
 //         logo,
-//         midi::{
-//             DDJ_FX_ON, DDJ_MASTER_CUE, DDJ_RIGHT_SAMPLER_0_0, DDJ_RIGHT_SAMPLER_0_1,
-//             DDJ_RIGHT_SAMPLER_1_0, DDJ_RIGHT_SAMPLER_1_1, DDJ_RIGHT_SAMPLER_2_0,
-//             DDJ_RIGHT_SAMPLER_2_1, DDJ_RIGHT_SAMPLER_3_0, DDJ_RIGHT_SAMPLER_3_1, VIDEO_BRI,
-//             VIDEO_FILE_1, VIDEO_FILE_2, VIDEO_FILE_3, VIDEO_FILE_4, VIDEO_FILE_5, VIDEO_FILE_6,
-//             VIDEO_FILE_7, VIDEO_FILE_8, VIDEO_FILE_9, VIDEO_FRY, VIDEO_ROTATE, VIDEO_SPEED,
-//             VIDEO_SPEED_SYNC,
-//         },
 //         strobe,
 //     },
 // };